@@ -0,0 +1,28 @@
+// Compares the const-generic N-D integrator with the slice-based one on a cheap 2D integrand,
+// where the per-sample overhead dominates. Run with `cargo bench --bench nd_array`
+use monte_carlo_integration_experiment::multidimensional::{integrate_nd, integrate_nd_array};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SAMPLE_COUNT: usize = 10_000_000;
+const REPETITIONS: u32 = 5;
+
+fn time(f: impl Fn() -> f64) -> Duration {
+    black_box(f());
+    let start = Instant::now();
+    for _ in 0..REPETITIONS {
+        black_box(f());
+    }
+    start.elapsed() / REPETITIONS
+}
+
+fn main() {
+    let array =
+        time(|| integrate_nd_array(|[x, y]| x * y, [0.0, 0.0], [1.0, 1.0], SAMPLE_COUNT).unwrap());
+    let slice =
+        time(|| integrate_nd(|p| p[0] * p[1], &[0.0, 0.0], &[1.0, 1.0], SAMPLE_COUNT).unwrap());
+
+    println!("integrate_nd_array: {:?} per run", array);
+    println!("integrate_nd:       {:?} per run", slice);
+    println!("speedup: {:.2}x", slice.as_secs_f64() / array.as_secs_f64());
+}
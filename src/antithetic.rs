@@ -0,0 +1,224 @@
+use crate::error::{check_integration, Result};
+use crate::integration::monte_carlo_integration;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Online co-moments of the pairs (f(x), f(a + b - x)), updated with the same Welford-style
+// recurrences as VarianceEstimator
+#[derive(Copy, Clone, Debug, Default)]
+struct PairStatistics {
+    count: i64,
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    m2_y: f64,
+    co_moment: f64,
+}
+
+impl PairStatistics {
+    fn add_pair(&mut self, x: f64, y: f64) {
+        self.count += 1;
+        let n = self.count as f64;
+        let delta_x = x - self.mean_x;
+        let delta_y = y - self.mean_y;
+        self.mean_x += delta_x / n;
+        self.mean_y += delta_y / n;
+        self.m2_x += delta_x * (x - self.mean_x);
+        self.m2_y += delta_y * (y - self.mean_y);
+        self.co_moment += delta_x * (y - self.mean_y);
+    }
+
+    fn merge(lhs: Self, rhs: Self) -> Self {
+        if rhs.count == 0 {
+            return lhs;
+        }
+        if lhs.count == 0 {
+            return rhs;
+        }
+
+        let left = lhs.count as f64;
+        let right = rhs.count as f64;
+        let count = lhs.count + rhs.count;
+        let n = count as f64;
+        let delta_x = rhs.mean_x - lhs.mean_x;
+        let delta_y = rhs.mean_y - lhs.mean_y;
+
+        Self {
+            count,
+            mean_x: (left * lhs.mean_x + right * rhs.mean_x) / n,
+            mean_y: (left * lhs.mean_y + right * rhs.mean_y) / n,
+            m2_x: lhs.m2_x + rhs.m2_x + delta_x * delta_x * left * right / n,
+            m2_y: lhs.m2_y + rhs.m2_y + delta_y * delta_y * left * right / n,
+            co_moment: lhs.co_moment + rhs.co_moment + delta_x * delta_y * left * right / n,
+        }
+    }
+
+    // Pearson correlation, or 0 if either side is constant
+    fn correlation(&self) -> f64 {
+        let denominator = (self.m2_x * self.m2_y).sqrt();
+        if denominator > 0.0 {
+            self.co_moment / denominator
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct AntitheticEstimate {
+    pub estimate: f64,
+    // Pearson correlation between f(x) and f(a + b - x). Close to -1 means antithetic sampling
+    // removes most of the variance, close to 0 means it does not help, and positive values mean
+    // it is worse than plain sampling
+    pub correlation: f64,
+}
+
+fn sample_pairs(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    pair_count: usize,
+) -> PairStatistics {
+    (0..pair_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            (f(x), f(a + b - x))
+        })
+        .fold(PairStatistics::default, |mut stats, (x, y)| {
+            stats.add_pair(x, y);
+            stats
+        })
+        .reduce(PairStatistics::default, PairStatistics::merge)
+}
+
+// Estimate integral from a to b of f(x) dx with antithetic variates: each uniform sample x is
+// paired with its mirror a + b - x, which cancels out most of the noise for monotone integrands
+pub fn integrate_antithetic(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    pair_count: usize,
+) -> Result<f64> {
+    Ok(integrate_antithetic_with_correlation(f, a, b, pair_count)?.estimate)
+}
+
+// Same as `integrate_antithetic`, but also reports how correlated the mirrored pairs were
+pub fn integrate_antithetic_with_correlation(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    pair_count: usize,
+) -> Result<AntitheticEstimate> {
+    check_integration(a, b, pair_count)?;
+    let stats = sample_pairs(f, a, b, pair_count);
+
+    Ok(AntitheticEstimate {
+        estimate: (stats.mean_x + stats.mean_y) / 2.0 * (b - a),
+        correlation: stats.correlation(),
+    })
+}
+
+// Pilot correlation below which `integrate_antithetic_adaptive` pairs its samples. A pair costs
+// two evaluations and has variance (1 + correlation) / 2 times that of two plain samples, so any
+// negative correlation helps; the margin keeps pilot noise around 0 from picking it by chance
+const ANTITHETIC_MAX_CORRELATION: f64 = -0.1;
+
+// Sampling chosen by `integrate_antithetic_adaptive`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AntitheticChoice {
+    Antithetic,
+    Plain,
+}
+
+// Estimate integral from a to b of f(x) dx with `sample_count` evaluations of f, using antithetic
+// variates only where they help. A pilot of `pilot_pairs` mirrored pairs, drawn on top of the
+// budget, estimates the correlation between f(x) and f(a + b - x). Antithetic sampling is used if
+// it is clearly negative, as for monotone integrands, and plain sampling otherwise, e.g. for
+// integrands symmetric around the midpoint, where the mirrored sample is just a copy. Returns the
+// estimate and the sampling used. A single sample cannot be paired, and is always plain
+pub fn integrate_antithetic_adaptive(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    pilot_pairs: usize,
+) -> Result<(f64, AntitheticChoice)> {
+    check_integration(a, b, sample_count)?;
+    let pilot = sample_pairs(&f, a, b, pilot_pairs);
+    if sample_count >= 2 && pilot.count > 1 && pilot.correlation() < ANTITHETIC_MAX_CORRELATION {
+        Ok((
+            integrate_antithetic(f, a, b, sample_count / 2)?,
+            AntitheticChoice::Antithetic,
+        ))
+    } else {
+        Ok((
+            monte_carlo_integration(f, a, b, sample_count)?.integral,
+            AntitheticChoice::Plain,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_antithetic_linear_is_exact() {
+        // f(x) + f(1 - x) = 1 for f(x) = x, so every pair averages to the exact answer
+        let result = integrate_antithetic_with_correlation(|x| x, 0.0, 1.0, 1000).unwrap();
+        assert_approx_eq!(result.estimate, 0.5, 1e-9);
+        assert_approx_eq!(result.correlation, -1.0, 1e-9);
+    }
+
+    #[test]
+    fn test_antithetic_correlation_sign() {
+        let monotone =
+            integrate_antithetic_with_correlation(|x| x.exp(), 0.0, 1.0, 10_000).unwrap();
+        assert!(monotone.correlation < -0.9);
+        assert_approx_eq!(monotone.estimate, std::f64::consts::E - 1.0, 0.01);
+
+        // Symmetric around the midpoint, so the mirrored sample is the same value
+        let symmetric =
+            integrate_antithetic_with_correlation(|x| (x - 0.5) * (x - 0.5), 0.0, 1.0, 10_000)
+                .unwrap();
+        assert_approx_eq!(symmetric.correlation, 1.0, 1e-9);
+    }
+
+    #[test]
+    fn test_adaptive_picks_antithetic_for_monotone() {
+        let (estimate, choice) =
+            integrate_antithetic_adaptive(f64::exp, 0.0, 1.0, 100_000, 100).unwrap();
+        assert_eq!(choice, AntitheticChoice::Antithetic);
+        assert_approx_eq!(estimate, std::f64::consts::E - 1.0, 0.01);
+    }
+
+    #[test]
+    fn test_adaptive_picks_plain_for_symmetric() {
+        let f = |x: f64| (x - 0.5) * (x - 0.5);
+        let (estimate, choice) = integrate_antithetic_adaptive(f, 0.0, 1.0, 100_000, 100).unwrap();
+        assert_eq!(choice, AntitheticChoice::Plain);
+        assert_approx_eq!(estimate, 1.0 / 12.0, 0.02);
+    }
+
+    #[test]
+    fn test_pair_statistics_merge() {
+        let mut all = PairStatistics::default();
+        let mut lhs = PairStatistics::default();
+        let mut rhs = PairStatistics::default();
+        for i in 0..50 {
+            let (x, y) = (i as f64, ((i * 7) % 11) as f64);
+            all.add_pair(x, y);
+            if i < 20 {
+                lhs.add_pair(x, y);
+            } else {
+                rhs.add_pair(x, y);
+            }
+        }
+
+        let merged = PairStatistics::merge(lhs, rhs);
+        assert_approx_eq!(merged.correlation(), all.correlation());
+        assert_approx_eq!(merged.mean_y, all.mean_y);
+    }
+}
@@ -0,0 +1,146 @@
+// Variance of the mean for autocorrelated samples, e.g. from MCMC, using the blocking method
+// H. Flyvbjerg and H. G. Petersen, "Error estimates on averages of correlated data", 1989
+//
+// Samples are averaged into blocks of 1, 2, 4, ... consecutive values, one level per block size.
+// Block means of large enough blocks are nearly independent, so the variance of the mean
+// estimated from them grows with the block size until it plateaus at the correct value
+use crate::variance_estimator::VarianceEstimator;
+
+// Fewest blocks a level needs for its variance to be considered
+const MIN_BLOCK_COUNT: i64 = 32;
+
+#[derive(Clone, Debug, Default)]
+pub struct BlockVarianceEstimator {
+    // Estimator of the block means of size 2^k at index k
+    levels: Vec<VarianceEstimator>,
+    // Block mean waiting for its pair at each level
+    pending: Vec<Option<f64>>,
+}
+
+impl BlockVarianceEstimator {
+    pub fn new() -> Self {
+        Self {
+            levels: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn add_sample(&mut self, x: f64) {
+        let mut value = x;
+        let mut level = 0;
+        loop {
+            if level == self.levels.len() {
+                self.levels.push(VarianceEstimator::new());
+                self.pending.push(None);
+            }
+            self.levels[level].add_sample(value);
+
+            match self.pending[level].take() {
+                Some(previous) => {
+                    value = (previous + value) / 2.0;
+                    level += 1;
+                }
+                None => {
+                    self.pending[level] = Some(value);
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.levels.first().map_or(0.0, |level| level.mean)
+    }
+
+    // Variance of the mean assuming independent samples, which underestimates the error of
+    // positively correlated data
+    pub fn naive_variance_of_mean(&self) -> f64 {
+        self.variance_of_mean_at(0)
+    }
+
+    // Variance of the mean estimated from blocks of size 2^level
+    pub fn variance_of_mean_at(&self, level: usize) -> f64 {
+        self.levels
+            .get(level)
+            .map_or(0.0, VarianceEstimator::variance_of_mean)
+    }
+
+    // Smallest level at which the variance of the mean stops growing, i.e. the next level's
+    // estimate lies within the statistical error of this one. Only levels with at least
+    // MIN_BLOCK_COUNT blocks are considered
+    pub fn optimal_level(&self) -> usize {
+        let usable = self
+            .levels
+            .iter()
+            .take_while(|level| level.sample_count() >= MIN_BLOCK_COUNT)
+            .count();
+
+        (0..usable.saturating_sub(1))
+            .find(|&level| {
+                let variance = self.variance_of_mean_at(level);
+                let block_count = self.levels[level].sample_count() as f64;
+                let error = variance * (2.0 / (block_count - 1.0)).sqrt();
+                self.variance_of_mean_at(level + 1) <= variance + error
+            })
+            .unwrap_or(usable.saturating_sub(1))
+    }
+
+    pub fn optimal_block_size(&self) -> usize {
+        1 << self.optimal_level()
+    }
+
+    // Variance of the mean at the optimal block size
+    pub fn variance_of_mean(&self) -> f64 {
+        self.variance_of_mean_at(self.optimal_level())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_levels() {
+        let mut ve = BlockVarianceEstimator::new();
+        (0..8).for_each(|i| ve.add_sample(i as f64));
+
+        assert_eq!(ve.mean(), 3.5);
+        assert_eq!(ve.levels.len(), 4);
+        assert_eq!(ve.levels[1].sample_count(), 4);
+        assert_eq!(ve.levels[3].mean, 3.5);
+    }
+
+    #[test]
+    fn test_independent_samples() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut ve = BlockVarianceEstimator::new();
+        (0..1 << 16).for_each(|_| ve.add_sample(rng.gen()));
+
+        assert_approx_eq!(ve.variance_of_mean(), ve.naive_variance_of_mean(), 0.5);
+    }
+
+    #[test]
+    fn test_autocorrelated_samples() {
+        // AR(1) process x_t = phi * x_{t-1} + e_t with e_t uniform on [-1, 1]
+        const PHI: f64 = 0.9;
+        const SAMPLE_COUNT: usize = 1 << 17;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let mut ve = BlockVarianceEstimator::new();
+        let mut x = 0.0;
+        (0..SAMPLE_COUNT).for_each(|_| {
+            x = PHI * x + rng.gen_range(-1.0..1.0);
+            ve.add_sample(x);
+        });
+
+        let process_variance = (1.0 / 3.0) / (1.0 - PHI * PHI);
+        let expected = process_variance / SAMPLE_COUNT as f64 * (1.0 + PHI) / (1.0 - PHI);
+
+        assert!(ve.optimal_block_size() > 1);
+        assert!(ve.variance_of_mean() > 5.0 * ve.naive_variance_of_mean());
+        assert_approx_eq!(ve.variance_of_mean(), expected, 0.3);
+    }
+}
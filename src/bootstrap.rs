@@ -0,0 +1,80 @@
+// Bootstrap confidence intervals, which make no normality assumption about the estimate and so
+// stay accurate for skewed or heavy-tailed integrands where mean ± z * std_error does not
+use crate::error::{check_integration, MonteCarloError, Result};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Coverage of the percentile interval returned by `integrate_bootstrap`
+pub const BOOTSTRAP_CONFIDENCE: f64 = 0.95;
+
+// Estimate integral from a to b of f(x) dx along with a 95% percentile bootstrap confidence
+// interval, from `bootstrap_resamples` resamples with replacement of the integrand values.
+// All `sample_count` values are kept in memory, that is 8 bytes per sample, and each resample
+// costs another O(sample_count) work
+pub fn integrate_bootstrap(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    bootstrap_resamples: usize,
+) -> Result<(f64, (f64, f64))> {
+    check_integration(a, b, sample_count)?;
+    if bootstrap_resamples == 0 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "bootstrap resamples",
+            value: 0.0,
+        });
+    }
+    let scale = (b - a) / (sample_count as f64);
+    let values: Vec<f64> = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| f(rng.gen_range(a..=b)))
+        .collect();
+    let estimate = values.iter().sum::<f64>() * scale;
+
+    let mut resampled: Vec<f64> = (0..bootstrap_resamples)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            (0..sample_count)
+                .map(|_| values[rng.gen_range(0..sample_count)])
+                .sum::<f64>()
+                * scale
+        })
+        .collect();
+    resampled.sort_unstable_by(f64::total_cmp);
+
+    let tail = (1.0 - BOOTSTRAP_CONFIDENCE) / 2.0;
+    let last = bootstrap_resamples - 1;
+    let lower = resampled[((tail * last as f64).round() as usize).min(last)];
+    let upper = resampled[(((1.0 - tail) * last as f64).round() as usize).min(last)];
+    Ok((estimate, (lower, upper)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration::sample_integrand_seeded;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_matches_normal_approximation() {
+        // The values of x on [0, 1] are symmetric, with standard deviation sqrt(1 / 12)
+        let (estimate, (lower, upper)) = integrate_bootstrap(|x| x, 0.0, 1.0, 2000, 1000).unwrap();
+        assert!(lower < estimate && estimate < upper);
+
+        let std_error = sample_integrand_seeded(|x| x, 0.0, 1.0, 2000, 1)
+            .unwrap()
+            .std_error()
+            .unwrap();
+        assert_approx_eq!(upper - lower, 2.0 * 1.96 * std_error, 0.15);
+        assert_approx_eq!((lower + upper) / 2.0, estimate, 0.01);
+    }
+
+    #[test]
+    fn test_constant_integrand() {
+        let (estimate, (lower, upper)) = integrate_bootstrap(|_| 2.0, 0.0, 3.0, 100, 50).unwrap();
+        assert_approx_eq!(estimate, 6.0);
+        assert_approx_eq!(lower, 6.0);
+        assert_approx_eq!(upper, 6.0);
+    }
+}
@@ -0,0 +1,163 @@
+// Predicting the running time and sample count of an integration before launching it, and
+// measuring how efficient it was afterwards
+use crate::error::{check_integration, MonteCarloError, Result};
+use crate::importance::normal_quantile;
+use crate::integration::sample_integrand_sequential;
+use rand::prelude::*;
+use std::time::{Duration, Instant};
+
+// Predicted wall-clock time of evaluating an integrand costing `f_cost_nanos` nanoseconds per
+// call `sample_count` times on `threads` threads. Assumes perfect scaling, so it is a lower bound
+// when the threads share the machine with other work or the integrand is memory-bound
+pub fn estimate_cost(sample_count: usize, f_cost_nanos: u64, threads: usize) -> Result<Duration> {
+    if threads == 0 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "thread count",
+            value: 0.0,
+        });
+    }
+    let samples_per_thread = sample_count.div_ceil(threads) as u64;
+    Ok(Duration::from_nanos(
+        samples_per_thread.saturating_mul(f_cost_nanos),
+    ))
+}
+
+// Measure the cost of f in nanoseconds per call, by timing `sample_count` sequential evaluations
+// at uniform points of [a, b], as an input to `estimate_cost`. A few thousand samples are usually
+// enough. Rounds up, so even a trivial integrand costs at least 1
+pub fn calibrate_cost(f: impl Fn(f64) -> f64, a: f64, b: f64, sample_count: usize) -> Result<u64> {
+    check_integration(a, b, sample_count)?;
+    let mut rng = thread_rng();
+    let start = Instant::now();
+    for _ in 0..sample_count {
+        std::hint::black_box(f(rng.gen_range(a..=b)));
+    }
+    let nanos = start.elapsed().as_nanos().div_ceil(sample_count as u128);
+    Ok(nanos.max(1) as u64)
+}
+
+// Time-normalized error std_error * sqrt(elapsed seconds), the standard error a method would
+// reach in one second. The error of Monte Carlo falls as 1 / sqrt(work), so this stays roughly
+// constant as the sample count grows, and compares methods regardless of how many samples each
+// was given. Lower is better
+pub fn time_normalized_error(std_error: f64, elapsed: Duration) -> f64 {
+    std_error * elapsed.as_secs_f64().sqrt()
+}
+
+// Time-normalized error of plain Monte Carlo for the integral from a to b of f(x) dx, measured
+// on a single-threaded run of `sample_count` samples so that the timing does not depend on what
+// else shares the thread pool
+pub fn measure_time_normalized_error(
+    f: impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: u64,
+) -> Result<f64> {
+    let start = Instant::now();
+    let ve = sample_integrand_sequential(f, a, b, sample_count, seed)?;
+    let elapsed = start.elapsed();
+    let std_error = ve.std_error().ok_or(MonteCarloError::InsufficientSamples {
+        required: 2,
+        provided: sample_count,
+    })?;
+    Ok(time_normalized_error(std_error * (b - a), elapsed))
+}
+
+// Number of samples for the half-width of the `confidence` (e.g. 0.95) normal confidence interval
+// of the integral from a to b to be at most `margin`, given the standard deviation of the
+// integrand values from a pilot run. The half-width is z * (b - a) * pilot_std / sqrt(n), so this
+// is (z * (b - a) * pilot_std / margin)^2 rounded up. It only holds in expectation: the standard
+// deviation of the final run differs from the pilot's by sampling noise
+pub fn samples_for_confidence(
+    pilot_std: f64,
+    a: f64,
+    b: f64,
+    confidence: f64,
+    margin: f64,
+) -> Result<usize> {
+    if margin <= 0.0 || margin.is_nan() {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "margin",
+            value: margin,
+        });
+    }
+    if confidence <= 0.0 || confidence >= 1.0 || confidence.is_nan() {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "confidence",
+            value: confidence,
+        });
+    }
+    let z = normal_quantile(0.5 + confidence / 2.0);
+    Ok(((z * (b - a).abs() * pilot_std / margin).powi(2).ceil() as usize).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        assert_eq!(
+            estimate_cost(1_000_000_000, 5, 8),
+            Ok(Duration::from_millis(625))
+        );
+        // An uneven split is limited by the busiest thread
+        assert_eq!(estimate_cost(10, 100, 4), Ok(Duration::from_nanos(300)));
+        assert_eq!(estimate_cost(0, 100, 4), Ok(Duration::ZERO));
+        assert!(estimate_cost(10, 100, 0).is_err());
+    }
+
+    #[test]
+    fn test_time_normalized_error() {
+        assert_eq!(time_normalized_error(0.5, Duration::from_secs(4)), 1.0);
+        assert_eq!(time_normalized_error(0.5, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_time_normalized_error_independent_of_sample_count() {
+        let f = |x: f64| (x.sin() + x.cos()).exp();
+        let small = measure_time_normalized_error(f, 0.0, 2.0, 100_000, 1).unwrap();
+        let large = measure_time_normalized_error(f, 0.0, 2.0, 800_000, 2).unwrap();
+
+        // 8 times more samples would shrink the plain error almost 3 times; timing noise aside,
+        // the normalized one stays the same
+        assert!(small > 0.0);
+        assert!((0.4..2.5).contains(&(large / small)));
+    }
+
+    #[test]
+    fn test_samples_for_confidence() {
+        let f = |x: f64| x.exp();
+        let pilot = sample_integrand_sequential(f, 0.0, 2.0, 100_000, 1).unwrap();
+        let sample_count = samples_for_confidence(pilot.std_dev(), 0.0, 2.0, 0.95, 0.01).unwrap();
+        // Halving the margin takes 4 times the samples, up to rounding
+        let halved = samples_for_confidence(pilot.std_dev(), 0.0, 2.0, 0.95, 0.005).unwrap();
+        assert!(halved.abs_diff(sample_count * 4) <= 4);
+
+        // A 1% safety factor on the pilot covers the noise of both standard deviations
+        let sample_count =
+            samples_for_confidence(1.01 * pilot.std_dev(), 0.0, 2.0, 0.95, 0.01).unwrap();
+        let run =
+            crate::integration::sample_integrand_seeded(f, 0.0, 2.0, sample_count, 2).unwrap();
+        let half_width = normal_quantile(0.975) * run.std_error().unwrap() * 2.0;
+        assert!(
+            half_width < 0.01,
+            "half-width {half_width} with {sample_count} samples"
+        );
+        // Not wildly oversized either
+        assert!(half_width > 0.009);
+
+        assert!(samples_for_confidence(1.0, 0.0, 1.0, 0.95, 0.0).is_err());
+        assert!(samples_for_confidence(1.0, 0.0, 1.0, 1.0, 0.01).is_err());
+    }
+
+    #[test]
+    fn test_trivial_integrand_is_cheap() {
+        let cost = calibrate_cost(|x| x * x, 0.0, 1.0, 100_000).unwrap();
+        assert!((1..10_000).contains(&cost));
+
+        let predicted = estimate_cost(1_000_000, cost, 1).unwrap();
+        assert!(predicted < Duration::from_secs(10));
+    }
+}
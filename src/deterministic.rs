@@ -0,0 +1,384 @@
+// Deterministic quadrature rules, used as ground truth to compare Monte Carlo estimates against
+use crate::error::{MonteCarloError, Result};
+
+// Rules on pairs of sub-intervals need a positive, even number of them
+fn check_even_intervals(intervals: usize) -> Result<()> {
+    if intervals == 0 || !intervals.is_multiple_of(2) {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "intervals",
+            value: intervals as f64,
+        });
+    }
+    Ok(())
+}
+
+// Composite Simpson's rule over an even number of sub-intervals
+pub fn simpson(f: impl Fn(f64) -> f64, a: f64, b: f64, intervals: usize) -> Result<f64> {
+    check_even_intervals(intervals)?;
+
+    let h = (b - a) / (intervals as f64);
+    let interior: f64 = (1..intervals)
+        .map(|i| {
+            let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+            weight * f(a + h * (i as f64))
+        })
+        .sum();
+
+    Ok(h / 3.0 * (f(a) + interior + f(b)))
+}
+
+// Simpson's rule with `intervals` and 2 * `intervals` sub-intervals, Richardson-extrapolated to
+// cancel the leading h^4 error term: S(h / 2) + (S(h / 2) - S(h)) / 15. The result is accurate to
+// O(h^6) for smooth integrands
+pub fn simpson_richardson(f: impl Fn(f64) -> f64, a: f64, b: f64, intervals: usize) -> Result<f64> {
+    let coarse = simpson(&f, a, b, intervals)?;
+    let fine = simpson(&f, a, b, 2 * intervals)?;
+    Ok(fine + (fine - coarse) / 15.0)
+}
+
+// Nonnegative nodes and their weights of the Gauss-Legendre rules with 1 to 5 points on [-1, 1]
+const GAUSS_LEGENDRE_TABLES: [&[(f64, f64)]; 5] = [
+    &[(0.0, 2.0)],
+    &[(0.577_350_269_189_625_8, 1.0)],
+    &[
+        (0.0, 0.888_888_888_888_889),
+        (0.774_596_669_241_483_4, 0.555_555_555_555_555_6),
+    ],
+    &[
+        (0.339_981_043_584_856_3, 0.652_145_154_862_546_1),
+        (0.861_136_311_594_052_6, 0.347_854_845_137_453_9),
+    ],
+    &[
+        (0.0, 0.568_888_888_888_888_9),
+        (0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+        (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    ],
+];
+
+// Nodes and weights of the `points`-point Gauss-Legendre rule on [-1, 1], in increasing node
+// order. Common orders come from `GAUSS_LEGENDRE_TABLES`, the others are computed
+pub(crate) fn gauss_legendre_rule(points: usize) -> Vec<(f64, f64)> {
+    match GAUSS_LEGENDRE_TABLES.get(points.wrapping_sub(1)) {
+        Some(table) => {
+            let negative = table.iter().rev().map(|&(x, w)| (-x, w));
+            let positive = table.iter().skip(points % 2).copied();
+            negative.chain(positive).collect()
+        }
+        None => compute_gauss_legendre_rule(points),
+    }
+}
+
+// Each node is a root of the Legendre polynomial P_points, found by Newton's iteration from
+// the Chebyshev-like guess cos(PI * (i + 3/4) / (points + 1/2)). The rule is symmetric, so only
+// half of the roots are computed
+fn compute_gauss_legendre_rule(points: usize) -> Vec<(f64, f64)> {
+    let n = points as f64;
+    let mut rule = vec![(0.0, 0.0); points];
+    for i in 0..points.div_ceil(2) {
+        let mut x = (std::f64::consts::PI * (i as f64 + 0.75) / (n + 0.5)).cos();
+        let mut derivative;
+        loop {
+            // Three-term recurrence for P_points(x) and P_(points - 1)(x)
+            let (mut p, mut previous) = (1.0, 0.0);
+            for j in 1..=points {
+                let j = j as f64;
+                (p, previous) = (((2.0 * j - 1.0) * x * p - (j - 1.0) * previous) / j, p);
+            }
+            derivative = n * (x * p - previous) / (x * x - 1.0);
+            let step = p / derivative;
+            x -= step;
+            if step.abs() < 1e-15 {
+                break;
+            }
+        }
+        let weight = 2.0 / ((1.0 - x * x) * derivative * derivative);
+        rule[i] = (-x, weight);
+        rule[points - 1 - i] = (x, weight);
+    }
+    rule
+}
+
+// `points`-point Gauss-Legendre quadrature of f over [a, b], exact for polynomials of degree up
+// to 2 * points - 1. Far more accurate than Monte Carlo with as many evaluations for smooth f
+pub fn gauss_legendre(f: impl Fn(f64) -> f64, a: f64, b: f64, points: usize) -> Result<f64> {
+    if points == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    let half_width = (b - a) / 2.0;
+    let center = (a + b) / 2.0;
+    Ok(half_width
+        * gauss_legendre_rule(points)
+            .iter()
+            .map(|&(x, w)| w * f(center + half_width * x))
+            .sum::<f64>())
+}
+
+// Nonnegative nodes and their weights of the Gauss-Hermite rules with 1 to 5 points, for the
+// weight exp(-x^2) over the real line
+const GAUSS_HERMITE_TABLES: [&[(f64, f64)]; 5] = [
+    &[(0.0, 1.772_453_850_905_516)],
+    &[(std::f64::consts::FRAC_1_SQRT_2, 0.886_226_925_452_758)],
+    &[
+        (0.0, 1.181_635_900_603_677_4),
+        (1.224_744_871_391_589, 0.295_408_975_150_919_35),
+    ],
+    &[
+        (0.524_647_623_275_290_4, 0.804_914_090_005_512_8),
+        (1.650_680_123_885_784_4, 0.081_312_835_447_245_17),
+    ],
+    &[
+        (0.0, 0.945_308_720_482_941_9),
+        (0.958_572_464_613_818_5, 0.393_619_323_152_241_2),
+        (2.020_182_870_456_085_6, 0.019_953_242_059_045_913),
+    ],
+];
+
+// `points`-point Gauss-Hermite quadrature of the integral of f(x) exp(-x^2) dx over the real line,
+// with `points` from 1 to 5. Exact when f is a polynomial of degree up to 2 * points - 1, which
+// makes it the reference for Gaussian-weighted integrands sampled by Monte Carlo
+pub fn gauss_hermite(f: impl Fn(f64) -> f64, points: usize) -> Result<f64> {
+    let table = GAUSS_HERMITE_TABLES.get(points.wrapping_sub(1)).ok_or(
+        MonteCarloError::InvalidParameter {
+            name: "points",
+            value: points as f64,
+        },
+    )?;
+    Ok(table
+        .iter()
+        .map(|&(x, w)| {
+            if x == 0.0 {
+                w * f(x)
+            } else {
+                w * (f(-x) + f(x))
+            }
+        })
+        .sum())
+}
+
+// Filon's coefficients alpha, beta, and gamma for theta = frequency * h. Small thetas use the
+// Taylor series, since the closed forms cancel catastrophically as theta goes to 0
+fn filon_coefficients(theta: f64) -> (f64, f64, f64) {
+    if theta.abs() < 1.0 / 6.0 {
+        let t2 = theta * theta;
+        let t3 = t2 * theta;
+        let alpha = 2.0 * t3 / 45.0 - 2.0 * t3 * t2 / 315.0 + 2.0 * t3 * t2 * t2 / 4725.0;
+        let beta = 2.0 / 3.0 + 2.0 * t2 / 15.0 - 4.0 * t2 * t2 / 105.0 + 2.0 * t3 * t3 / 567.0;
+        let gamma = 4.0 / 3.0 - 2.0 * t2 / 15.0 + t2 * t2 / 210.0 - t3 * t3 / 11340.0;
+        (alpha, beta, gamma)
+    } else {
+        let (sin, cos) = theta.sin_cos();
+        let t3 = theta * theta * theta;
+        let alpha = (theta * theta + theta * sin * cos - 2.0 * sin * sin) / t3;
+        let beta = 2.0 * (theta * (1.0 + cos * cos) - 2.0 * sin * cos) / t3;
+        let gamma = 4.0 * (sin - theta * cos) / t3;
+        (alpha, beta, gamma)
+    }
+}
+
+// Filon's rule for the integral from a to b of amplitude_fn(x) * sin(frequency * x) dx over an
+// even number of sub-intervals. The amplitude is interpolated by parabolas and the oscillating
+// factor is integrated exactly, so it stays accurate for highly oscillatory integrands where
+// Monte Carlo and Simpson's rule need a huge number of samples
+pub fn filon_sine(
+    amplitude_fn: impl Fn(f64) -> f64,
+    frequency: f64,
+    a: f64,
+    b: f64,
+    intervals: usize,
+) -> Result<f64> {
+    check_even_intervals(intervals)?;
+
+    let h = (b - a) / (intervals as f64);
+    let (alpha, beta, gamma) = filon_coefficients(frequency * h);
+    let term = |x: f64| amplitude_fn(x) * (frequency * x).sin();
+
+    let even: f64 = (0..=intervals)
+        .step_by(2)
+        .map(|i| term(a + h * (i as f64)))
+        .sum::<f64>()
+        - 0.5 * (term(a) + term(b));
+    let odd: f64 = (1..intervals)
+        .step_by(2)
+        .map(|i| term(a + h * (i as f64)))
+        .sum();
+    let boundary =
+        amplitude_fn(a) * (frequency * a).cos() - amplitude_fn(b) * (frequency * b).cos();
+
+    Ok(h * (alpha * boundary + beta * even + gamma * odd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration::monte_carlo_integration;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_simpson_cubic_exact() {
+        // Simpson's rule is exact for cubics
+        assert_approx_eq!(simpson(|x| x * x * x - x, 0.0, 2.0, 2).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_richardson_improves_simpson() {
+        let expected = 1.0_f64.exp() - 1.0;
+        let simpson_error = (simpson(f64::exp, 0.0, 1.0, 16).unwrap() - expected).abs();
+        let richardson_error =
+            (simpson_richardson(f64::exp, 0.0, 1.0, 8).unwrap() - expected).abs();
+        assert!(richardson_error < simpson_error / 100.0);
+        assert!(richardson_error < 1e-9);
+    }
+
+    #[test]
+    fn test_gauss_legendre_tables_match_computed_rules() {
+        for points in 1..=GAUSS_LEGENDRE_TABLES.len() {
+            let table = gauss_legendre_rule(points);
+            let computed = compute_gauss_legendre_rule(points);
+            assert_eq!(table.len(), points);
+            for (&(x, w), &(computed_x, computed_w)) in table.iter().zip(&computed) {
+                assert!((x - computed_x).abs() < 1e-14);
+                assert!((w - computed_w).abs() < 1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn test_gauss_legendre_exact_for_polynomials() {
+        let (a, b): (f64, f64) = (-0.5, 1.5);
+        for points in 1..=12 {
+            for degree in 0..=2 * points {
+                let k = degree as i32;
+                let expected = (b.powi(k + 1) - a.powi(k + 1)) / (k + 1) as f64;
+                let error = (gauss_legendre(|x| x.powi(k), a, b, points).unwrap() - expected).abs()
+                    / expected.abs();
+                if degree < 2 * points {
+                    assert!(error < 1e-13, "{points} points, degree {degree}");
+                } else {
+                    assert!(error > 1e-11, "{points} points, degree {degree}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_gauss_legendre_rule() {
+        let rule = compute_gauss_legendre_rule(3);
+        let expected = [
+            (-(0.6_f64).sqrt(), 5.0 / 9.0),
+            (0.0, 8.0 / 9.0),
+            ((0.6_f64).sqrt(), 5.0 / 9.0),
+        ];
+        for (&(x, w), &(expected_x, expected_w)) in rule.iter().zip(&expected) {
+            assert!((x - expected_x).abs() < 1e-14);
+            assert!((w - expected_w).abs() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn test_gauss_hermite_gaussian() {
+        let sqrt_pi = std::f64::consts::PI.sqrt();
+        for points in 1..=GAUSS_HERMITE_TABLES.len() {
+            assert!((gauss_hermite(|_| 1.0, points).unwrap() - sqrt_pi).abs() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn test_gauss_hermite_exact_for_polynomials() {
+        // The integral of x^2k exp(-x^2) is (2k - 1)!! / 2^k * sqrt(PI), and 0 for odd powers
+        let moment = |degree: i32| {
+            if degree % 2 == 1 {
+                0.0
+            } else {
+                (1..degree)
+                    .step_by(2)
+                    .map(|j| j as f64 / 2.0)
+                    .product::<f64>()
+                    * std::f64::consts::PI.sqrt()
+            }
+        };
+        for points in 1..=GAUSS_HERMITE_TABLES.len() {
+            for degree in 0..=2 * points as i32 {
+                let error =
+                    (gauss_hermite(|x| x.powi(degree), points).unwrap() - moment(degree)).abs();
+                if degree < 2 * points as i32 {
+                    assert!(error < 1e-13, "{points} points, degree {degree}");
+                } else {
+                    assert!(error > 1e-3, "{points} points, degree {degree}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_filon_sine_constant_amplitude() {
+        let k: f64 = 100.0;
+        let expected = (1.0 - k.cos()) / k;
+        assert_approx_eq!(
+            filon_sine(|_| 1.0, k, 0.0, 1.0, 10).unwrap(),
+            expected,
+            1e-9
+        );
+    }
+
+    #[test]
+    fn test_filon_sine_linear_amplitude() {
+        let k: f64 = 50.0;
+        let antiderivative = |x: f64| (k * x).sin() / (k * k) - x * (k * x).cos() / k;
+        let expected = antiderivative(2.0) - antiderivative(0.5);
+        assert_approx_eq!(filon_sine(|x| x, k, 0.5, 2.0, 8).unwrap(), expected, 1e-9);
+    }
+
+    #[test]
+    fn test_filon_small_theta() {
+        // With a tiny frequency, the series coefficients must still give the Simpson-like answer
+        let k: f64 = 1e-3;
+        let expected = (1.0 - (k * 2.0).cos()) / k;
+        assert_approx_eq!(
+            filon_sine(|_| 1.0, k, 0.0, 2.0, 100).unwrap(),
+            expected,
+            1e-9
+        );
+    }
+
+    #[test]
+    fn test_monte_carlo_struggles_on_oscillatory() {
+        let k: f64 = 100.0;
+        let expected = (1.0 - k.cos()) / k;
+
+        let filon_error = (filon_sine(|_| 1.0, k, 0.0, 1.0, 10).unwrap() - expected).abs();
+        let simpson_error = (simpson(|x| (k * x).sin(), 0.0, 1.0, 10).unwrap() - expected).abs();
+        let mc_error = (monte_carlo_integration(|x| (k * x).sin(), 0.0, 1.0, 10_000)
+            .unwrap()
+            .integral
+            - expected)
+            .abs();
+
+        assert!(filon_error < 1e-9);
+        assert!(simpson_error > 1e-3);
+        assert!(mc_error > filon_error);
+    }
+
+    #[test]
+    fn test_invalid_rule_sizes() {
+        let intervals = |value| {
+            Err(MonteCarloError::InvalidParameter {
+                name: "intervals",
+                value,
+            })
+        };
+        assert_eq!(simpson(|x| x, 0.0, 1.0, 3), intervals(3.0));
+        assert_eq!(simpson_richardson(|x| x, 0.0, 1.0, 0), intervals(0.0));
+        assert_eq!(filon_sine(|x| x, 1.0, 0.0, 1.0, 5), intervals(5.0));
+        assert_eq!(
+            gauss_legendre(|x| x, 0.0, 1.0, 0),
+            Err(MonteCarloError::ZeroSamples)
+        );
+        assert_eq!(
+            gauss_hermite(|x| x, 6),
+            Err(MonteCarloError::InvalidParameter {
+                name: "points",
+                value: 6.0
+            })
+        );
+    }
+}
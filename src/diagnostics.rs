@@ -0,0 +1,808 @@
+use crate::deterministic::simpson_richardson;
+use crate::error::{check_integration, MonteCarloError, Result};
+use crate::importance::gaussian_pdf;
+use crate::integration::{chunk_rng, sample_integrand, SEEDED_CHUNK_SIZE};
+use crate::variance_estimator::VarianceEstimator;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Running sum of the integrand, along with the sample point where it was largest. Ties go to the
+// smallest x, so the reported point does not depend on the order the samples are reduced in
+#[derive(Copy, Clone, Debug)]
+struct ArgmaxAccumulator {
+    sum: f64,
+    arg_max: f64,
+    max_value: f64,
+}
+
+impl ArgmaxAccumulator {
+    fn new() -> Self {
+        Self {
+            sum: 0.0,
+            arg_max: f64::NAN,
+            max_value: f64::NEG_INFINITY,
+        }
+    }
+
+    // Whether (x, value) beats the current maximum, including on a tie with a larger x
+    fn is_better(&self, x: f64, value: f64) -> bool {
+        value > self.max_value
+            || (value == self.max_value && (x < self.arg_max || self.arg_max.is_nan()))
+    }
+
+    fn add_sample(mut self, x: f64, value: f64) -> Self {
+        self.sum += value;
+        if self.is_better(x, value) {
+            self.arg_max = x;
+            self.max_value = value;
+        }
+        self
+    }
+
+    fn merge(lhs: Self, rhs: Self) -> Self {
+        let best = if lhs.is_better(rhs.arg_max, rhs.max_value) {
+            rhs
+        } else {
+            lhs
+        };
+        Self {
+            sum: lhs.sum + rhs.sum,
+            ..best
+        }
+    }
+}
+
+// Estimate integral from a to b of f(x) dx, and also return the sample point where f was largest
+// along with that value. This is a cheap mode-finding pass, only as precise as the sampling is dense
+pub fn integrate_with_argmax(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<(f64, f64, f64)> {
+    check_integration(a, b, sample_count)?;
+    let result = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            (x, f(x))
+        })
+        .fold(ArgmaxAccumulator::new, |acc, (x, value)| {
+            acc.add_sample(x, value)
+        })
+        .reduce(ArgmaxAccumulator::new, ArgmaxAccumulator::merge);
+
+    let estimate = result.sum * (b - a) / (sample_count as f64);
+    Ok((estimate, result.arg_max, result.max_value))
+}
+
+// Same as `integrate_with_argmax`, but reproducible for a given seed, with the same samples as
+// `integrate_seeded`. Thanks to the tie-breaking, even an integrand whose maximum is a plateau
+// reports the same point whatever the thread count
+pub fn integrate_with_argmax_seeded(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: u64,
+) -> Result<(f64, f64, f64)> {
+    check_integration(a, b, sample_count)?;
+    let result = (0..sample_count.div_ceil(SEEDED_CHUNK_SIZE))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut rng = chunk_rng(seed, chunk);
+            let start = chunk * SEEDED_CHUNK_SIZE;
+            let end = (start + SEEDED_CHUNK_SIZE).min(sample_count);
+            (start..end).fold(ArgmaxAccumulator::new(), |acc, _| {
+                let x = rng.gen_range(a..=b);
+                acc.add_sample(x, f(x))
+            })
+        })
+        .reduce(ArgmaxAccumulator::new, ArgmaxAccumulator::merge);
+
+    let estimate = result.sum * (b - a) / (sample_count as f64);
+    Ok((estimate, result.arg_max, result.max_value))
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ContributionEstimate {
+    pub estimate: f64,
+    // Largest |f(x) (b - a) / n| of a single sample, that sample's share of the estimate
+    pub max_contribution: f64,
+    // Sample point with the largest contribution
+    pub arg_max: f64,
+    // max_contribution over the sum of all absolute contributions. Close to 1 / n for a well
+    // behaved integrand; a large fraction means rare spikes ("fireflies") dominate the estimate
+    pub max_fraction: f64,
+}
+
+// Estimate integral from a to b of f(x) dx, and find the single sample that contributes the most
+pub fn integrate_with_max_contribution(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<ContributionEstimate> {
+    check_integration(a, b, sample_count)?;
+    let (magnitudes, sum) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            (x, f(x))
+        })
+        .fold(
+            || (ArgmaxAccumulator::new(), 0.0),
+            |(acc, sum), (x, value)| (acc.add_sample(x, value.abs()), sum + value),
+        )
+        .reduce(
+            || (ArgmaxAccumulator::new(), 0.0),
+            |lhs, rhs| (ArgmaxAccumulator::merge(lhs.0, rhs.0), lhs.1 + rhs.1),
+        );
+
+    let weight = (b - a) / (sample_count as f64);
+    Ok(ContributionEstimate {
+        estimate: sum * weight,
+        max_contribution: magnitudes.max_value * weight,
+        arg_max: magnitudes.arg_max,
+        max_fraction: if magnitudes.sum > 0.0 {
+            magnitudes.max_value / magnitudes.sum
+        } else {
+            0.0
+        },
+    })
+}
+
+// Lorenz-curve-like concentration of the integrand's contribution: for each fraction q in
+// `top_fractions`, the share of the sum of |f| held by the top q of the `sample_count` samples,
+// ranked by |f(x)|. A uniform contribution gives a share of q. Shares close to 1 for small q mean
+// a few samples carry the integral, and the integrand calls for importance sampling. All the
+// values are kept in memory
+pub fn contribution_concentration(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    top_fractions: &[f64],
+) -> Result<Vec<f64>> {
+    check_integration(a, b, sample_count)?;
+    let mut magnitudes: Vec<f64> = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| f(rng.gen_range(a..=b)).abs())
+        .collect();
+    magnitudes.par_sort_unstable_by(|lhs, rhs| rhs.total_cmp(lhs));
+
+    // Prefix sums of the sorted magnitudes, largest first
+    let cumulative: Vec<f64> = magnitudes
+        .iter()
+        .scan(0.0, |sum, &m| {
+            *sum += m;
+            Some(*sum)
+        })
+        .collect();
+    let total = cumulative.last().copied().unwrap_or(0.0);
+
+    Ok(top_fractions
+        .iter()
+        .map(|&q| {
+            let count =
+                ((q.clamp(0.0, 1.0) * sample_count as f64).round() as usize).min(sample_count);
+            if count == 0 || total <= 0.0 {
+                0.0
+            } else {
+                cumulative[count - 1] / total
+            }
+        })
+        .collect())
+}
+
+// Estimate integral from a to b of f(x) dx, and for each threshold the fraction of the domain
+// where f(x) > threshold
+pub fn integrate_with_exceedance(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    thresholds: &[f64],
+    sample_count: usize,
+) -> Result<(f64, Vec<f64>)> {
+    check_integration(a, b, sample_count)?;
+    let identity = || (0.0, vec![0_usize; thresholds.len()]);
+    let (sum, counts) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| f(rng.gen_range(a..=b)))
+        .fold(identity, |(sum, mut counts), value| {
+            thresholds
+                .iter()
+                .zip(counts.iter_mut())
+                .filter(|(&threshold, _)| value > threshold)
+                .for_each(|(_, count)| *count += 1);
+            (sum + value, counts)
+        })
+        .reduce(
+            identity,
+            |(lhs_sum, mut lhs_counts), (rhs_sum, rhs_counts)| {
+                lhs_counts
+                    .iter_mut()
+                    .zip(rhs_counts)
+                    .for_each(|(lhs, rhs)| *lhs += rhs);
+                (lhs_sum + rhs_sum, lhs_counts)
+            },
+        );
+
+    let fractions = counts
+        .into_iter()
+        .map(|count| count as f64 / sample_count as f64)
+        .collect();
+    Ok((sum * (b - a) / (sample_count as f64), fractions))
+}
+
+// Estimate integral from a to b of f(x) dx, and from the same samples the fraction of the domain
+// where `predicate(x, f(x))` holds, e.g. `|_, value| value > 0.0` for the support of the positive
+// part. The measure of the region is that fraction times (b - a)
+pub fn integrate_with_region_measure(
+    f: impl Fn(f64) -> f64 + Sync,
+    predicate: impl Fn(f64, f64) -> bool + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<(f64, f64)> {
+    check_integration(a, b, sample_count)?;
+    let (sum, count) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            let value = f(x);
+            (value, predicate(x, value) as usize)
+        })
+        .reduce(|| (0.0, 0), |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1));
+
+    Ok((
+        sum * (b - a) / (sample_count as f64),
+        count as f64 / sample_count as f64,
+    ))
+}
+
+// Tail index below which the integrand has infinite variance, so CLT-based error bars are invalid
+pub const HEAVY_TAIL_INDEX: f64 = 2.0;
+
+// Hill estimator of the tail index alpha, assuming P(|X| > t) ~ t^-alpha, computed from the
+// `tail_count` largest magnitudes. Returns None when there are not enough positive samples
+pub fn tail_index_estimate(samples: &[f64], tail_count: usize) -> Option<f64> {
+    let mut magnitudes: Vec<f64> = samples
+        .iter()
+        .map(|x| x.abs())
+        .filter(|x| x.is_finite() && *x > 0.0)
+        .collect();
+    if tail_count == 0 || magnitudes.len() <= tail_count {
+        return None;
+    }
+
+    magnitudes.sort_unstable_by(|lhs, rhs| rhs.total_cmp(lhs));
+    let threshold = magnitudes[tail_count];
+    let hill = magnitudes[..tail_count]
+        .iter()
+        .map(|x| (x / threshold).ln())
+        .sum::<f64>()
+        / (tail_count as f64);
+
+    (hill > 0.0).then(|| 1.0 / hill)
+}
+
+// Estimate integral from a to b of f(x) dx along with the tail index of the sampled integrand
+// values, using the largest sqrt(n) samples. A tail index below HEAVY_TAIL_INDEX means the
+// variance, and therefore any error bar, is not to be trusted
+pub fn integrate_with_tail_index(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<(f64, Option<f64>)> {
+    check_integration(a, b, sample_count)?;
+    let values: Vec<f64> = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| f(rng.gen_range(a..=b)))
+        .collect();
+
+    let estimate = values.iter().sum::<f64>() * (b - a) / (sample_count as f64);
+    let tail_count = (sample_count as f64).sqrt() as usize;
+    Ok((estimate, tail_index_estimate(&values, tail_count)))
+}
+
+// Gaussian kernel density estimate of the distribution of a set of values
+#[derive(Clone, Debug)]
+pub struct KernelDensity {
+    values: Vec<f64>,
+    bandwidth: f64,
+}
+
+impl KernelDensity {
+    // Density estimate of `values` with Silverman's rule of thumb for the bandwidth,
+    // 0.9 min(standard deviation, interquartile range / 1.34) n^(-1/5). When the values are all
+    // equal, the bandwidth falls back to a tiny multiple of their magnitude
+    pub fn from_values(mut values: Vec<f64>) -> Result<Self> {
+        if values.is_empty() {
+            return Err(MonteCarloError::ZeroSamples);
+        }
+        values.sort_unstable_by(f64::total_cmp);
+        let n = values.len();
+
+        let mut ve = VarianceEstimator::new();
+        values.iter().for_each(|&x| ve.add_sample(x));
+        let quartile = |q: f64| values[((q * (n - 1) as f64).round() as usize).min(n - 1)];
+        let iqr = quartile(0.75) - quartile(0.25);
+        let spread = if iqr > 0.0 {
+            ve.std_dev().min(iqr / 1.34)
+        } else {
+            ve.std_dev()
+        };
+
+        let bandwidth = if spread > 0.0 {
+            0.9 * spread * (n as f64).powf(-0.2)
+        } else {
+            f64::EPSILON * ve.mean.abs().max(1.0)
+        };
+        Ok(Self { values, bandwidth })
+    }
+
+    pub fn bandwidth(&self) -> f64 {
+        self.bandwidth
+    }
+
+    // Estimated density at y
+    pub fn density(&self, y: f64) -> f64 {
+        self.values
+            .iter()
+            .map(|&x| gaussian_pdf(y, x, self.bandwidth))
+            .sum::<f64>()
+            / self.values.len() as f64
+    }
+
+    // The density at `points` evenly spaced values from the smallest to the largest sample, as
+    // (value, density) pairs for plotting
+    pub fn curve(&self, points: usize) -> Vec<(f64, f64)> {
+        let (lo, hi) = (self.values[0], self.values[self.values.len() - 1]);
+        (0..points)
+            .map(|i| {
+                let y = if points > 1 {
+                    lo + (hi - lo) * i as f64 / (points - 1) as f64
+                } else {
+                    lo
+                };
+                (y, self.density(y))
+            })
+            .collect()
+    }
+}
+
+// Estimate integral from a to b of f(x) dx along with a kernel density estimate of the
+// distribution of the sampled values of f, from the same samples. Evaluating the density costs
+// O(n) per point, so keep `sample_count` moderate
+pub fn integrate_with_density(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<(f64, KernelDensity)> {
+    check_integration(a, b, sample_count)?;
+    let values: Vec<f64> = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| f(rng.gen_range(a..=b)))
+        .collect();
+
+    let estimate = values.iter().sum::<f64>() * (b - a) / (sample_count as f64);
+    Ok((estimate, KernelDensity::from_values(values)?))
+}
+
+// Sample autocorrelation function of `values` at lags 0 through max_lag, normalized so the value
+// at lag 0 is 1. A constant sequence has no defined correlation and gives zero at every other lag
+pub fn autocorrelation(values: &[f64], max_lag: usize) -> Vec<f64> {
+    let n = values.len();
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let autocovariance = |lag: usize| {
+        values[..n - lag]
+            .iter()
+            .zip(&values[lag..])
+            .map(|(x, y)| (x - mean) * (y - mean))
+            .sum::<f64>()
+            / n as f64
+    };
+
+    let variance = autocovariance(0);
+    (0..=max_lag.min(n.saturating_sub(1)))
+        .map(|lag| match lag {
+            0 => 1.0,
+            _ if variance == 0.0 => 0.0,
+            _ => autocovariance(lag) / variance,
+        })
+        .collect()
+}
+
+// Integrated autocorrelation time tau = 1 + 2 * sum of the autocorrelations, summed up to max_lag
+// or until they stop being positive, since the tail is dominated by noise. Correlated samples
+// are worth n / tau independent ones
+pub fn integrated_autocorrelation_time(values: &[f64], max_lag: usize) -> f64 {
+    1.0 + 2.0
+        * autocorrelation(values, max_lag)
+            .iter()
+            .skip(1)
+            .take_while(|&&rho| rho > 0.0)
+            .sum::<f64>()
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ReferenceCheck {
+    pub estimate: f64,
+    // None with fewer than 2 samples, as for `VarianceEstimator::std_error`
+    pub std_error: Option<f64>,
+    // Richardson-extrapolated Simpson value, standing in for the exact integral
+    pub reference: f64,
+    // Signed distance from the reference to the estimate, in standard errors. None if the
+    // standard error is unknown or 0, e.g. for a constant integrand
+    pub standard_errors: Option<f64>,
+}
+
+// Estimate integral from a to b of f(x) dx and compare it against a deterministic ground truth
+// from `simpson_richardson` with `intervals` sub-intervals. Only meaningful for smooth f, where
+// the reference is far more accurate than the Monte Carlo estimate
+pub fn check_against_richardson(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    intervals: usize,
+) -> Result<ReferenceCheck> {
+    check_integration(a, b, sample_count)?;
+    let reference = simpson_richardson(&f, a, b, intervals)?;
+    let ve = sample_integrand(&f, a, b, sample_count);
+    let estimate = ve.mean * (b - a);
+    let std_error = ve.std_error().map(|std_error| std_error * (b - a));
+
+    Ok(ReferenceCheck {
+        estimate,
+        std_error,
+        reference,
+        standard_errors: std_error
+            .filter(|&std_error| std_error > 0.0)
+            .map(|std_error| (estimate - reference) / std_error),
+    })
+}
+
+// History of the integrand variance estimate, recorded after each batch of samples
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarianceConvergence {
+    // (sample count, variance) after each batch
+    pub trace: Vec<(usize, f64)>,
+    // Sample count at which the variance was declared converged, if it was
+    pub converged_at: Option<usize>,
+}
+
+// Sample f on [a, b] in batches of `batch_size`, up to `max_batches` batches, and track whether
+// the variance estimate of the integrand values has settled. It is declared converged once the
+// relative change between consecutive batches stayed below `tolerance` for `window` batches in a
+// row, and sampling stops there. A variance that keeps jumping, e.g. from a heavy tail, never
+// converges, and its error bars should not be trusted
+pub fn track_variance_convergence(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    batch_size: usize,
+    max_batches: usize,
+    window: usize,
+    tolerance: f64,
+) -> Result<VarianceConvergence> {
+    check_integration(a, b, batch_size)?;
+    let mut ve = VarianceEstimator::new();
+    let mut trace = Vec::new();
+    let mut stable_batches = 0;
+
+    for _ in 0..max_batches {
+        let previous = ve.variance();
+        ve = VarianceEstimator::merge(ve, sample_integrand(&f, a, b, batch_size));
+        let variance = ve.variance();
+        trace.push((ve.sample_count() as usize, variance));
+
+        let change = (variance - previous).abs() / variance.abs().max(f64::MIN_POSITIVE);
+        stable_batches = if trace.len() > 1 && change < tolerance {
+            stable_batches + 1
+        } else {
+            0
+        };
+        if stable_batches >= window {
+            return Ok(VarianceConvergence {
+                converged_at: Some(ve.sample_count() as usize),
+                trace,
+            });
+        }
+    }
+
+    Ok(VarianceConvergence {
+        trace,
+        converged_at: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_argmax_gaussian_peak() {
+        let f = |x: f64| (-(x - 0.3).powi(2) * 50.0).exp();
+        let (estimate, arg_max, max_value) = integrate_with_argmax(f, 0.0, 1.0, 100_000).unwrap();
+
+        // Nearly all of the Gaussian's mass sqrt(PI / 50) lies within [0, 1]
+        assert_approx_eq!(estimate, (PI / 50.0).sqrt(), 0.02);
+        assert!((arg_max - 0.3).abs() < 0.01);
+        assert_approx_eq!(max_value, f(arg_max));
+        assert!(max_value > 0.99);
+    }
+
+    #[test]
+    fn test_argmax_keeps_global_max() {
+        let lhs = ArgmaxAccumulator::new().add_sample(0.1, 5.0);
+        let rhs = ArgmaxAccumulator::new()
+            .add_sample(0.2, 1.0)
+            .add_sample(0.7, 9.0);
+
+        let merged = ArgmaxAccumulator::merge(lhs, rhs);
+        assert_eq!(merged.arg_max, 0.7);
+        assert_eq!(merged.max_value, 9.0);
+        assert_eq!(merged.sum, 15.0);
+
+        let merged = ArgmaxAccumulator::merge(rhs, ArgmaxAccumulator::new());
+        assert_eq!(merged.arg_max, 0.7);
+    }
+
+    #[test]
+    fn test_argmax_ties_pick_smallest_x() {
+        let lhs = ArgmaxAccumulator::new().add_sample(0.6, 2.0);
+        let rhs = ArgmaxAccumulator::new()
+            .add_sample(0.4, 2.0)
+            .add_sample(0.5, 2.0);
+        assert_eq!(rhs.arg_max, 0.4);
+        assert_eq!(ArgmaxAccumulator::merge(lhs, rhs).arg_max, 0.4);
+        assert_eq!(ArgmaxAccumulator::merge(rhs, lhs).arg_max, 0.4);
+    }
+
+    #[test]
+    fn test_argmax_plateau_is_reproducible() {
+        let plateau = |x: f64| if (0.4..0.6).contains(&x) { 1.0 } else { x };
+        let with_threads = |threads: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| integrate_with_argmax_seeded(plateau, 0.0, 1.0, 100_000, 8).unwrap())
+        };
+
+        let (_, arg_max, max_value) = with_threads(1);
+        assert_eq!(max_value, 1.0);
+        assert!((0.4..0.401).contains(&arg_max));
+        for threads in [2, 3, 8] {
+            assert_eq!(with_threads(threads).1, arg_max);
+        }
+    }
+
+    #[test]
+    fn test_max_contribution_of_spiky_integrand() {
+        // A spike of height 1e6 on a width of 1e-5 holds 10 of the total integral of about 11
+        let f = |x: f64| {
+            if (0.5..0.50001).contains(&x) {
+                1e6
+            } else {
+                1.0
+            }
+        };
+        let samples = 100_000;
+        let result = (0..50)
+            .map(|_| integrate_with_max_contribution(f, 0.0, 1.0, samples).unwrap())
+            .find(|result| result.max_contribution > 1.0)
+            .unwrap();
+
+        assert!((0.5..0.50001).contains(&result.arg_max));
+        assert_approx_eq!(result.max_contribution, 1e6 / samples as f64);
+        assert!(result.max_fraction > 0.05);
+    }
+
+    #[test]
+    fn test_max_contribution_of_smooth_integrand() {
+        let result = integrate_with_max_contribution(|x| x * x, 0.0, 1.0, 10_000).unwrap();
+        assert_approx_eq!(result.estimate, 1.0 / 3.0, 0.05);
+        assert!(result.max_contribution <= 1.0 / 10_000.0);
+        assert!(result.max_fraction < 1e-3);
+    }
+
+    #[test]
+    fn test_check_against_richardson() {
+        let check = check_against_richardson(|x| x.cos(), 0.0, 1.0, 100_000, 16).unwrap();
+        assert_approx_eq!(check.reference, 1.0_f64.sin(), 1e-10);
+        let standard_errors = check.standard_errors.unwrap();
+        assert!(standard_errors.abs() < 5.0);
+        assert_approx_eq!(
+            standard_errors,
+            (check.estimate - check.reference) / check.std_error.unwrap()
+        );
+
+        // Neither a single sample nor a constant integrand gives a usable standard error
+        let single = check_against_richardson(|x| x.cos(), 0.0, 1.0, 1, 16).unwrap();
+        assert_eq!(single.std_error, None);
+        assert_eq!(single.standard_errors, None);
+        let constant = check_against_richardson(|_| 2.0, 0.0, 1.0, 1000, 16).unwrap();
+        assert_eq!(constant.std_error, Some(0.0));
+        assert_eq!(constant.standard_errors, None);
+    }
+
+    #[test]
+    fn test_concentration_of_uniform_integrand() {
+        let shares =
+            contribution_concentration(|_| 2.0, 0.0, 1.0, 10_000, &[0.0, 0.01, 0.5, 1.0]).unwrap();
+        assert_eq!(shares[0], 0.0);
+        assert_approx_eq!(shares[1], 0.01);
+        assert_approx_eq!(shares[2], 0.5);
+        assert_approx_eq!(shares[3], 1.0);
+    }
+
+    #[test]
+    fn test_concentration_of_spiky_integrand() {
+        // A narrow peak of width ~0.001 holds almost all of the integral
+        let spike = |x: f64| (-((x - 0.3) / 0.001).powi(2)).exp() * 1000.0 + 0.01;
+        let shares = contribution_concentration(spike, 0.0, 1.0, 100_000, &[0.01, 0.1]).unwrap();
+        assert!(shares[0] > 0.8);
+        assert!(shares[1] > shares[0]);
+
+        let smooth = contribution_concentration(|x| x, 0.0, 1.0, 100_000, &[0.01]).unwrap();
+        assert!(smooth[0] < 0.05);
+    }
+
+    #[test]
+    fn test_exceedance_fractions() {
+        let (estimate, fractions) =
+            integrate_with_exceedance(|x| x, 0.0, 1.0, &[-1.0, 0.5, 0.9, 2.0], 100_000).unwrap();
+
+        assert_approx_eq!(estimate, 0.5, 0.01);
+        assert_eq!(fractions[0], 1.0);
+        assert_approx_eq!(fractions[1], 0.5, 0.02);
+        assert_approx_eq!(fractions[2], 0.1, 0.05);
+        assert_eq!(fractions[3], 0.0);
+    }
+
+    #[test]
+    fn test_region_measure_of_positive_part() {
+        let (estimate, fraction) =
+            integrate_with_region_measure(|x| x - 0.5, |_, value| value > 0.0, 0.0, 1.0, 100_000)
+                .unwrap();
+        assert_approx_eq!(estimate, 0.0, 0.01);
+        assert_approx_eq!(fraction, 0.5, 0.02);
+
+        let (_, fraction) =
+            integrate_with_region_measure(|x| x, |x, _| x < 1.0, -1.0, 3.0, 100_000).unwrap();
+        assert_approx_eq!(fraction, 0.5, 0.02);
+    }
+
+    #[test]
+    fn test_tail_index_of_pareto() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for alpha in [1.5, 3.0] {
+            let samples: Vec<f64> = (0..100_000)
+                .map(|_| (1.0 - rng.gen::<f64>()).powf(-1.0 / alpha))
+                .collect();
+            let estimate = tail_index_estimate(&samples, 1000).unwrap();
+            assert_approx_eq!(estimate, alpha, 0.1);
+        }
+    }
+
+    #[test]
+    fn test_tail_index_detects_infinite_variance() {
+        // x^-0.8 has a finite integral of 5 on [0, 1], but its square is not integrable. The
+        // values f(U) have tail index 1 / 0.8 = 1.25
+        let (estimate, tail_index) =
+            integrate_with_tail_index(|x| x.powf(-0.8), 0.0, 1.0, 100_000).unwrap();
+        assert!(estimate > 3.0);
+        assert!(tail_index.unwrap() < HEAVY_TAIL_INDEX);
+
+        // x^-0.3 has tail index 1 / 0.3, comfortably finite variance
+        let (_, tail_index) =
+            integrate_with_tail_index(|x| x.powf(-0.3), 0.0, 1.0, 100_000).unwrap();
+        assert!(tail_index.unwrap() > HEAVY_TAIL_INDEX);
+    }
+
+    #[test]
+    fn test_density_of_uniform_integrand() {
+        // f(x) = 2x for uniform x in [0, 1] is uniform on [0, 2], with density 1/2
+        let (estimate, kde) = integrate_with_density(|x| 2.0 * x, 0.0, 1.0, 20_000).unwrap();
+        assert_approx_eq!(estimate, 1.0, 0.02);
+
+        let curve = kde.curve(21);
+        assert_eq!(curve.len(), 21);
+        assert!(curve[0].0 >= 0.0 && curve[20].0 <= 2.0);
+        // Away from the edges, where the kernel smooths the jumps, the density is flat
+        for &(y, density) in &curve[3..18] {
+            assert!((density - 0.5).abs() < 0.05, "density {density} at {y}");
+        }
+        assert!(kde.density(3.0) < 1e-3);
+    }
+
+    #[test]
+    fn test_density_bandwidth() {
+        // Silverman's rule for standard normal values is about 0.9 n^(-1/5)
+        let mut rng = thread_rng();
+        let values: Vec<f64> = (0..10_000)
+            .map(|_| crate::importance::sample_gaussian(&mut rng, 0.0, 1.0))
+            .collect();
+        let kde = KernelDensity::from_values(values).unwrap();
+        assert_approx_eq!(kde.bandwidth(), 0.9 * 10_000_f64.powf(-0.2), 0.05);
+        assert!(
+            KernelDensity::from_values(vec![2.0; 10])
+                .unwrap()
+                .bandwidth()
+                > 0.0
+        );
+    }
+
+    #[test]
+    fn test_autocorrelation_of_independent_sequence() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let values: Vec<f64> = (0..100_000).map(|_| rng.gen()).collect();
+        let rho = autocorrelation(&values, 10);
+
+        assert_eq!(rho.len(), 11);
+        assert_eq!(rho[0], 1.0);
+        for &r in &rho[1..] {
+            assert!(r.abs() < 0.02);
+        }
+        assert!((integrated_autocorrelation_time(&values, 10) - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_autocorrelation_of_ar1_sequence() {
+        // x_t = phi x_{t-1} + noise has autocorrelation phi^k and tau = (1 + phi) / (1 - phi)
+        const PHI: f64 = 0.5;
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut x = 0.0;
+        let values: Vec<f64> = (0..200_000)
+            .map(|_| {
+                x = PHI * x + rng.gen::<f64>() - 0.5;
+                x
+            })
+            .collect();
+
+        let rho = autocorrelation(&values, 5);
+        for (lag, &r) in rho.iter().enumerate() {
+            assert!((r - PHI.powi(lag as i32)).abs() < 0.02);
+        }
+        assert_approx_eq!(integrated_autocorrelation_time(&values, 50), 3.0, 0.05);
+    }
+
+    #[test]
+    fn test_autocorrelation_of_constant_sequence() {
+        assert_eq!(autocorrelation(&[2.0; 4], 10), [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(integrated_autocorrelation_time(&[2.0; 4], 10), 1.0);
+    }
+
+    #[test]
+    fn test_variance_converges_for_well_behaved_integrand() {
+        let result = track_variance_convergence(|x| x, 0.0, 1.0, 10_000, 50, 5, 0.01).unwrap();
+        let converged_at = result.converged_at.unwrap();
+
+        assert_eq!(converged_at, result.trace.len() * 10_000);
+        assert!(result.trace.len() < 50);
+        assert_approx_eq!(result.trace.last().unwrap().1, 1.0 / 12.0, 0.02);
+    }
+
+    #[test]
+    fn test_variance_does_not_converge_for_heavy_tail() {
+        // x^-0.8 has a finite integral but an infinite variance
+        let result =
+            track_variance_convergence(|x| x.powf(-0.8), 0.0, 1.0, 10_000, 50, 5, 0.01).unwrap();
+        assert_eq!(result.converged_at, None);
+        assert_eq!(result.trace.len(), 50);
+    }
+
+    #[test]
+    fn test_tail_index_needs_samples() {
+        assert_eq!(tail_index_estimate(&[1.0, 2.0], 2), None);
+        assert_eq!(tail_index_estimate(&[1.0, 2.0, 3.0], 0), None);
+        assert_eq!(tail_index_estimate(&[1.0; 10], 3), None);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::tagged_estimator::SampleKind;
+
+// Errors reported by the integrators and estimators of this crate. Invalid arguments are reported
+// with these rather than by panicking or by returning NaN
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum MonteCarloError {
+    #[error("invalid integration bounds [{a}, {b}]: bounds must be finite and ordered")]
+    InvalidBounds { a: f64, b: f64 },
+    #[error("dimension mismatch: expected {expected} dimensions, got {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+    #[error("at least one sample is required")]
+    ZeroSamples,
+    #[error("{required} samples are required, but only {provided} were provided")]
+    InsufficientSamples { required: usize, provided: usize },
+    #[error("index {index} is out of range for {count} partitions")]
+    PartitionOutOfRange { index: usize, count: usize },
+    #[error("did not converge after {sample_count} samples")]
+    NonConvergence { sample_count: usize },
+    #[error("dimension {dimension} is not supported, the maximum is {max}")]
+    UnsupportedDimension { dimension: usize, max: usize },
+    #[error("invalid direction numbers on line {line}")]
+    InvalidDirectionNumbers { line: usize },
+    #[error("cannot merge an estimator of {lhs:?} samples with one of {rhs:?} samples")]
+    KindMismatch { lhs: SampleKind, rhs: SampleKind },
+    #[error("invalid {name}: {value}")]
+    InvalidParameter { name: &'static str, value: f64 },
+}
+
+pub type Result<T> = std::result::Result<T, MonteCarloError>;
+
+// Bounds that can be sampled uniformly: finite and ordered. a == b is an empty domain
+pub(crate) fn check_bounds(a: f64, b: f64) -> Result<()> {
+    if !a.is_finite() || !b.is_finite() || a > b {
+        return Err(MonteCarloError::InvalidBounds { a, b });
+    }
+    Ok(())
+}
+
+// Checks shared by the integrators that sample [a, b]: valid bounds and at least one sample
+pub(crate) fn check_integration(a: f64, b: f64, sample_count: usize) -> Result<()> {
+    check_bounds(a, b)?;
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(
+            MonteCarloError::InvalidBounds { a: 1.0, b: 0.0 }.to_string(),
+            "invalid integration bounds [1, 0]: bounds must be finite and ordered"
+        );
+        assert_eq!(
+            MonteCarloError::DimensionMismatch {
+                expected: 3,
+                actual: 2
+            }
+            .to_string(),
+            "dimension mismatch: expected 3 dimensions, got 2"
+        );
+        assert_eq!(
+            MonteCarloError::ZeroSamples.to_string(),
+            "at least one sample is required"
+        );
+        assert_eq!(
+            MonteCarloError::InsufficientSamples {
+                required: 40,
+                provided: 10
+            }
+            .to_string(),
+            "40 samples are required, but only 10 were provided"
+        );
+        assert_eq!(
+            MonteCarloError::PartitionOutOfRange { index: 4, count: 4 }.to_string(),
+            "index 4 is out of range for 4 partitions"
+        );
+        assert_eq!(
+            MonteCarloError::NonConvergence { sample_count: 100 }.to_string(),
+            "did not converge after 100 samples"
+        );
+        assert_eq!(
+            MonteCarloError::UnsupportedDimension {
+                dimension: 40,
+                max: 10
+            }
+            .to_string(),
+            "dimension 40 is not supported, the maximum is 10"
+        );
+        assert_eq!(
+            MonteCarloError::InvalidDirectionNumbers { line: 3 }.to_string(),
+            "invalid direction numbers on line 3"
+        );
+        assert_eq!(
+            MonteCarloError::KindMismatch {
+                lhs: SampleKind::IntegrandValue,
+                rhs: SampleKind::IntegralEstimate
+            }
+            .to_string(),
+            "cannot merge an estimator of IntegrandValue samples with one of IntegralEstimate samples"
+        );
+        assert_eq!(
+            MonteCarloError::InvalidParameter {
+                name: "decay factor",
+                value: 1.5
+            }
+            .to_string(),
+            "invalid decay factor: 1.5"
+        );
+    }
+
+    #[test]
+    fn test_check_integration() {
+        assert_eq!(check_integration(0.0, 1.0, 1), Ok(()));
+        assert_eq!(check_integration(1.0, 1.0, 1), Ok(()));
+        assert_eq!(
+            check_integration(1.0, 0.0, 1),
+            Err(MonteCarloError::InvalidBounds { a: 1.0, b: 0.0 })
+        );
+        assert!(matches!(
+            check_integration(0.0, f64::NAN, 1),
+            Err(MonteCarloError::InvalidBounds { .. })
+        ));
+        assert_eq!(
+            check_integration(0.0, 1.0, 0),
+            Err(MonteCarloError::ZeroSamples)
+        );
+    }
+
+    #[test]
+    fn test_is_std_error() {
+        fn as_std_error(error: MonteCarloError) -> Box<dyn std::error::Error + Send + Sync> {
+            Box::new(error)
+        }
+        assert_eq!(
+            as_std_error(MonteCarloError::ZeroSamples).to_string(),
+            "at least one sample is required"
+        );
+    }
+}
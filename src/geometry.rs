@@ -0,0 +1,204 @@
+// Integration over non-rectangular domains
+use crate::error::{MonteCarloError, Result};
+use crate::importance::sample_gaussian;
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+// Uniformly distributed direction: a normalized standard Gaussian vector, which is isotropic
+fn sample_direction(rng: &mut impl Rng, point: &mut [f64]) {
+    loop {
+        point
+            .iter_mut()
+            .for_each(|x| *x = sample_gaussian(rng, 0.0, 1.0));
+        let norm = point.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            point.iter_mut().for_each(|x| *x /= norm);
+            return;
+        }
+    }
+}
+
+// Volume of the unit ball in `dim` dimensions, using V(n) = 2 PI / n * V(n - 2)
+pub fn unit_ball_volume(dim: usize) -> f64 {
+    match dim {
+        0 => 1.0,
+        1 => 2.0,
+        _ => 2.0 * PI / (dim as f64) * unit_ball_volume(dim - 2),
+    }
+}
+
+// Surface area of the unit sphere bounding the unit ball in `dim` dimensions
+pub fn unit_sphere_area(dim: usize) -> f64 {
+    dim as f64 * unit_ball_volume(dim)
+}
+
+// Volume 1 / dim! of the unit simplex in `dim` dimensions
+pub fn unit_simplex_volume(dim: usize) -> f64 {
+    (1..=dim).map(|k| 1.0 / k as f64).product()
+}
+
+// There are no directions in 0 dimensions, so neither the ball nor the sphere can be sampled
+fn check_direction_dimension(dim: usize) -> Result<()> {
+    if dim == 0 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "dimension",
+            value: 0.0,
+        });
+    }
+    Ok(())
+}
+
+fn integrate_over(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    dim: usize,
+    sample_count: usize,
+    measure: f64,
+    sample_point: impl Fn(&mut ThreadRng, &mut [f64]) + Sync,
+) -> Result<f64> {
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(
+            || (thread_rng(), vec![0.0; dim]),
+            |(rng, point), _| {
+                sample_point(rng, point);
+                f(point)
+            },
+        )
+        .sum();
+
+    Ok(sum * measure / (sample_count as f64))
+}
+
+// Estimate the integral of f over the unit ball in `dim` dimensions. Points are a uniform
+// direction scaled by u^(1/dim), which makes them uniform in volume
+pub fn integrate_unit_ball(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    dim: usize,
+    sample_count: usize,
+) -> Result<f64> {
+    check_direction_dimension(dim)?;
+    integrate_over(f, dim, sample_count, unit_ball_volume(dim), |rng, point| {
+        sample_direction(rng, point);
+        let radius = rng.gen::<f64>().powf(1.0 / dim as f64);
+        point.iter_mut().for_each(|x| *x *= radius);
+    })
+}
+
+// Estimate the integral of f over the surface of the unit sphere in `dim` dimensions
+pub fn integrate_unit_sphere(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    dim: usize,
+    sample_count: usize,
+) -> Result<f64> {
+    check_direction_dimension(dim)?;
+    integrate_over(
+        f,
+        dim,
+        sample_count,
+        unit_sphere_area(dim),
+        sample_direction,
+    )
+}
+
+// Estimate the integral of f over the unit simplex in `dim` dimensions, the points with
+// nonnegative coordinates summing to at most 1. Equivalently these are the first `dim` barycentric
+// coordinates of the probability simplex, the last being 1 minus their sum. Normalizing dim + 1
+// exponential variates gives points uniform over the simplex
+pub fn integrate_simplex(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    dim: usize,
+    sample_count: usize,
+) -> Result<f64> {
+    integrate_over(
+        f,
+        dim,
+        sample_count,
+        unit_simplex_volume(dim),
+        |rng, point| {
+            point
+                .iter_mut()
+                .for_each(|x| *x = -(1.0 - rng.gen::<f64>()).ln());
+            let total = point.iter().sum::<f64>() - (1.0 - rng.gen::<f64>()).ln();
+            point.iter_mut().for_each(|x| *x /= total);
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_volumes() {
+        assert_approx_eq!(unit_ball_volume(2), PI);
+        assert_approx_eq!(unit_ball_volume(3), 4.0 / 3.0 * PI);
+        assert_approx_eq!(unit_sphere_area(3), 4.0 * PI);
+    }
+
+    #[test]
+    fn test_ball_volume() {
+        assert_approx_eq!(
+            integrate_unit_ball(|_| 1.0, 3, 1000).unwrap(),
+            4.0 / 3.0 * PI
+        );
+    }
+
+    #[test]
+    fn test_ball_radial_integrand() {
+        // Integral of r^2 over the 3-ball is 4 PI / 5
+        let result = integrate_unit_ball(|x| x.iter().map(|c| c * c).sum(), 3, 200_000).unwrap();
+        assert_approx_eq!(result, 4.0 * PI / 5.0, 0.02);
+    }
+
+    #[test]
+    fn test_simplex_volume() {
+        assert_eq!(unit_simplex_volume(0), 1.0);
+        assert_approx_eq!(unit_simplex_volume(3), 1.0 / 6.0);
+        assert_approx_eq!(integrate_simplex(|_| 1.0, 2, 1000).unwrap(), 0.5);
+    }
+
+    #[test]
+    fn test_simplex_points_and_moments() {
+        let inside = integrate_simplex(
+            |x| {
+                let in_simplex = x.iter().all(|&c| c >= 0.0) && x.iter().sum::<f64>() <= 1.0;
+                if in_simplex {
+                    1.0
+                } else {
+                    0.0
+                }
+            },
+            4,
+            1000,
+        )
+        .unwrap();
+        assert_approx_eq!(inside, 1.0 / 24.0);
+
+        // Integral of x over the 2-simplex is 1/6
+        let result = integrate_simplex(|x| x[0], 2, 200_000).unwrap();
+        assert_approx_eq!(result, 1.0 / 6.0, 0.02);
+    }
+
+    #[test]
+    fn test_sphere() {
+        assert_approx_eq!(integrate_unit_sphere(|_| 1.0, 3, 1000).unwrap(), 4.0 * PI);
+        // By symmetry, each squared coordinate averages to 1/3 over the sphere
+        let result = integrate_unit_sphere(|x| x[0] * x[0], 3, 200_000).unwrap();
+        assert_approx_eq!(result, 4.0 * PI / 3.0, 0.02);
+    }
+
+    #[test]
+    fn test_zero_dimensions() {
+        let error = MonteCarloError::InvalidParameter {
+            name: "dimension",
+            value: 0.0,
+        };
+        assert_eq!(integrate_unit_ball(|_| 1.0, 0, 100), Err(error.clone()));
+        assert_eq!(integrate_unit_sphere(|_| 1.0, 0, 100), Err(error));
+    }
+}
@@ -0,0 +1,90 @@
+// Derivatives of integrals with respect to a parameter theta, estimated from the same samples as
+// the integral itself
+use crate::error::{check_integration, MonteCarloError, Result};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Estimate integral from a to b of f(x; theta) dx and its derivative with respect to theta by
+// pathwise differentiation: theta only enters the integrand, so the derivative is the integral of
+// dfdtheta(x) = ∂f/∂theta (x; theta), evaluated at the same sample points. Returns
+// (estimate, gradient_estimate)
+pub fn integrate_and_gradient(
+    f: impl Fn(f64) -> f64 + Sync,
+    dfdtheta: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<(f64, f64)> {
+    check_integration(a, b, sample_count)?;
+    let (sum, gradient_sum) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            (f(x), dfdtheta(x))
+        })
+        .reduce(|| (0.0, 0.0), |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1));
+
+    let scale = (b - a) / (sample_count as f64);
+    Ok((sum * scale, gradient_sum * scale))
+}
+
+// Estimate E[g(X)] for X drawn from a density p(x; theta) by `sample`, and its derivative with
+// respect to theta by the score-function (REINFORCE) estimator E[g(X) ∂/∂theta log p(X; theta)].
+// Here theta enters the sampling distribution rather than the integrand, and `score` is the
+// derivative of the log density. It needs no derivative of g, at the price of a higher variance
+// than pathwise differentiation. Returns (estimate, gradient_estimate)
+pub fn expectation_and_score_gradient(
+    g: impl Fn(f64) -> f64 + Sync,
+    sample: impl Fn(&mut ThreadRng) -> f64 + Sync,
+    score: impl Fn(f64) -> f64 + Sync,
+    sample_count: usize,
+) -> Result<(f64, f64)> {
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    let (sum, gradient_sum) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = sample(rng);
+            let value = g(x);
+            (value, value * score(x))
+        })
+        .reduce(|| (0.0, 0.0), |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1));
+
+    let n = sample_count as f64;
+    Ok((sum / n, gradient_sum / n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_pathwise_gradient_of_linear_parameter() {
+        // d/dtheta of the integral of theta x over [0, 2] is the integral of x, that is 2
+        const THETA: f64 = 3.0;
+        let (estimate, gradient) =
+            integrate_and_gradient(|x| THETA * x, |x| x, 0.0, 2.0, 100_000).unwrap();
+        assert_approx_eq!(estimate, 6.0, 0.02);
+        assert_approx_eq!(gradient, 2.0, 0.02);
+        // Both are estimated from the same samples
+        assert_approx_eq!(estimate, THETA * gradient);
+    }
+
+    #[test]
+    fn test_score_function_gradient() {
+        // For X exponential with rate theta, E[X] = 1 / theta, so d/dtheta E[X] = -1 / theta^2.
+        // The log density is ln(theta) - theta x, with score 1 / theta - x
+        const THETA: f64 = 2.0;
+        let (estimate, gradient) = expectation_and_score_gradient(
+            |x| x,
+            |rng| -(1.0 - rng.gen::<f64>()).ln() / THETA,
+            |x| 1.0 / THETA - x,
+            1_000_000,
+        )
+        .unwrap();
+        assert_approx_eq!(estimate, 0.5, 0.01);
+        assert_approx_eq!(gradient, -0.25, 0.03);
+    }
+}
@@ -0,0 +1,136 @@
+// Integration of fields sampled on a regular grid, e.g. an image, by Monte Carlo over an
+// interpolant of the grid values
+use crate::error::{check_bounds, MonteCarloError, Result};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// How grid values are interpolated between nodes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interp {
+    Nearest,
+    Bilinear,
+}
+
+// Position of `t` in the nodes 0..count, spread evenly from lo to hi, as an index and the
+// fraction of the way to the next node
+fn locate(t: f64, (lo, hi): (f64, f64), count: usize) -> (usize, f64) {
+    if count == 1 {
+        return (0, 0.0);
+    }
+    let scaled = ((t - lo) / (hi - lo) * (count - 1) as f64).clamp(0.0, (count - 1) as f64);
+    let index = (scaled.floor() as usize).min(count - 2);
+    (index, scaled - index as f64)
+}
+
+fn interpolate(
+    grid: &[Vec<f64>],
+    bounds: ((f64, f64), (f64, f64)),
+    mode: Interp,
+    x: f64,
+    y: f64,
+) -> f64 {
+    let (row, ty) = locate(y, bounds.1, grid.len());
+    let (col, tx) = locate(x, bounds.0, grid[0].len());
+    let next_row = (row + 1).min(grid.len() - 1);
+    let next_col = (col + 1).min(grid[0].len() - 1);
+
+    match mode {
+        Interp::Nearest => {
+            let row = if ty < 0.5 { row } else { next_row };
+            let col = if tx < 0.5 { col } else { next_col };
+            grid[row][col]
+        }
+        Interp::Bilinear => {
+            let bottom = grid[row][col] * (1.0 - tx) + grid[row][next_col] * tx;
+            let top = grid[next_row][col] * (1.0 - tx) + grid[next_row][next_col] * tx;
+            bottom * (1.0 - ty) + top * ty
+        }
+    }
+}
+
+// Estimate the integral of the field tabulated by `grid` over bounds = ((x0, x1), (y0, y1)).
+// grid[j][i] is the value at the node x0 + i * dx, y0 + j * dy, with the outermost nodes on the
+// boundary of the domain. Every row must have the same length
+pub fn integrate_grid_2d(
+    grid: &[Vec<f64>],
+    bounds: ((f64, f64), (f64, f64)),
+    mode: Interp,
+    sample_count: usize,
+) -> Result<f64> {
+    if grid.is_empty() || grid[0].is_empty() || sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    if let Some(row) = grid.iter().find(|row| row.len() != grid[0].len()) {
+        return Err(MonteCarloError::DimensionMismatch {
+            expected: grid[0].len(),
+            actual: row.len(),
+        });
+    }
+
+    let ((x0, x1), (y0, y1)) = bounds;
+    check_bounds(x0, x1)?;
+    check_bounds(y0, y1)?;
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(x0..=x1);
+            let y = rng.gen_range(y0..=y1);
+            interpolate(grid, bounds, mode, x, y)
+        })
+        .sum();
+
+    Ok(sum * (x1 - x0) * (y1 - y0) / (sample_count as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    const BOUNDS: ((f64, f64), (f64, f64)) = ((0.0, 2.0), (1.0, 4.0));
+
+    #[test]
+    fn test_constant_grid() {
+        let grid = vec![vec![3.0; 5]; 4];
+        for mode in [Interp::Nearest, Interp::Bilinear] {
+            let estimate = integrate_grid_2d(&grid, BOUNDS, mode, 1000).unwrap();
+            assert_approx_eq!(estimate, 3.0 * 6.0, 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_linear_ramp_grid() {
+        // f(x, y) = x tabulated at x = 0, 0.5, ..., 2, with an exact integral of 2 * 3 = 6.
+        // Bilinear interpolation reproduces it everywhere, nearest-neighbor on average
+        let grid = vec![(0..5).map(|i| i as f64 * 0.5).collect::<Vec<f64>>(); 3];
+        for mode in [Interp::Nearest, Interp::Bilinear] {
+            let estimate = integrate_grid_2d(&grid, BOUNDS, mode, 200_000).unwrap();
+            assert_approx_eq!(estimate, 6.0, 0.01);
+        }
+    }
+
+    #[test]
+    fn test_interpolation_at_nodes() {
+        let grid = vec![vec![0.0, 1.0], vec![2.0, 3.0]];
+        let bounds = ((0.0, 1.0), (0.0, 1.0));
+        assert_eq!(interpolate(&grid, bounds, Interp::Bilinear, 1.0, 1.0), 3.0);
+        assert_eq!(interpolate(&grid, bounds, Interp::Bilinear, 0.5, 0.5), 1.5);
+        assert_eq!(interpolate(&grid, bounds, Interp::Nearest, 0.2, 0.9), 2.0);
+    }
+
+    #[test]
+    fn test_invalid_grid() {
+        let ragged = vec![vec![1.0, 2.0], vec![3.0]];
+        assert_eq!(
+            integrate_grid_2d(&ragged, BOUNDS, Interp::Nearest, 10),
+            Err(MonteCarloError::DimensionMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            integrate_grid_2d(&[], BOUNDS, Interp::Nearest, 10),
+            Err(MonteCarloError::ZeroSamples)
+        );
+    }
+}
@@ -0,0 +1,696 @@
+// Building blocks of the convergence experiments run by the binary
+use crate::error::{check_integration, MonteCarloError, Result};
+use crate::integration::{monte_carlo_integration, sample_integrand};
+use crate::variance_estimator::VarianceEstimator;
+use rayon::prelude::*;
+
+// Run `repetitions` independent integrations of f from a to b with `sample_count` samples each,
+// and return all of their estimates
+pub fn collect_run_estimates(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    repetitions: usize,
+) -> Result<Vec<f64>> {
+    check_integration(a, b, sample_count)?;
+    (0..repetitions)
+        .into_par_iter()
+        .map(|_| Ok(monte_carlo_integration(&f, a, b, sample_count)?.integral))
+        .collect()
+}
+
+// Number of digits printed for each column of a harness row: decimals for the mean of means, and
+// mantissa decimals of the scientific notation for both variances. With `plot`, a convergence
+// experiment also draws its variances in an ASCII plot of that many (columns, rows)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HarnessFormat {
+    pub mean_precision: usize,
+    pub variance_precision: usize,
+    pub plot: Option<(usize, usize)>,
+}
+
+impl Default for HarnessFormat {
+    fn default() -> Self {
+        Self {
+            mean_precision: 2,
+            variance_precision: 1,
+            plot: None,
+        }
+    }
+}
+
+// Draw (sample count, variance) points on log-log axes as `rows` lines of `columns` characters.
+// The first column and the last row are the axes, the points are marked with '*' in the rest,
+// scaled so the smallest and largest sample counts and variances span the whole area. Points with
+// a variance that is not positive and finite cannot be drawn on a log scale and are skipped
+pub fn render_loglog_plot(points: &[(usize, f64)], columns: usize, rows: usize) -> Result<String> {
+    // The plot needs room for the axes
+    if columns < 2 || rows < 2 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "plot size",
+            value: columns.min(rows) as f64,
+        });
+    }
+    let (width, height) = (columns - 1, rows - 1);
+    let mut grid = vec![vec![' '; width]; height];
+
+    let logs: Vec<(f64, f64)> = points
+        .iter()
+        .filter(|&&(n, variance)| n > 0 && variance.is_finite() && variance > 0.0)
+        .map(|&(n, variance)| ((n as f64).log2(), variance.log10()))
+        .collect();
+    let range = |values: &mut dyn Iterator<Item = f64>| {
+        values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        })
+    };
+    let (x_lo, x_hi) = range(&mut logs.iter().map(|&(x, _)| x));
+    let (y_lo, y_hi) = range(&mut logs.iter().map(|&(_, y)| y));
+    // Position of v in [lo, hi] on a scale of `cells` cells, the middle one when the range is empty
+    let scale = |v: f64, lo: f64, hi: f64, cells: usize| {
+        if hi > lo {
+            ((v - lo) / (hi - lo) * (cells - 1) as f64).round() as usize
+        } else {
+            (cells - 1) / 2
+        }
+    };
+    for &(x, y) in &logs {
+        let column = scale(x, x_lo, x_hi, width);
+        let row = height - 1 - scale(y, y_lo, y_hi, height);
+        grid[row][column] = '*';
+    }
+
+    let mut plot = String::new();
+    for line in grid {
+        plot.push('|');
+        plot.extend(line);
+        plot.push('\n');
+    }
+    plot.push('+');
+    plot.extend(std::iter::repeat_n('-', width));
+    Ok(plot)
+}
+
+// One line of the harness output, for the estimator of the run estimates at a sample count. The
+// variances are computed across runs, so they are meaningful even for runs of a single sample,
+// but not with fewer than 2 runs, where they are reported as insufficient instead of as 0
+pub fn format_harness_row(
+    sample_count: usize,
+    ve: &VarianceEstimator,
+    format: &HarnessFormat,
+) -> String {
+    if !ve.is_variance_reliable(2) {
+        return format!(
+            "sample count: {}, mean of means: {:.*}, variance: insufficient samples",
+            sample_count, format.mean_precision, ve.mean
+        );
+    }
+    format!(
+        "sample count: {}, mean of means: {:.*}, variance: {:.*e}, variance of mean: {:.*e}",
+        sample_count,
+        format.mean_precision,
+        ve.mean,
+        format.variance_precision,
+        ve.variance(),
+        format.variance_precision,
+        ve.variance_of_mean()
+    )
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BestRunEstimate {
+    // Plain average of the estimates of all runs
+    pub combined: f64,
+    // Index of the run whose estimate had the lowest variance, the lowest index on ties
+    pub best_repetition: usize,
+    pub best_estimate: f64,
+    pub best_variance: f64,
+}
+
+// Running sum of the run estimates, along with the lowest-variance run so far
+#[derive(Copy, Clone, Debug)]
+struct BestRunAccumulator {
+    sum: f64,
+    count: usize,
+    best_repetition: usize,
+    best_estimate: f64,
+    best_variance: f64,
+}
+
+impl BestRunAccumulator {
+    fn new() -> Self {
+        Self {
+            sum: 0.0,
+            count: 0,
+            best_repetition: usize::MAX,
+            best_estimate: f64::NAN,
+            best_variance: f64::INFINITY,
+        }
+    }
+
+    fn single(repetition: usize, estimate: f64, variance: f64) -> Self {
+        Self {
+            sum: estimate,
+            count: 1,
+            best_repetition: repetition,
+            best_estimate: estimate,
+            best_variance: variance,
+        }
+    }
+
+    // Order-independent, so the result is the same however rayon splits the runs
+    fn merge(lhs: Self, rhs: Self) -> Self {
+        let rhs_is_better =
+            (rhs.best_variance, rhs.best_repetition) < (lhs.best_variance, lhs.best_repetition);
+        let best = if rhs_is_better { rhs } else { lhs };
+        Self {
+            sum: lhs.sum + rhs.sum,
+            count: lhs.count + rhs.count,
+            ..best
+        }
+    }
+}
+
+// Run `repetitions` independent integrations of f from a to b with `sample_count` samples each,
+// and keep the single run with the lowest estimated variance next to the combined estimate. The
+// variance of a run of fewer than 2 samples is unknown rather than 0, so it is taken as infinite
+pub fn track_best_run(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    repetitions: usize,
+) -> Result<BestRunEstimate> {
+    check_integration(a, b, sample_count)?;
+    if repetitions == 0 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "repetitions",
+            value: 0.0,
+        });
+    }
+    let result = (0..repetitions)
+        .into_par_iter()
+        .map(|repetition| {
+            let ve = sample_integrand(&f, a, b, sample_count);
+            let scale = b - a;
+            let variance = match ve.std_error() {
+                Some(std_error) => std_error * std_error * scale * scale,
+                None => f64::INFINITY,
+            };
+            BestRunAccumulator::single(repetition, ve.mean * scale, variance)
+        })
+        .reduce(BestRunAccumulator::new, BestRunAccumulator::merge);
+
+    Ok(BestRunEstimate {
+        combined: result.sum / result.count as f64,
+        best_repetition: result.best_repetition,
+        best_estimate: result.best_estimate,
+        best_variance: result.best_variance,
+    })
+}
+
+// Distribution of the estimates of repeated runs at one sample count. The spread between min,
+// median, and max shows skewness and outlier runs that the mean and variance alone hide
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RunSummary {
+    pub mean: f64,
+    // Sample standard deviation of the estimates, 0 with fewer than 2 runs
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+    // Middle estimate, or the average of the two middle ones for an even number of runs
+    pub median: f64,
+}
+
+impl RunSummary {
+    pub fn from_estimates(estimates: &[f64]) -> Result<Self> {
+        if estimates.is_empty() {
+            return Err(MonteCarloError::ZeroSamples);
+        }
+        let mut sorted = estimates.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        } else {
+            sorted[middle]
+        };
+
+        let mut ve = VarianceEstimator::new();
+        estimates
+            .iter()
+            .for_each(|&estimate| ve.add_sample(estimate));
+        Ok(Self {
+            mean: ve.mean,
+            std: ve.std_dev(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            median,
+        })
+    }
+}
+
+// Number of independent runs at each sample count of a convergence experiment
+pub const EXPERIMENT_REPETITIONS: usize = 128;
+
+// Sample counts of a convergence experiment are the powers of two up to 2^EXPERIMENT_MAX_POWER
+pub const EXPERIMENT_MAX_POWER: u32 = 7;
+
+// Print how the spread of repeated estimates of the integral from a to b of f(x) dx shrinks as
+// the sample count grows, one row per power of two. The runs of all sample counts are then
+// combined into one final estimate with `merge_weighted`, each run weighted by the inverse of the
+// variance across the runs of its row. Returns the summary of the run estimates at each sample
+// count
+pub fn run_convergence_experiment(
+    f: impl Fn(f64) -> f64 + Sync + Copy,
+    f_desc: &str,
+    a: f64,
+    b: f64,
+    expected: f64,
+    format: HarnessFormat,
+) -> Result<Vec<(usize, RunSummary)>> {
+    println!("Estimate {f_desc}. Expected result: {expected}");
+    let mut variances = Vec::new();
+    let mut summaries = Vec::new();
+    let mut runs = Vec::new();
+    for i in 0..=EXPERIMENT_MAX_POWER {
+        let sample_count = 2_usize.pow(i);
+
+        let estimates = collect_run_estimates(f, a, b, sample_count, EXPERIMENT_REPETITIONS)?;
+        let mut ve = VarianceEstimator::new();
+        estimates.iter().for_each(|&result| ve.add_sample(result));
+
+        println!("{}", format_harness_row(sample_count, &ve, &format));
+        variances.push((sample_count, ve.variance()));
+        summaries.push((sample_count, RunSummary::from_estimates(&estimates)?));
+        runs.extend(estimates.iter().map(|&estimate| (estimate, ve.variance())));
+    }
+    let (combined, combined_variance) = merge_weighted(&runs)?;
+    println!(
+        "combined estimate: {:.*}, variance: {:.*e}",
+        format.mean_precision, combined, format.variance_precision, combined_variance
+    );
+    if let Some((columns, rows)) = format.plot {
+        println!("log variance against log sample count:");
+        println!("{}", render_loglog_plot(&variances, columns, rows)?);
+    }
+    println!("==========");
+    Ok(summaries)
+}
+
+// Panic unless an estimate of the integral from a to b of f(x) dx with `sample_count` samples
+// lies within 5 standard errors of `expected`. Needs at least 2 samples for the standard error
+pub fn assert_converges(
+    f: impl Fn(f64) -> f64 + Sync,
+    f_desc: &str,
+    a: f64,
+    b: f64,
+    expected: f64,
+    sample_count: usize,
+) {
+    let ve = sample_integrand(&f, a, b, sample_count);
+    let std_error = ve
+        .std_error()
+        .unwrap_or_else(|| panic!("{f_desc}: insufficient samples to estimate the error"));
+    let estimate = ve.mean * (b - a);
+    let tolerance = 5.0 * std_error * (b - a).abs() + 1e-12;
+    assert!(
+        (estimate - expected).abs() <= tolerance,
+        "{f_desc}: estimate {estimate} is not within {tolerance} of {expected}"
+    );
+}
+
+// Sample count of each test generated by `mc_suite!`
+pub const SUITE_TEST_SAMPLES: usize = 100_000;
+
+// Declare a suite of integrals as `"description" => |x| expr, a..b => expected`, separated by
+// semicolons. Used as a statement, it runs the convergence experiment of each integral, with an
+// optional `, format` after the expected value, and returns early with the error of an experiment
+// that fails. Prefixing every entry with `#[test] name:` instead
+// generates one test function per integral, asserting that its estimate converges to `expected`
+#[macro_export]
+macro_rules! mc_suite {
+    ($(#[test] $name:ident: $desc:literal => $f:expr, $range:expr => $expected:expr);+ $(;)?) => {
+        $(
+            #[test]
+            fn $name() {
+                let range: ::std::ops::Range<f64> = $range;
+                $crate::harness::assert_converges(
+                    $f,
+                    $desc,
+                    range.start,
+                    range.end,
+                    $expected,
+                    $crate::harness::SUITE_TEST_SAMPLES,
+                );
+            }
+        )+
+    };
+    ($($desc:literal => $f:expr, $range:expr => $expected:expr $(, $format:expr)?);+ $(;)?) => {
+        $({
+            let range: ::std::ops::Range<f64> = $range;
+            #[allow(unused_variables)]
+            let format = $crate::harness::HarnessFormat::default();
+            $(let format = $format;)?
+            $crate::harness::run_convergence_experiment(
+                $f,
+                $desc,
+                range.start,
+                range.end,
+                $expected,
+                format,
+            )?;
+        })+
+    };
+}
+
+// Run one integration of f from a to b per entry of `sample_counts`, and return each estimate
+// together with its estimated variance. Every run needs at least 2 samples for its variance
+pub fn collect_weighted_runs(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_counts: &[usize],
+) -> Result<Vec<(f64, f64)>> {
+    sample_counts
+        .par_iter()
+        .map(|&sample_count| {
+            check_integration(a, b, sample_count)?;
+            let ve = sample_integrand(&f, a, b, sample_count);
+            let std_error = ve.std_error().ok_or(MonteCarloError::InsufficientSamples {
+                required: 2,
+                provided: sample_count,
+            })?;
+            Ok((ve.mean * (b - a), std_error * std_error * (b - a) * (b - a)))
+        })
+        .collect()
+}
+
+// Combine independent (estimate, variance) pairs by inverse-variance weighting, the minimum
+// variance unbiased combination, and return the combined estimate and its variance. Runs of
+// equal variance reduce to the plain average. A run of zero variance is exact and gets all of
+// the weight in the limit, so if there are any, the result is the plain average of the exact
+// runs with a variance of 0. A negative, infinite or NaN variance is an error, as is no runs
+pub fn merge_weighted(runs: &[(f64, f64)]) -> Result<(f64, f64)> {
+    if runs.is_empty() {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    if let Some(&(_, variance)) = runs
+        .iter()
+        .find(|&&(_, variance)| !variance.is_finite() || variance < 0.0)
+    {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "variance",
+            value: variance,
+        });
+    }
+
+    let exact: Vec<f64> = runs
+        .iter()
+        .filter(|&&(_, variance)| variance == 0.0)
+        .map(|&(estimate, _)| estimate)
+        .collect();
+    if !exact.is_empty() {
+        return Ok((exact.iter().sum::<f64>() / exact.len() as f64, 0.0));
+    }
+
+    let weight_sum: f64 = runs.iter().map(|&(_, variance)| 1.0 / variance).sum();
+    let weighted_sum: f64 = runs
+        .iter()
+        .map(|&(estimate, variance)| estimate / variance)
+        .sum();
+    Ok((weighted_sum / weight_sum, 1.0 / weight_sum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_collect_run_estimates() {
+        let estimates = collect_run_estimates(|x| x * x, 0.0, 1.0, 256, 128).unwrap();
+        assert_eq!(estimates.len(), 128);
+
+        let mean = estimates.iter().sum::<f64>() / estimates.len() as f64;
+        assert_approx_eq!(mean, 1.0 / 3.0, 0.05);
+    }
+
+    mc_suite! {
+        #[test] suite_square: "x^2 on [0, 1]" => |x| x * x, 0.0..1.0 => 1.0 / 3.0;
+        #[test] suite_sine: "sin(x) on [0, PI]" => |x: f64| x.sin(), 0.0..std::f64::consts::PI => 2.0;
+    }
+
+    #[test]
+    fn test_suite_runs_experiments() -> Result<()> {
+        mc_suite! {
+            "1 on [0, 2]" => |_| 1.0, 0.0..2.0 => 2.0;
+            "x on [0, 1]" => |x| x, 0.0..1.0 => 0.5, HarnessFormat {
+                mean_precision: 4,
+                variance_precision: 2,
+                plot: Some((40, 10)),
+            };
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "x on [0, 1]: estimate")]
+    fn test_assert_converges_rejects_wrong_value() {
+        assert_converges(|x| x, "x on [0, 1]", 0.0, 1.0, 0.6, 10_000);
+    }
+
+    #[test]
+    fn test_format_harness_row() {
+        let mut ve = VarianceEstimator::new();
+        [0.84, 0.8427, 0.8433]
+            .iter()
+            .for_each(|&x| ve.add_sample(x));
+
+        assert_eq!(
+            format_harness_row(4, &ve, &HarnessFormat::default()),
+            "sample count: 4, mean of means: 0.84, variance: 3.1e-6, variance of mean: 1.0e-6"
+        );
+        let precise = HarnessFormat {
+            mean_precision: 4,
+            variance_precision: 3,
+            ..HarnessFormat::default()
+        };
+        assert_eq!(
+            format_harness_row(4, &ve, &precise),
+            "sample count: 4, mean of means: 0.8420, variance: 3.090e-6, variance of mean: 1.030e-6"
+        );
+    }
+
+    #[test]
+    fn test_single_sample_row() {
+        // With one sample per run, the variance across runs is that of f itself, 4/45 for x^2
+        let mut ve = VarianceEstimator::new();
+        collect_run_estimates(|x| x * x, 0.0, 1.0, 1, 4096)
+            .unwrap()
+            .into_iter()
+            .for_each(|estimate| ve.add_sample(estimate));
+        let row = format_harness_row(1, &ve, &HarnessFormat::default());
+        assert!(row.starts_with("sample count: 1, mean of means: 0.3"));
+        assert!(!row.contains("insufficient"));
+        assert_approx_eq!(ve.variance(), 4.0 / 45.0, 0.1);
+
+        let mut single_run = VarianceEstimator::new();
+        single_run.add_sample(0.25);
+        assert_eq!(
+            format_harness_row(1, &single_run, &HarnessFormat::default()),
+            "sample count: 1, mean of means: 0.25, variance: insufficient samples"
+        );
+    }
+
+    #[test]
+    fn test_best_run_of_single_samples() {
+        let result = track_best_run(|x| x, 0.0, 1.0, 1, 8).unwrap();
+        assert_eq!(result.best_variance, f64::INFINITY);
+        assert_eq!(result.best_repetition, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "insufficient samples")]
+    fn test_assert_converges_needs_two_samples() {
+        assert_converges(|x| x, "x on [0, 1]", 0.0, 1.0, 0.5, 1);
+    }
+
+    #[test]
+    fn test_run_summary() {
+        let summary = RunSummary::from_estimates(&[3.0, 1.0, 4.0, 1.5]).unwrap();
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 4.0);
+        assert_eq!(summary.median, 2.25);
+        assert_approx_eq!(summary.mean, 2.375);
+        assert_eq!(
+            RunSummary::from_estimates(&[2.0, 7.0, 5.0]).unwrap().median,
+            5.0
+        );
+        assert_eq!(RunSummary::from_estimates(&[2.0]).unwrap().std, 0.0);
+    }
+
+    #[test]
+    fn test_run_summary_brackets_runs() {
+        let estimates = collect_run_estimates(|x| x.exp(), 0.0, 1.0, 16, 128).unwrap();
+        let summary = RunSummary::from_estimates(&estimates).unwrap();
+        assert!(estimates
+            .iter()
+            .all(|&estimate| summary.min <= estimate && estimate <= summary.max));
+        assert!(summary.min <= summary.median && summary.median <= summary.max);
+
+        let summaries = run_convergence_experiment(
+            |x| x * x,
+            "x^2 on [0, 1]",
+            0.0,
+            1.0,
+            1.0 / 3.0,
+            HarnessFormat::default(),
+        )
+        .unwrap();
+        assert_eq!(summaries.len(), EXPERIMENT_MAX_POWER as usize + 1);
+        assert_eq!(summaries[0].0, 1);
+        let (_, first) = summaries[0];
+        let (_, last) = summaries[summaries.len() - 1];
+        assert!(last.max - last.min < first.max - first.min);
+        assert!(last.std < first.std);
+    }
+
+    #[test]
+    fn test_loglog_plot_shape() {
+        let points: Vec<(usize, f64)> = (0..8).map(|i| (1 << i, 1.0 / (1 << i) as f64)).collect();
+        let plot = render_loglog_plot(&points, 30, 8).unwrap();
+        let lines: Vec<&str> = plot.lines().collect();
+
+        assert_eq!(lines.len(), 8);
+        assert!(lines.iter().all(|line| line.chars().count() == 30));
+        assert!(lines[..7].iter().all(|line| line.starts_with('|')));
+        assert_eq!(lines[7], format!("+{}", "-".repeat(29)));
+        assert_eq!(plot.matches('*').count(), 8);
+    }
+
+    #[test]
+    fn test_loglog_plot_slopes_down_for_decreasing_variance() {
+        let points: Vec<(usize, f64)> = (0..8).map(|i| (1 << i, 3.0 / (1 << i) as f64)).collect();
+        let plot = render_loglog_plot(&points, 30, 8).unwrap();
+        // (column, row) of every point, from left to right
+        let mut marks: Vec<(usize, usize)> = plot
+            .lines()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c == '*')
+                    .map(move |(column, _)| (column, row))
+            })
+            .collect();
+        marks.sort_unstable();
+
+        assert_eq!(marks.first(), Some(&(1, 0)));
+        assert_eq!(marks.last(), Some(&(29, 6)));
+        assert!(marks.windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn test_loglog_plot_skips_undrawable_points() {
+        // A single drawable point lands in the middle of both ranges
+        let plot = render_loglog_plot(&[(1, 0.0), (2, f64::NAN), (4, 1.0)], 5, 3).unwrap();
+        assert_eq!(plot, "|    \n| *  \n+----");
+    }
+
+    #[test]
+    fn test_best_run_is_minimum_variance() {
+        let runs: Vec<(f64, f64)> = (0..1000)
+            .map(|i| (i as f64, ((i * 7919) % 1009) as f64 + 1.0))
+            .collect();
+        let (min_index, &(min_estimate, min_variance)) = runs
+            .iter()
+            .enumerate()
+            .min_by(|lhs, rhs| lhs.1 .1.total_cmp(&rhs.1 .1))
+            .unwrap();
+
+        let result = runs
+            .par_iter()
+            .enumerate()
+            .map(|(i, &(estimate, variance))| BestRunAccumulator::single(i, estimate, variance))
+            .reduce(BestRunAccumulator::new, BestRunAccumulator::merge);
+
+        assert_eq!(result.best_repetition, min_index);
+        assert_eq!(result.best_estimate, min_estimate);
+        assert_eq!(result.best_variance, min_variance);
+        assert_eq!(result.count, 1000);
+    }
+
+    #[test]
+    fn test_best_run_ties_pick_lowest_index() {
+        let lhs = BestRunAccumulator::single(3, 1.0, 0.5);
+        let rhs = BestRunAccumulator::single(1, 2.0, 0.5);
+        assert_eq!(BestRunAccumulator::merge(lhs, rhs).best_repetition, 1);
+        assert_eq!(BestRunAccumulator::merge(rhs, lhs).best_repetition, 1);
+    }
+
+    #[test]
+    fn test_track_best_run() {
+        let result = track_best_run(|x| x * x, 0.0, 1.0, 1000, 32).unwrap();
+        assert!(result.best_repetition < 32);
+        assert_approx_eq!(result.combined, 1.0 / 3.0, 0.05);
+        assert_approx_eq!(result.best_estimate, 1.0 / 3.0, 0.2);
+        assert!(result.best_variance > 0.0);
+    }
+
+    #[test]
+    fn test_merge_weighted_equal_variance() {
+        let (estimate, variance) = merge_weighted(&[(1.0, 0.5), (2.0, 0.5), (6.0, 0.5)]).unwrap();
+        assert_approx_eq!(estimate, 3.0);
+        assert_approx_eq!(variance, 0.5 / 3.0);
+    }
+
+    #[test]
+    fn test_merge_weighted_favors_precise_runs() {
+        // The second run is four times as precise, so it gets four times the weight
+        let (estimate, variance) = merge_weighted(&[(1.0, 0.4), (2.0, 0.1)]).unwrap();
+        assert_approx_eq!(estimate, 1.8);
+        assert_approx_eq!(variance, 0.08);
+    }
+
+    #[test]
+    fn test_merge_weighted_exact_runs_take_all_weight() {
+        assert_eq!(
+            merge_weighted(&[(1.0, 0.0), (3.0, 0.1), (2.0, 0.0)]),
+            Ok((1.5, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_merge_weighted_invalid_runs() {
+        assert_eq!(merge_weighted(&[]), Err(MonteCarloError::ZeroSamples));
+        assert_eq!(
+            merge_weighted(&[(1.0, 0.1), (2.0, -0.1)]),
+            Err(MonteCarloError::InvalidParameter {
+                name: "variance",
+                value: -0.1
+            })
+        );
+        assert!(merge_weighted(&[(1.0, f64::INFINITY)]).is_err());
+        assert!(merge_weighted(&[(1.0, f64::NAN)]).is_err());
+    }
+
+    #[test]
+    fn test_weighted_runs_of_different_sizes() {
+        let runs = collect_weighted_runs(|x| x * x, 0.0, 1.0, &[64, 256, 1024, 4096]).unwrap();
+        assert_eq!(runs.len(), 4);
+        assert!(runs[3].1 < runs[0].1);
+        assert_eq!(
+            collect_weighted_runs(|x| x, 0.0, 1.0, &[64, 1]),
+            Err(MonteCarloError::InsufficientSamples {
+                required: 2,
+                provided: 1
+            })
+        );
+
+        let (estimate, variance) = merge_weighted(&runs).unwrap();
+        assert_approx_eq!(estimate, 1.0 / 3.0, 0.05);
+        assert!(variance < runs[3].1);
+    }
+}
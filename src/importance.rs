@@ -0,0 +1,679 @@
+// Importance sampling: estimating integrals from samples of a proposal density instead of
+// uniform samples
+use crate::error::{check_integration, MonteCarloError, Result};
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+// Gaussian sample with the given mean and standard deviation, with the Box-Muller transform
+pub fn sample_gaussian(rng: &mut impl Rng, mean: f64, std_dev: f64) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    mean + std_dev * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+// Density of the Gaussian distribution with the given mean and standard deviation
+pub fn gaussian_pdf(x: f64, mean: f64, std_dev: f64) -> f64 {
+    let z = (x - mean) / std_dev;
+    (-0.5 * z * z).exp() / (std_dev * (2.0 * PI).sqrt())
+}
+
+// Quantile function of the standard normal distribution, by Acklam's rational approximation with
+// a relative error below 1.2e-9, e.g. 1.96 for p = 0.975. NaN outside of (0, 1), like the
+// floating-point functions of std outside of their domain
+pub fn normal_quantile(p: f64) -> f64 {
+    if p <= 0.0 || p >= 1.0 || p.is_nan() {
+        return f64::NAN;
+    }
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    let polynomial = |coeffs: &[f64], x: f64| coeffs.iter().fold(0.0, |acc, &c| acc * x + c);
+    // Lower tail, the upper one follows by symmetry
+    let tail = |p: f64| {
+        let q = (-2.0 * p.ln()).sqrt();
+        polynomial(&C, q) / (polynomial(&D, q) * q + 1.0)
+    };
+    if p < P_LOW {
+        tail(p)
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        polynomial(&A, r) * q / (polynomial(&B, r) * r + 1.0)
+    } else {
+        -tail(1.0 - p)
+    }
+}
+
+// Natural logarithm of the gamma function for x > 0, with the Lanczos approximation (g = 7), which
+// is accurate to about 15 digits
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    let t = x + 7.5;
+    0.5 * (2.0 * PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+// Gamma(shape, 1) sample with the method of Marsaglia and Tsang. Shapes below 1 are boosted to
+// shape + 1 and scaled back by u^(1 / shape)
+fn sample_gamma(rng: &mut impl Rng, shape: f64) -> f64 {
+    if shape < 1.0 {
+        let u: f64 = 1.0 - rng.gen::<f64>();
+        return sample_gamma(rng, shape + 1.0) * u.powf(1.0 / shape);
+    }
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let z = sample_gaussian(rng, 0.0, 1.0);
+        let v = (1.0 + c * z).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u: f64 = 1.0 - rng.gen::<f64>();
+        if u.ln() < 0.5 * z * z + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+// Beta(alpha, beta) sample on [0, 1], as X / (X + Y) of two gamma samples
+pub fn sample_beta(rng: &mut impl Rng, alpha: f64, beta: f64) -> f64 {
+    let x = sample_gamma(rng, alpha);
+    let y = sample_gamma(rng, beta);
+    x / (x + y)
+}
+
+// Density of the Beta(alpha, beta) distribution at t in [0, 1]
+pub fn beta_pdf(t: f64, alpha: f64, beta: f64) -> f64 {
+    let ln_beta = ln_gamma(alpha) + ln_gamma(beta) - ln_gamma(alpha + beta);
+    ((alpha - 1.0) * t.ln() + (beta - 1.0) * (1.0 - t).ln() - ln_beta).exp()
+}
+
+// Estimate the integral of f dx from `sample_count` points drawn by `sample` from a proposal
+// density `pdf`, as the mean of the weights f(x) / pdf(x). The pdf must be normalized and nonzero
+// wherever f is
+pub fn integrate_importance(
+    f: impl Fn(f64) -> f64 + Sync,
+    sample: impl Fn(&mut ThreadRng) -> f64 + Sync,
+    pdf: impl Fn(f64) -> f64 + Sync,
+    sample_count: usize,
+) -> Result<f64> {
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = sample(rng);
+            f(x) / pdf(x)
+        })
+        .sum();
+
+    Ok(sum / sample_count as f64)
+}
+
+// Proposal distribution for importance sampling, a sampler along with its normalized density
+pub trait Proposal: Sync {
+    fn sample(&self, rng: &mut ThreadRng) -> f64;
+    fn pdf(&self, x: f64) -> f64;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GaussianProposal {
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl Proposal for GaussianProposal {
+    fn sample(&self, rng: &mut ThreadRng) -> f64 {
+        sample_gaussian(rng, self.mean, self.std_dev)
+    }
+
+    fn pdf(&self, x: f64) -> f64 {
+        gaussian_pdf(x, self.mean, self.std_dev)
+    }
+}
+
+// Weighted mixture of proposals, for integrands with several modes that no single component
+// covers. Sampling picks a component with probability proportional to its weight, then samples
+// it. The density is the weighted sum of the component densities, not that of the picked
+// component, which keeps the importance weights unbiased
+#[derive(Clone, Debug)]
+pub struct MixtureProposal<P = GaussianProposal> {
+    // Components with their weights, normalized to sum to 1
+    components: Vec<(f64, P)>,
+}
+
+impl<P: Proposal> MixtureProposal<P> {
+    // Mixture of the (weight, component) pairs. The weights need not sum to 1, but must be
+    // nonnegative and not all zero
+    pub fn new(components: Vec<(f64, P)>) -> Result<Self> {
+        if let Some(&(weight, _)) = components
+            .iter()
+            .find(|(weight, _)| *weight < 0.0 || !weight.is_finite())
+        {
+            return Err(MonteCarloError::InvalidParameter {
+                name: "mixture weight",
+                value: weight,
+            });
+        }
+        let total: f64 = components.iter().map(|(weight, _)| weight).sum();
+        if total <= 0.0 {
+            return Err(MonteCarloError::InvalidParameter {
+                name: "total mixture weight",
+                value: total,
+            });
+        }
+        Ok(Self {
+            components: components
+                .into_iter()
+                .map(|(weight, component)| (weight / total, component))
+                .collect(),
+        })
+    }
+}
+
+impl<P: Proposal> Proposal for MixtureProposal<P> {
+    fn sample(&self, rng: &mut ThreadRng) -> f64 {
+        let mut u: f64 = rng.gen();
+        for (weight, component) in &self.components {
+            if u < *weight {
+                return component.sample(rng);
+            }
+            u -= weight;
+        }
+        // Rounding can leave u just above the last cumulative weight
+        let (_, last) = self
+            .components
+            .iter()
+            .rev()
+            .find(|(weight, _)| *weight > 0.0)
+            .unwrap();
+        last.sample(rng)
+    }
+
+    fn pdf(&self, x: f64) -> f64 {
+        self.components
+            .iter()
+            .map(|(weight, component)| weight * component.pdf(x))
+            .sum()
+    }
+}
+
+// Estimate the integral of f over the support of `proposal` by importance sampling from it
+pub fn integrate_with_proposal(
+    f: impl Fn(f64) -> f64 + Sync,
+    proposal: &impl Proposal,
+    sample_count: usize,
+) -> Result<f64> {
+    integrate_importance(
+        f,
+        |rng| proposal.sample(rng),
+        |x| proposal.pdf(x),
+        sample_count,
+    )
+}
+
+// Estimate the integral of f over the whole real line with a Gaussian proposal of the given mean
+// and standard deviation. f must decay faster than the Gaussian in the tails, or the weights have
+// infinite variance
+pub fn integrate_importance_gaussian(
+    f: impl Fn(f64) -> f64 + Sync,
+    mean: f64,
+    std_dev: f64,
+    sample_count: usize,
+) -> Result<f64> {
+    if std_dev <= 0.0 || !std_dev.is_finite() {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "standard deviation",
+            value: std_dev,
+        });
+    }
+    integrate_with_proposal(f, &GaussianProposal { mean, std_dev }, sample_count)
+}
+
+// Estimate integral from a to b of f(x) dx with samples biased toward one end of the interval: a
+// Beta(alpha, beta) sample on [0, 1] mapped to [a, b], weighted by the inverse of its density.
+// alpha > beta favors b, alpha < beta favors a, and alpha = beta = 1 is plain uniform sampling.
+// The density vanishes at an end where its parameter is above 1, so f must vanish there too, or
+// the weights have infinite variance. An empty interval (a == b) has the integral 0
+pub fn integrate_beta_biased(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    alpha: f64,
+    beta: f64,
+    sample_count: usize,
+) -> Result<f64> {
+    check_integration(a, b, sample_count)?;
+    for (name, value) in [("alpha", alpha), ("beta", beta)] {
+        if value <= 0.0 || value.is_nan() {
+            return Err(MonteCarloError::InvalidParameter { name, value });
+        }
+    }
+    let width = b - a;
+    // The density of the mapped samples would divide by the zero width
+    if width == 0.0 {
+        return Ok(0.0);
+    }
+    integrate_importance(
+        f,
+        |rng| a + width * sample_beta(rng, alpha, beta),
+        |x| beta_pdf((x - a) / width, alpha, beta) / width,
+        sample_count,
+    )
+}
+
+// Cap on the magnitude of importance weights
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WeightCap {
+    // Clamp the weights to [-cap, cap]
+    Absolute(f64),
+    // Clamp the weights to the given quantile of their magnitudes in the run, e.g. 0.99 clamps
+    // the largest 1%
+    Percentile(f64),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ClampedEstimate {
+    // Estimate from the clamped weights
+    pub estimate: f64,
+    // Number of weights larger than the cap
+    pub clamped_count: usize,
+    // Cap that was applied, as an absolute value
+    pub cap: f64,
+    // Weight removed by clamping, divided by the sample count. Adding it back gives the unclamped
+    // estimate, it is an estimate of the bias introduced by clamping
+    pub removed_weight: f64,
+}
+
+// Same as `integrate_importance`, but clamps the magnitude of the weights at `cap`. When the pdf
+// is a poor match for f, a few samples land where the pdf is tiny and get enormous weights that
+// dominate the variance. Clamping them trades that variance for a bias: the estimate converges to
+// the integral of the clamped weights, which misses E[max(|w| - cap, 0)]. The bias shrinks as the
+// cap grows, while the variance grows with it, so a cap only a little above the typical weight
+// is usually best. `removed_weight` reports how much clamping took off in this run
+pub fn integrate_importance_clamped(
+    f: impl Fn(f64) -> f64 + Sync,
+    sample: impl Fn(&mut ThreadRng) -> f64 + Sync,
+    pdf: impl Fn(f64) -> f64 + Sync,
+    sample_count: usize,
+    cap: WeightCap,
+) -> Result<ClampedEstimate> {
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    let weights: Vec<f64> = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = sample(rng);
+            f(x) / pdf(x)
+        })
+        .collect();
+
+    let cap = match cap {
+        WeightCap::Absolute(cap) if cap >= 0.0 => cap,
+        WeightCap::Absolute(cap) => {
+            return Err(MonteCarloError::InvalidParameter {
+                name: "weight cap",
+                value: cap,
+            });
+        }
+        WeightCap::Percentile(quantile) => {
+            if !(0.0..=1.0).contains(&quantile) {
+                return Err(MonteCarloError::InvalidParameter {
+                    name: "quantile",
+                    value: quantile,
+                });
+            }
+            let mut magnitudes: Vec<f64> = weights.iter().map(|w| w.abs()).collect();
+            let rank =
+                ((quantile * (sample_count - 1) as f64).round() as usize).min(sample_count - 1);
+            *magnitudes.select_nth_unstable_by(rank, f64::total_cmp).1
+        }
+    };
+
+    let (sum, removed, clamped_count) = weights
+        .par_iter()
+        .map(|&w| {
+            let clamped = w.clamp(-cap, cap);
+            (clamped, w - clamped, usize::from(clamped != w))
+        })
+        .reduce(
+            || (0.0, 0.0, 0),
+            |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1, lhs.2 + rhs.2),
+        );
+
+    let n = sample_count as f64;
+    Ok(ClampedEstimate {
+        estimate: sum / n,
+        clamped_count,
+        cap,
+        removed_weight: removed / n,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deterministic::{gauss_legendre, simpson};
+    use crate::variance_estimator::VarianceEstimator;
+    use approx_eq::assert_approx_eq;
+
+    // Integrate 1 over [0, 1] with the proposal 4x^3, whose weights 1 / (4x^3) have infinite
+    // variance because the pdf vanishes at 0
+    fn mismatched(sample_count: usize, cap: Option<WeightCap>) -> ClampedEstimate {
+        let sample = |rng: &mut ThreadRng| (1.0 - rng.gen::<f64>()).powf(0.25);
+        let pdf = |x: f64| 4.0 * x * x * x;
+        match cap {
+            Some(cap) => {
+                integrate_importance_clamped(|_| 1.0, sample, pdf, sample_count, cap).unwrap()
+            }
+            None => ClampedEstimate {
+                estimate: integrate_importance(|_| 1.0, sample, pdf, sample_count).unwrap(),
+                clamped_count: 0,
+                cap: f64::INFINITY,
+                removed_weight: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_gaussian_moments() {
+        let mut rng = thread_rng();
+        let mut ve = VarianceEstimator::new();
+        (0..1_000_000).for_each(|_| ve.add_sample(sample_gaussian(&mut rng, 3.0, 2.0)));
+        assert_approx_eq!(ve.mean, 3.0, 0.005);
+        assert_approx_eq!(ve.variance(), 4.0, 0.01);
+    }
+
+    #[test]
+    fn test_normal_quantile() {
+        assert_eq!(normal_quantile(0.5), 0.0);
+        assert!((normal_quantile(0.975) - 1.959_963_985).abs() < 1e-8);
+        assert!((normal_quantile(0.995) - 2.575_829_304).abs() < 1e-8);
+        assert!((normal_quantile(0.001) + 3.090_232_306).abs() < 1e-8);
+        assert!((normal_quantile(0.2) + normal_quantile(0.8)).abs() < 1e-15);
+        assert!(normal_quantile(0.0).is_nan());
+        assert!(normal_quantile(1.5).is_nan());
+    }
+
+    #[test]
+    fn test_ln_gamma() {
+        // Gamma(n) = (n - 1)!, and Gamma(1/2) = sqrt(PI)
+        assert_approx_eq!(ln_gamma(1.0).exp(), 1.0, 1e-13);
+        assert_approx_eq!(ln_gamma(5.0).exp(), 24.0, 1e-13);
+        assert_approx_eq!(ln_gamma(0.5).exp(), PI.sqrt(), 1e-13);
+        assert_approx_eq!(ln_gamma(0.25).exp(), 3.625_609_908_221_908, 1e-13);
+    }
+
+    #[test]
+    fn test_beta_moments() {
+        // Beta(2, 5) has mean 2/7 and variance 10 / (49 * 8)
+        let mut rng = thread_rng();
+        let mut ve = VarianceEstimator::new();
+        (0..200_000).for_each(|_| ve.add_sample(sample_beta(&mut rng, 2.0, 5.0)));
+        assert_approx_eq!(ve.mean, 2.0 / 7.0, 0.01);
+        assert_approx_eq!(ve.variance(), 10.0 / 392.0, 0.02);
+        // Shapes below 1 take the boosted path
+        let mut ve = VarianceEstimator::new();
+        (0..200_000).for_each(|_| ve.add_sample(sample_beta(&mut rng, 0.5, 0.5)));
+        assert_approx_eq!(ve.mean, 0.5, 0.01);
+        assert_approx_eq!(ve.variance(), 0.125, 0.02);
+
+        // The Beta(2, 5) density is a polynomial of degree 5, integrated exactly with 3 points
+        assert_approx_eq!(
+            gauss_legendre(|t| beta_pdf(t, 2.0, 5.0), 0.0, 1.0, 3).unwrap(),
+            1.0,
+            1e-12
+        );
+    }
+
+    #[test]
+    fn test_beta_biased_uniform_case() {
+        // With alpha = beta = 1, the density is 1 and every weight is (b - a) f(x)
+        assert_approx_eq!(beta_pdf(0.3, 1.0, 1.0), 1.0, 1e-13);
+        let estimate = integrate_beta_biased(|x| x * x, 1.0, 3.0, 1.0, 1.0, 100_000).unwrap();
+        assert_approx_eq!(estimate, 26.0 / 3.0, 0.01);
+    }
+
+    #[test]
+    fn test_beta_biased_empty_interval() {
+        assert_eq!(
+            integrate_beta_biased(|x| x * x, 2.0, 2.0, 2.0, 0.5, 1000),
+            Ok(0.0)
+        );
+    }
+
+    #[test]
+    fn test_beta_biased_toward_end() {
+        // x^3 on [0, 2] puts most of its mass near 2, which Beta(3, 1) favors
+        let estimate = integrate_beta_biased(|x| x * x * x, 0.0, 2.0, 3.0, 1.0, 100_000).unwrap();
+        assert_approx_eq!(estimate, 4.0, 0.01);
+    }
+
+    #[test]
+    fn test_gaussian_pdf() {
+        assert_approx_eq!(gaussian_pdf(0.0, 0.0, 1.0), 1.0 / (2.0 * PI).sqrt());
+        assert_approx_eq!(
+            gaussian_pdf(3.0, 1.0, 2.0),
+            gaussian_pdf(1.0, 0.0, 1.0) / 2.0
+        );
+    }
+
+    #[test]
+    fn test_importance_gaussian_proposal() {
+        // The integral of exp(-x^2) over the real line is sqrt(PI)
+        let estimate =
+            integrate_importance_gaussian(|x| (-x * x).exp(), 0.0, 1.0, 100_000).unwrap();
+        assert_approx_eq!(estimate, PI.sqrt(), 0.01);
+    }
+
+    // Two separated bumps of total integral 2
+    fn bimodal(x: f64) -> f64 {
+        gaussian_pdf(x, -3.0, 0.5) + gaussian_pdf(x, 3.0, 0.5)
+    }
+
+    fn mixture() -> MixtureProposal {
+        MixtureProposal::new(vec![
+            (
+                1.0,
+                GaussianProposal {
+                    mean: -3.0,
+                    std_dev: 0.6,
+                },
+            ),
+            (
+                1.0,
+                GaussianProposal {
+                    mean: 3.0,
+                    std_dev: 0.6,
+                },
+            ),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mixture_pdf_is_normalized() {
+        let mixture = MixtureProposal::new(vec![
+            (
+                3.0,
+                GaussianProposal {
+                    mean: -1.0,
+                    std_dev: 0.5,
+                },
+            ),
+            (
+                1.0,
+                GaussianProposal {
+                    mean: 4.0,
+                    std_dev: 0.5,
+                },
+            ),
+            (
+                0.0,
+                GaussianProposal {
+                    mean: 0.0,
+                    std_dev: 1.0,
+                },
+            ),
+        ])
+        .unwrap();
+        assert_approx_eq!(
+            simpson(|x| mixture.pdf(x), -12.0, 12.0, 2000).unwrap(),
+            1.0,
+            1e-9
+        );
+
+        // A quarter of the samples come from the component around 4
+        let mut rng = thread_rng();
+        let above = (0..100_000)
+            .filter(|_| mixture.sample(&mut rng) > 1.5)
+            .count();
+        assert_approx_eq!(above as f64 / 100_000.0, 0.25, 0.05);
+    }
+
+    #[test]
+    fn test_mixture_covers_bimodal_integrand() {
+        let single = GaussianProposal {
+            mean: 0.0,
+            std_dev: 1.0,
+        };
+        let mixture = mixture();
+        let mut single_ve = VarianceEstimator::new();
+        let mut mixture_ve = VarianceEstimator::new();
+        for _ in 0..64 {
+            single_ve.add_sample(integrate_with_proposal(bimodal, &single, 1000).unwrap());
+            mixture_ve.add_sample(integrate_with_proposal(bimodal, &mixture, 1000).unwrap());
+        }
+
+        assert_approx_eq!(mixture_ve.mean, 2.0, 0.01);
+        assert!(mixture_ve.std_dev() < 0.02);
+        assert!(single_ve.std_dev() > 10.0 * mixture_ve.std_dev());
+    }
+
+    #[test]
+    fn test_importance_matched_pdf() {
+        // With a pdf proportional to f, every weight is the integral
+        let estimate = integrate_importance(
+            |x| 3.0 * x * x,
+            |rng| (1.0 - rng.gen::<f64>()).cbrt(),
+            |x| 3.0 * x * x,
+            100,
+        )
+        .unwrap();
+        assert_approx_eq!(estimate, 1.0);
+    }
+
+    #[test]
+    fn test_clamping_trades_variance_for_bias() {
+        const CAP: f64 = 100.0;
+        let mut unclamped = VarianceEstimator::new();
+        let mut clamped = VarianceEstimator::new();
+        for _ in 0..1024 {
+            unclamped.add_sample(mismatched(1000, None).estimate);
+            clamped.add_sample(mismatched(1000, Some(WeightCap::Absolute(CAP))).estimate);
+        }
+
+        // Weights are above the cap for x < x0 = (4 CAP)^(-1/3), where the clamped weight has
+        // expectation CAP * x0^4 instead of x0. The bias is x0 - CAP x0^4 = 3 x0 / 4
+        let x0 = (4.0 * CAP).powf(-1.0 / 3.0);
+        assert!((clamped.mean - (1.0 - 0.75 * x0)).abs() < 0.02);
+        assert!(clamped.variance() < unclamped.variance() / 2.0);
+    }
+
+    #[test]
+    fn test_clamping_reports_removed_weight() {
+        let result = mismatched(10_000, Some(WeightCap::Percentile(0.99)));
+        assert!((90..=100).contains(&result.clamped_count));
+        assert!(result.removed_weight > 0.0);
+        let reference =
+            integrate_importance_clamped(|_| 1.0, |_| 0.5, |_| 1.0, 10, WeightCap::Absolute(0.5))
+                .unwrap();
+        assert_eq!(reference.clamped_count, 10);
+        assert_approx_eq!(reference.estimate, 0.5);
+        assert_approx_eq!(reference.removed_weight, 0.5);
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let gaussian = GaussianProposal {
+            mean: 0.0,
+            std_dev: 1.0,
+        };
+        assert!(MixtureProposal::new(vec![(-1.0, gaussian), (2.0, gaussian)]).is_err());
+        assert!(MixtureProposal::new(vec![(0.0, gaussian)]).is_err());
+        assert_eq!(
+            integrate_importance(|x| x, |_| 0.5, |_| 1.0, 0),
+            Err(MonteCarloError::ZeroSamples)
+        );
+        assert!(integrate_importance_gaussian(|x| x, 0.0, 0.0, 100).is_err());
+        assert!(integrate_beta_biased(|x| x, 0.0, 1.0, 0.0, 1.0, 100).is_err());
+        assert!(integrate_beta_biased(|x| x, 1.0, 0.0, 1.0, 1.0, 100).is_err());
+        assert!(integrate_importance_clamped(
+            |x| x,
+            |_| 0.5,
+            |_| 1.0,
+            10,
+            WeightCap::Absolute(-1.0)
+        )
+        .is_err());
+        assert!(integrate_importance_clamped(
+            |x| x,
+            |_| 0.5,
+            |_| 1.0,
+            10,
+            WeightCap::Percentile(1.5)
+        )
+        .is_err());
+    }
+}
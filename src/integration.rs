@@ -0,0 +1,2067 @@
+use crate::deterministic::gauss_legendre;
+use crate::error::{check_bounds, check_integration, MonteCarloError, Result};
+use crate::parallel_estimator::accumulate_parallel;
+use crate::ratio_estimator::RatioEstimator;
+use crate::stable_rng::StableRng;
+use crate::stratified::{allocate, stratum_bounds};
+use crate::variance_estimator::VarianceEstimator;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Integrand as a trait object, for storing heterogeneous integrands in a collection at runtime
+pub type Integrand = dyn Fn(f64) -> f64 + Sync;
+pub type BoxedIntegrand = Box<Integrand>;
+
+// Result of a Monte Carlo integration from a to b, with both the integral and the plain mean of
+// the integrand values it was scaled from, so that neither has to be guessed from the other
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IntegralEstimate {
+    // Estimate of the integral, `sample_mean * (b - a)`
+    pub integral: f64,
+    // Mean of the sampled values of f
+    pub sample_mean: f64,
+    // Variance of `integral`, i.e. the variance of the mean of f scaled by (b - a)^2
+    pub variance: f64,
+    pub samples: usize,
+}
+
+impl IntegralEstimate {
+    // Scale the integrand values accumulated in `ve`, sampled uniformly on [a, b]
+    pub fn from_estimator(ve: &VarianceEstimator, a: f64, b: f64) -> Self {
+        let width = b - a;
+        Self {
+            integral: ve.mean * width,
+            sample_mean: ve.mean,
+            variance: ve.variance_of_mean() * width * width,
+            samples: ve.sample_count() as usize,
+        }
+    }
+
+    // Standard error of `integral`
+    pub fn std_error(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    // How many leading decimal digits of `integral` are trustworthy, floor(log10(|integral| /
+    // std_error)), e.g. 3 for 2.00314 ± 0.001, so "2.00" is worth printing. 0 when the error is as
+    // large as the estimate itself, which includes every estimate of 0, or when there are too few
+    // samples for an error. A zero error (a constant integrand) gives MAX_SIGNIFICANT_DIGITS
+    pub fn significant_digits(&self) -> usize {
+        let magnitude = self.integral.abs();
+        if self.samples < 2 || magnitude == 0.0 || !magnitude.is_finite() {
+            return 0;
+        }
+        let std_error = self.std_error();
+        if std_error <= magnitude * 10f64.powi(-(MAX_SIGNIFICANT_DIGITS as i32)) {
+            return MAX_SIGNIFICANT_DIGITS;
+        }
+        (magnitude / std_error).log10().floor().max(0.0) as usize
+    }
+}
+
+// Upper bound of `IntegralEstimate::significant_digits`, about the precision of an f64
+pub const MAX_SIGNIFICANT_DIGITS: usize = 15;
+
+// Whether uniform samples of [a, b] may land exactly on b. For a continuous distribution the
+// difference has measure zero, so it does not change the estimate, but an integrand singular at
+// b such as 1 / (b - x) must never be evaluated there. The lower bound a is always included
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UpperBound {
+    // Sample [a, b], what the integration functions do unless told otherwise
+    #[default]
+    Inclusive,
+    // Sample [a, b)
+    Exclusive,
+}
+
+impl UpperBound {
+    pub fn sample<R: Rng>(self, rng: &mut R, a: f64, b: f64) -> f64 {
+        match self {
+            UpperBound::Inclusive => rng.gen_range(a..=b),
+            UpperBound::Exclusive => rng.gen_range(a..b),
+        }
+    }
+}
+
+// Estimate integral from a to b of f(x) dx
+pub fn monte_carlo_integration(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    Ok(IntegralEstimate::from_estimator(
+        &sample_integrand(&f, a, b, sample_count),
+        a,
+        b,
+    ))
+}
+
+// Estimate integral from a to b of f(x) dx by evaluating f at the provided sample points.
+// The points are assumed to lie within [a, b]. Checking that is the caller's responsibility,
+// and is only asserted in debug builds. An empty set of points has no mean and is an error. The
+// variance is that of uniform random points, so it is only meaningful for such points
+pub fn integrate_from_samples(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    samples: &[f64],
+) -> Result<IntegralEstimate> {
+    if samples.is_empty() {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    debug_assert!(
+        samples.iter().all(|x| (a..=b).contains(x)),
+        "sample points must lie within [a, b]"
+    );
+
+    let ve = samples
+        .par_iter()
+        .fold(VarianceEstimator::new, |mut ve, &x| {
+            ve.add_sample(f(x));
+            ve
+        })
+        .reduce(VarianceEstimator::new, VarianceEstimator::merge);
+
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Estimate integral from a to b of |f(x)| dx, the normalization of the optimal importance
+// sampling density |f| / ∫|f| for a signed integrand
+pub fn integrate_abs(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<f64> {
+    Ok(monte_carlo_integration(|x| f(x).abs(), a, b, sample_count)?.integral)
+}
+
+// Estimate integral from a to b of f dx for an integrand whose samples carry an intrinsic weight,
+// e.g. a phase-space factor. f returns (value, weight), and the estimate is the weighted mean of
+// the values, mean(value * weight) / mean(weight), scaled by b - a. Unlike importance sampling,
+// the points are still drawn uniformly. The variance is the delta-method one of the ratio, see
+// `RatioEstimator::variance`, and `sample_mean` is the weighted mean
+pub fn integrate_weighted_fn(
+    f: impl Fn(f64) -> (f64, f64) + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    let re: RatioEstimator = accumulate_parallel(sample_count, |rng, _| {
+        let (value, weight) = f(rng.gen_range(a..=b));
+        (value * weight, weight)
+    })?;
+
+    let width = b - a;
+    Ok(IntegralEstimate {
+        integral: re.ratio() * width,
+        sample_mean: re.ratio(),
+        variance: re.variance() * width * width,
+        samples: sample_count,
+    })
+}
+
+// Method used by `integrate_hybrid`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IntegrationMethod {
+    GaussLegendre,
+    MonteCarlo,
+}
+
+// Estimate integral from a to b of f(x) dx with `sample_count` evaluations of f. Below
+// `threshold`, a handful of random samples is much worse than the same number of Gauss-Legendre
+// nodes, so it uses `sample_count`-point Gauss-Legendre quadrature instead, and Monte Carlo from
+// `threshold` on. Quadrature has no variance to estimate, and assumes f is smooth. Returns the
+// estimate and the method used
+pub fn integrate_hybrid(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    threshold: usize,
+) -> Result<(f64, IntegrationMethod)> {
+    check_integration(a, b, sample_count)?;
+    if sample_count < threshold {
+        Ok((
+            gauss_legendre(f, a, b, sample_count)?,
+            IntegrationMethod::GaussLegendre,
+        ))
+    } else {
+        Ok((
+            monte_carlo_integration(f, a, b, sample_count)?.integral,
+            IntegrationMethod::MonteCarlo,
+        ))
+    }
+}
+
+// Same as `monte_carlo_integration`, but dispatches dynamically on the integrand
+pub fn integrate_dyn(
+    f: &Integrand,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<IntegralEstimate> {
+    monte_carlo_integration(f, a, b, sample_count)
+}
+
+// Number of samples drawn from each independently seeded generator in seeded integration.
+// Fixing it (instead of splitting by thread) keeps the results independent of the thread count
+pub(crate) const SEEDED_CHUNK_SIZE: usize = 4096;
+
+// Generator for the `chunk`-th chunk of samples of a seeded run. ChaCha is counter-based, so
+// every chunk gets its own stream of the same key instead of a reseeded generator, which
+// guarantees the streams never overlap
+pub(crate) fn chunk_rng(seed: u64, chunk: usize) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(chunk as u64);
+    rng
+}
+
+fn sample_chunk(
+    f: &impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    upper_bound: UpperBound,
+    sample_count: usize,
+    seed: u64,
+    chunk: usize,
+) -> VarianceEstimator {
+    let mut rng = chunk_rng(seed, chunk);
+    let start = chunk * SEEDED_CHUNK_SIZE;
+    let end = (start + SEEDED_CHUNK_SIZE).min(sample_count);
+
+    let mut ve = VarianceEstimator::new();
+    (start..end).for_each(|_| ve.add_sample(f(upper_bound.sample(&mut rng, a, b))));
+    ve
+}
+
+// Evaluate f at `sample_count` uniformly distributed points of [a, b], and accumulate the values
+// into a VarianceEstimator. The result only depends on `seed`, not on how rayon schedules the work
+pub fn sample_integrand_seeded(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: u64,
+) -> Result<VarianceEstimator> {
+    check_integration(a, b, sample_count)?;
+    Ok(sample_integrand_seeded_bounded(
+        f,
+        a,
+        b,
+        UpperBound::Inclusive,
+        sample_count,
+        seed,
+    ))
+}
+
+// Same as `sample_integrand_seeded`, with control over whether b can be sampled
+pub(crate) fn sample_integrand_seeded_bounded(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    upper_bound: UpperBound,
+    sample_count: usize,
+    seed: u64,
+) -> VarianceEstimator {
+    let chunk_count = sample_count.div_ceil(SEEDED_CHUNK_SIZE);
+    let chunks: Vec<VarianceEstimator> = (0..chunk_count)
+        .into_par_iter()
+        .map(|chunk| sample_chunk(&f, a, b, upper_bound, sample_count, seed, chunk))
+        .collect();
+
+    chunks
+        .into_iter()
+        .fold(VarianceEstimator::new(), VarianceEstimator::merge)
+}
+
+// Single-threaded version of `sample_integrand_seeded`, for stepping through an integrand in a
+// debugger. It draws the same samples and merges them in the same order, so the results match
+pub fn sample_integrand_sequential(
+    f: impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: u64,
+) -> Result<VarianceEstimator> {
+    check_integration(a, b, sample_count)?;
+    Ok((0..sample_count.div_ceil(SEEDED_CHUNK_SIZE))
+        .map(|chunk| sample_chunk(&f, a, b, UpperBound::Inclusive, sample_count, seed, chunk))
+        .fold(VarianceEstimator::new(), VarianceEstimator::merge))
+}
+
+// Estimate integral from a to b of f(x) dx with StableRng, one stream per chunk. Unlike
+// `integrate_seeded`, the result for a given seed is guaranteed not to change across versions
+// of this crate or its dependencies, as long as f itself evaluates identically
+pub fn integrate_seeded_stable(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: u64,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    let chunks: Vec<VarianceEstimator> = (0..sample_count.div_ceil(SEEDED_CHUNK_SIZE))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut rng = StableRng::with_stream(seed, chunk as u64);
+            let start = chunk * SEEDED_CHUNK_SIZE;
+            let end = (start + SEEDED_CHUNK_SIZE).min(sample_count);
+
+            let mut ve = VarianceEstimator::new();
+            (start..end).for_each(|_| ve.add_sample(f(rng.next_in(a, b))));
+            ve
+        })
+        .collect();
+
+    let ve = chunks
+        .into_iter()
+        .fold(VarianceEstimator::new(), VarianceEstimator::merge);
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Estimate integral from a to b of f(x) dx, reproducibly for a given seed
+pub fn integrate_seeded(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: u64,
+) -> Result<IntegralEstimate> {
+    let ve = sample_integrand_seeded(f, a, b, sample_count, seed)?;
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Single-threaded version of `integrate_seeded`
+pub fn integrate_sequential(
+    f: impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: u64,
+) -> Result<IntegralEstimate> {
+    let ve = sample_integrand_sequential(f, a, b, sample_count, seed)?;
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Estimate integral from a to b of f(x) dx after 1, 2, 4, ..., 2^max_power samples of a single
+// run, for convergence plots. The samples are the same as `integrate_seeded` with the same seed,
+// so the last checkpoint matches a direct run of 2^max_power samples. A `max_power` whose sample
+// count does not fit in a usize is an error
+pub fn integrate_logscale_trace(
+    f: impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    max_power: u32,
+    seed: u64,
+) -> Result<Vec<(usize, f64)>> {
+    check_bounds(a, b)?;
+    let sample_count = 1_usize
+        .checked_shl(max_power)
+        .ok_or(MonteCarloError::InvalidParameter {
+            name: "max_power",
+            value: max_power as f64,
+        })?;
+    let mut trace = Vec::with_capacity(max_power as usize + 1);
+    let mut ve = VarianceEstimator::new();
+    let mut next_checkpoint = 1;
+
+    for chunk in 0..sample_count.div_ceil(SEEDED_CHUNK_SIZE) {
+        let mut rng = chunk_rng(seed, chunk);
+        let start = chunk * SEEDED_CHUNK_SIZE;
+        let end = (start + SEEDED_CHUNK_SIZE).min(sample_count);
+        for i in start..end {
+            ve.add_sample(f(rng.gen_range(a..=b)));
+            if i + 1 == next_checkpoint {
+                trace.push((next_checkpoint, ve.mean * (b - a)));
+                next_checkpoint = next_checkpoint.saturating_mul(2);
+            }
+        }
+    }
+
+    Ok(trace)
+}
+
+// Snapshots of a single seeded run of f on [a, b], sampled in parallel: for every cumulative
+// sample count in `checkpoints`, which must be increasing and not empty, the estimator of the
+// first that many samples. The samples are numbered as in `sample_integrand_seeded`, and each
+// chunk splits its samples at the checkpoints it spans, so every snapshot matches a separate run
+// of that many samples with the same seed, up to rounding. The run has as many samples as the
+// last checkpoint
+pub fn sample_integrand_checkpoints(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    checkpoints: &[usize],
+    seed: u64,
+) -> Result<Vec<VarianceEstimator>> {
+    let sample_count = checkpoints.last().copied().unwrap_or(0);
+    check_integration(a, b, sample_count)?;
+    if let Some(pair) = checkpoints.windows(2).find(|pair| pair[0] >= pair[1]) {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "checkpoint",
+            value: pair[1] as f64,
+        });
+    }
+
+    // The samples of each chunk between consecutive checkpoints, along with the index of the
+    // first checkpoint that includes them
+    let chunk_count = sample_count.div_ceil(SEEDED_CHUNK_SIZE);
+    let segments: Vec<Vec<(usize, VarianceEstimator)>> = (0..chunk_count)
+        .into_par_iter()
+        .map(|chunk| {
+            let mut rng = chunk_rng(seed, chunk);
+            let start = chunk * SEEDED_CHUNK_SIZE;
+            let end = (start + SEEDED_CHUNK_SIZE).min(sample_count);
+
+            let mut segments: Vec<(usize, VarianceEstimator)> = Vec::new();
+            for i in start..end {
+                let checkpoint = checkpoints.partition_point(|&count| count <= i);
+                let value = f(rng.gen_range(a..=b));
+                match segments.last_mut() {
+                    Some((last, ve)) if *last == checkpoint => ve.add_sample(value),
+                    _ => {
+                        let mut ve = VarianceEstimator::new();
+                        ve.add_sample(value);
+                        segments.push((checkpoint, ve));
+                    }
+                }
+            }
+            segments
+        })
+        .collect();
+
+    let mut increments = vec![VarianceEstimator::new(); checkpoints.len()];
+    for (checkpoint, ve) in segments.into_iter().flatten() {
+        increments[checkpoint] = VarianceEstimator::merge(increments[checkpoint], ve);
+    }
+    Ok(increments
+        .into_iter()
+        .scan(VarianceEstimator::new(), |total, increment| {
+            *total = VarianceEstimator::merge(*total, increment);
+            Some(*total)
+        })
+        .collect())
+}
+
+// Draw `sample_count` samples of f in parallel into a VarianceEstimator
+pub(crate) fn sample_integrand(
+    f: &(impl Fn(f64) -> f64 + Sync),
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> VarianceEstimator {
+    sample_integrand_bounded(f, a, b, UpperBound::Inclusive, sample_count)
+}
+
+// Same as `sample_integrand`, with control over whether b can be sampled
+pub(crate) fn sample_integrand_bounded(
+    f: &(impl Fn(f64) -> f64 + Sync),
+    a: f64,
+    b: f64,
+    upper_bound: UpperBound,
+    sample_count: usize,
+) -> VarianceEstimator {
+    accumulate_parallel(sample_count, |rng, _| f(upper_bound.sample(rng, a, b)))
+        .expect("a VarianceEstimator accepts every sample")
+}
+
+// Estimate integral from a to b of f(x) dx, sampling in batches of `batch_size` for as long as
+// `should_continue` returns true, up to `max_samples` samples. The predicate is called after each
+// batch with the estimator of the integrand values so far (not scaled by b - a). Empty batches
+// would never make progress, so a `batch_size` of 0 is an error
+pub fn integrate_while(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    should_continue: impl Fn(&VarianceEstimator) -> bool,
+    batch_size: usize,
+    max_samples: usize,
+) -> Result<IntegralEstimate> {
+    integrate_while_batched(
+        f,
+        a,
+        b,
+        should_continue,
+        std::iter::repeat(batch_size),
+        max_samples,
+    )
+}
+
+// Endless sequence of batch sizes growing geometrically: `initial`, then each one `growth` times
+// the previous, rounded up, until `max_batch` which is repeated from then on. Early batches are
+// small so that the first convergence checks come quickly, and later ones large so that the
+// checks and the parallel dispatch cost little relative to the sampling. With this policy the
+// samples drawn past the point where sampling could have stopped are at most one batch, i.e. a
+// fraction (growth - 1) / growth of the total at worst, until the cap
+#[derive(Copy, Clone, Debug)]
+pub struct GeometricBatches {
+    next: usize,
+    growth: f64,
+    max_batch: usize,
+}
+
+impl GeometricBatches {
+    pub fn new(initial: usize, growth: f64, max_batch: usize) -> Self {
+        assert!(initial > 0, "batches need at least one sample");
+        assert!(max_batch > 0, "batches need at least one sample");
+        assert!(growth >= 1.0, "batch sizes must not shrink");
+        Self {
+            next: initial.min(max_batch),
+            growth,
+            max_batch,
+        }
+    }
+}
+
+impl Iterator for GeometricBatches {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let batch = self.next;
+        self.next = ((batch as f64 * self.growth).ceil() as usize).min(self.max_batch);
+        Some(batch)
+    }
+}
+
+// Same as `integrate_while`, but with the batch sizes taken in turn from `batches`, e.g.
+// `GeometricBatches`. Sampling also stops when `batches` runs out, and a batch size of 0 is an
+// error
+pub fn integrate_while_batched(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    should_continue: impl Fn(&VarianceEstimator) -> bool,
+    batches: impl IntoIterator<Item = usize>,
+    max_samples: usize,
+) -> Result<IntegralEstimate> {
+    let ve = sample_while_batched(f, a, b, should_continue, batches, max_samples)?;
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Sampling loop of `integrate_while_batched`, returning the estimator of the integrand values
+fn sample_while_batched(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    should_continue: impl Fn(&VarianceEstimator) -> bool,
+    batches: impl IntoIterator<Item = usize>,
+    max_samples: usize,
+) -> Result<VarianceEstimator> {
+    check_integration(a, b, max_samples)?;
+    let mut ve = VarianceEstimator::new();
+    for batch_size in batches {
+        if ve.sample_count() as usize >= max_samples {
+            break;
+        }
+        if batch_size == 0 {
+            return Err(MonteCarloError::InvalidParameter {
+                name: "batch size",
+                value: 0.0,
+            });
+        }
+        let batch = batch_size.min(max_samples - ve.sample_count() as usize);
+        ve = VarianceEstimator::merge(ve, sample_integrand(&f, a, b, batch));
+        if !should_continue(&ve) {
+            break;
+        }
+    }
+
+    Ok(ve)
+}
+
+// Estimate integral from a to b of f(x) dx, sampling in batches of `batch_size` until the
+// standard error of the integral drops below max(abs_tol, rel_tol * |estimate|). The relative
+// tolerance alone never terminates for an integral close to 0, like that of cos over [0, PI],
+// since its estimate shrinks along with the error; the absolute one takes over there. Reaching
+// `max_samples` samples first is a `NonConvergence` error
+pub fn integrate_to_tolerance(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    abs_tol: f64,
+    rel_tol: f64,
+    batch_size: usize,
+    max_samples: usize,
+) -> Result<IntegralEstimate> {
+    integrate_to_tolerance_batched(
+        f,
+        a,
+        b,
+        abs_tol,
+        rel_tol,
+        std::iter::repeat(batch_size),
+        max_samples,
+    )
+}
+
+// Same as `integrate_to_tolerance`, but with the batch sizes taken in turn from `batches`, as for
+// `integrate_while_batched`. With `GeometricBatches`, the first checks come after few samples
+// while the overshoot past the tolerance stays within one batch. Running out of batches before
+// the tolerance is met is also a `NonConvergence` error
+pub fn integrate_to_tolerance_batched(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    abs_tol: f64,
+    rel_tol: f64,
+    batches: impl IntoIterator<Item = usize>,
+    max_samples: usize,
+) -> Result<IntegralEstimate> {
+    let width = (b - a).abs();
+    let should_continue = |ve: &VarianceEstimator| match ve.std_error() {
+        Some(std_error) => std_error * width >= abs_tol.max(rel_tol * (ve.mean * width).abs()),
+        None => true,
+    };
+    let ve = sample_while_batched(f, a, b, should_continue, batches, max_samples)?;
+    if should_continue(&ve) {
+        return Err(MonteCarloError::NonConvergence {
+            sample_count: ve.sample_count() as usize,
+        });
+    }
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Running state of a streaming integration after a batch
+#[derive(Copy, Clone, Debug)]
+pub struct StreamingEstimate {
+    pub sample_count: usize,
+    pub estimate: f64,
+    // Variance of the integral estimate
+    pub variance: f64,
+    // Coefficient of variation of the estimate, see `VarianceEstimator::coefficient_of_variation`
+    pub coefficient_of_variation: Option<f64>,
+}
+
+impl StreamingEstimate {
+    // Number of significant bits of the estimate, log2(|estimate| / std_error). Each quadrupling
+    // of the sample count halves the standard error, and so adds one bit. 0 when the estimate is
+    // no larger than its error, or when the coefficient of variation is undefined, e.g. for an
+    // estimate of zero. At most the 53 bits of precision of an f64, which is also what a zero
+    // error (a constant integrand) gives
+    pub fn effective_bits(&self) -> f64 {
+        match self.coefficient_of_variation {
+            Some(cov) => (-cov.log2()).clamp(0.0, f64::MANTISSA_DIGITS as f64),
+            None => 0.0,
+        }
+    }
+}
+
+// Iterator over the running estimates of an integration, see `integrate_streaming`
+pub struct IntegrationStream<F> {
+    f: F,
+    a: f64,
+    b: f64,
+    batch_size: usize,
+    estimator: VarianceEstimator,
+}
+
+// Estimate integral from a to b of f(x) dx as an endless stream, yielding the running estimate
+// after each batch of `batch_size` samples. The caller decides when to stop, e.g. with
+// `take_while` on the coefficient of variation
+pub fn integrate_streaming<F: Fn(f64) -> f64 + Sync>(
+    f: F,
+    a: f64,
+    b: f64,
+    batch_size: usize,
+) -> Result<IntegrationStream<F>> {
+    check_integration(a, b, batch_size)?;
+    Ok(IntegrationStream {
+        f,
+        a,
+        b,
+        batch_size,
+        estimator: VarianceEstimator::new(),
+    })
+}
+
+impl<F: Fn(f64) -> f64 + Sync> Iterator for IntegrationStream<F> {
+    type Item = StreamingEstimate;
+
+    fn next(&mut self) -> Option<StreamingEstimate> {
+        let batch = sample_integrand(&self.f, self.a, self.b, self.batch_size);
+        self.estimator = VarianceEstimator::merge(self.estimator, batch);
+
+        let width = self.b - self.a;
+        Some(StreamingEstimate {
+            sample_count: self.estimator.sample_count() as usize,
+            estimate: self.estimator.mean * width,
+            variance: self.estimator.variance_of_mean() * width * width,
+            coefficient_of_variation: self.estimator.coefficient_of_variation(),
+        })
+    }
+}
+
+// Result of integrating one sub-interval of a partitioned domain
+#[derive(Copy, Clone, Debug)]
+pub struct PartialIntegral {
+    // Estimate of the integral over the sub-interval
+    pub contribution: f64,
+    // Integrand values sampled within the sub-interval
+    pub estimator: VarianceEstimator,
+}
+
+// Integrate f over the `index`-th of `partitions` equal-width sub-intervals of [a, b], so that the
+// work can be split across machines. A coordinator sums the contributions of all the partitions
+// to get the full integral, and can `merge` their estimators. Each partition derives its own seed
+// from `seed` and `index`
+pub fn integrate_partition(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    partitions: usize,
+    index: usize,
+    sample_count_per_partition: usize,
+    seed: u64,
+) -> Result<PartialIntegral> {
+    check_integration(a, b, sample_count_per_partition)?;
+    if index >= partitions {
+        return Err(MonteCarloError::PartitionOutOfRange {
+            index,
+            count: partitions,
+        });
+    }
+
+    let (lo, hi) = stratum_bounds(a, b, partitions, index);
+    let partition_seed = seed ^ (index as u64).wrapping_mul(0xD1B5_4A32_D192_ED03);
+    let estimator = sample_integrand_seeded(f, lo, hi, sample_count_per_partition, partition_seed)?;
+
+    Ok(PartialIntegral {
+        contribution: estimator.mean * (hi - lo),
+        estimator,
+    })
+}
+
+// Handle to an integration that can be extended with more samples, and checkpointed to resume it
+// later, possibly in another process. Sample i is drawn from the same generator as in
+// `sample_integrand_seeded`, so a run split across any number of `sample` calls and restarts
+// sees exactly the same samples as an uninterrupted one
+pub struct ResumableIntegration<F> {
+    f: F,
+    a: f64,
+    b: f64,
+    seed: u64,
+    estimator: VarianceEstimator,
+}
+
+// Start a resumable integration from a to b of f(x) dx
+pub fn integrate_resumable<F: Fn(f64) -> f64 + Sync>(
+    f: F,
+    a: f64,
+    b: f64,
+    seed: u64,
+) -> Result<ResumableIntegration<F>> {
+    resume_integration(f, a, b, seed, VarianceEstimator::new())
+}
+
+// Continue an integration from a snapshot. `f`, `a`, `b`, and `seed` must be the ones the
+// snapshot was taken with
+pub fn resume_integration<F: Fn(f64) -> f64 + Sync>(
+    f: F,
+    a: f64,
+    b: f64,
+    seed: u64,
+    snapshot: VarianceEstimator,
+) -> Result<ResumableIntegration<F>> {
+    check_bounds(a, b)?;
+    Ok(ResumableIntegration {
+        f,
+        a,
+        b,
+        seed,
+        estimator: snapshot,
+    })
+}
+
+impl<F: Fn(f64) -> f64 + Sync> ResumableIntegration<F> {
+    // Draw `sample_count` more samples
+    pub fn sample(&mut self, sample_count: usize) {
+        let (f, a, b, seed) = (&self.f, self.a, self.b, self.seed);
+        let start = self.estimator.sample_count() as usize;
+        let end = start + sample_count;
+
+        let chunks: Vec<VarianceEstimator> = (start / SEEDED_CHUNK_SIZE
+            ..end.div_ceil(SEEDED_CHUNK_SIZE))
+            .into_par_iter()
+            .map(|chunk| {
+                let mut rng = chunk_rng(seed, chunk);
+                let chunk_start = chunk * SEEDED_CHUNK_SIZE;
+                let first = start.max(chunk_start);
+                let last = end.min(chunk_start + SEEDED_CHUNK_SIZE);
+
+                // Skip the draws already consumed before the previous snapshot
+                (chunk_start..first).for_each(|_| {
+                    rng.gen_range(a..=b);
+                });
+
+                let mut ve = VarianceEstimator::new();
+                (first..last).for_each(|_| ve.add_sample(f(rng.gen_range(a..=b))));
+                ve
+            })
+            .collect();
+
+        self.estimator = chunks
+            .into_iter()
+            .fold(self.estimator, VarianceEstimator::merge);
+    }
+
+    // Current estimate of the integral
+    pub fn estimate(&self) -> f64 {
+        self.estimator.mean * (self.b - self.a)
+    }
+
+    // Current state of the integration, to be persisted and passed to `resume_integration`
+    pub fn snapshot(&self) -> VarianceEstimator {
+        self.estimator
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FallibleEstimate {
+    // Estimate built from the samples where the integrand succeeded
+    pub estimate: f64,
+    // Number of samples where the integrand returned an error
+    pub error_count: usize,
+}
+
+// Estimate integral from a to b of f(x) dx for an integrand that may fail at some points. Failed
+// samples are skipped and counted, instead of poisoning the result like a NaN would. The estimate
+// averages over successful samples only, so it is biased if failures cover a non-negligible part
+// of the domain
+pub fn integrate_fallible<E>(
+    f: impl Fn(f64) -> std::result::Result<f64, E> + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<FallibleEstimate> {
+    check_integration(a, b, sample_count)?;
+    let (sum, valid_count) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| match f(rng.gen_range(a..=b)) {
+            Ok(value) => (value, 1),
+            Err(_) => (0.0, 0),
+        })
+        .reduce(|| (0.0, 0_usize), |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1));
+
+    let estimate = if valid_count == 0 {
+        0.0
+    } else {
+        sum * (b - a) / (valid_count as f64)
+    };
+
+    Ok(FallibleEstimate {
+        estimate,
+        error_count: sample_count - valid_count,
+    })
+}
+
+// Estimate integral from a to b of f(x) dx for an experimental integrand that may panic. With
+// `catch_panics`, each evaluation runs under `catch_unwind`, and panicking samples are skipped
+// and reported in `error_count` like the errors of `integrate_fallible`, instead of tearing down
+// the whole run. The panic hook still prints each panic message. Without it, panics propagate
+pub fn integrate_catching_panics(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    catch_panics: bool,
+) -> Result<FallibleEstimate> {
+    if !catch_panics {
+        return Ok(FallibleEstimate {
+            estimate: monte_carlo_integration(f, a, b, sample_count)?.integral,
+            error_count: 0,
+        });
+    }
+    integrate_fallible(
+        |x| std::panic::catch_unwind(AssertUnwindSafe(|| f(x))),
+        a,
+        b,
+        sample_count,
+    )
+}
+
+// Number of samples drawn between two progress reports
+const PROGRESS_INTERVAL: usize = 4096;
+
+// Percentage of `completed` over `total`, computed in floating point so that it cannot overflow
+// like `completed * 100 / total` does for huge counts
+pub fn progress_percentage(completed: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        completed as f64 / total as f64 * 100.0
+    }
+}
+
+// Same as `monte_carlo_integration`, but calls `on_progress` with the percentage of samples drawn
+// so far, every `PROGRESS_INTERVAL` samples. Reports are serialized and never decrease, even
+// though the workers finish their chunks in any order
+pub fn integrate_with_progress(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    on_progress: impl Fn(f64) + Sync,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    let completed = AtomicUsize::new(0);
+    let last_reported = Mutex::new(0.0);
+
+    let ve = (0..sample_count.div_ceil(PROGRESS_INTERVAL))
+        .into_par_iter()
+        .map_init(thread_rng, |rng, chunk| {
+            let start = chunk * PROGRESS_INTERVAL;
+            let end = (start + PROGRESS_INTERVAL).min(sample_count);
+            let mut ve = VarianceEstimator::new();
+            (start..end).for_each(|_| ve.add_sample(f(rng.gen_range(a..=b))));
+
+            let done = completed.fetch_add(end - start, Ordering::Relaxed) + (end - start);
+            let mut last_reported = last_reported.lock().unwrap();
+            let percentage = progress_percentage(done, sample_count);
+            if percentage > *last_reported {
+                *last_reported = percentage;
+                on_progress(percentage);
+            }
+            ve
+        })
+        .reduce(VarianceEstimator::new, VarianceEstimator::merge);
+
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Number of grid points evaluated when scanning for the support of an integrand
+const SUPPORT_SCAN_POINTS: usize = 4096;
+
+// Values below this fraction of the largest scanned |f| are treated as zero
+const SUPPORT_THRESHOLD: f64 = 1e-9;
+
+// Find the sub-interval of [a, b] where |f| is not negligible, by evaluating f on a coarse grid.
+// The result is widened by one grid cell on each side. Returns None if f looks zero everywhere
+fn detect_support(f: &(impl Fn(f64) -> f64 + Sync), a: f64, b: f64) -> Option<(f64, f64)> {
+    let step = (b - a) / ((SUPPORT_SCAN_POINTS - 1) as f64);
+    let values: Vec<f64> = (0..SUPPORT_SCAN_POINTS)
+        .into_par_iter()
+        .map(|i| f(a + step * (i as f64)).abs())
+        .collect();
+
+    let threshold = values.iter().cloned().fold(0.0, f64::max) * SUPPORT_THRESHOLD;
+    let first = values.iter().position(|&v| v > threshold)?;
+    let last = values.iter().rposition(|&v| v > threshold)?;
+
+    let lo = a + step * (first.saturating_sub(1) as f64);
+    let hi = (a + step * ((last + 1) as f64)).min(b);
+    Some((lo, hi))
+}
+
+// Estimate integral from a to b of f(x) dx. With `auto_support`, a coarse grid scan first finds
+// where f is not negligible, and all the samples are drawn there; the rest of [a, b] is assumed
+// to contribute nothing. Features narrower than the grid spacing (b - a) / 4096 can be missed.
+// `sample_mean` is then the mean of f over the detected support. If f looks zero everywhere, no
+// samples are drawn and the estimate is exactly 0
+pub fn integrate_auto_support(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    auto_support: bool,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    if !auto_support {
+        return monte_carlo_integration(f, a, b, sample_count);
+    }
+
+    match detect_support(&f, a, b) {
+        Some((lo, hi)) => monte_carlo_integration(f, lo, hi, sample_count),
+        None => Ok(IntegralEstimate::from_estimator(
+            &VarianceEstimator::new(),
+            a,
+            b,
+        )),
+    }
+}
+
+// Number of grid cells scanned for sign changes of an integrand
+const SIGN_SCAN_CELLS: usize = 256;
+
+// Number of bisection steps refining each sign change found by the scan
+const SIGN_BISECTION_STEPS: usize = 40;
+
+// Approximate zero-crossings of f in (a, b), in increasing order. f is evaluated on a grid of
+// `SIGN_SCAN_CELLS` cells. An interior grid node where f is exactly 0 is a root as is, and each
+// cell whose ends have opposite signs is bisected down to a root. Cells containing an even number
+// of roots show no sign change, so closely spaced roots can be missed
+fn find_sign_changes(f: &(impl Fn(f64) -> f64 + Sync), a: f64, b: f64) -> Vec<f64> {
+    let step = (b - a) / (SIGN_SCAN_CELLS as f64);
+    let values: Vec<f64> = (0..=SIGN_SCAN_CELLS)
+        .into_par_iter()
+        .map(|i| f(a + step * (i as f64)))
+        .collect();
+
+    let bisect = |i: usize| {
+        let (mut lo, mut hi) = (a + step * (i as f64), a + step * ((i + 1) as f64));
+        let lo_sign = values[i].signum();
+        for _ in 0..SIGN_BISECTION_STEPS {
+            let mid = 0.5 * (lo + hi);
+            if f(mid).signum() == lo_sign {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        0.5 * (lo + hi)
+    };
+    (0..SIGN_SCAN_CELLS)
+        .flat_map(|i| {
+            let node = (i > 0 && values[i] == 0.0).then_some(a + step * (i as f64));
+            let crossing = (values[i] * values[i + 1] < 0.0).then(|| bisect(i));
+            node.into_iter().chain(crossing)
+        })
+        .collect()
+}
+
+// Estimate integral from a to b of f(x) dx for a signed integrand by integrating each same-sign
+// segment separately and summing. Positive and negative parts no longer cancel within a single
+// average, which removes the variance that cancellation causes. The segments come from a coarse
+// scan for sign changes, so this is a heuristic: roots closer together than (b - a) / 256 may be
+// missed, and such segments are then integrated as one. Samples are split across segments in
+// proportion to their widths. The segment estimates are independent, so the integral and its
+// variance are their sums, and `sample_mean` is the integral divided by b - a
+pub fn integrate_sign_split(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    let mut boundaries = vec![a];
+    boundaries.extend(find_sign_changes(&f, a, b));
+    boundaries.push(b);
+
+    let segments = boundaries
+        .windows(2)
+        .map(|w| {
+            let share = (sample_count as f64 * (w[1] - w[0]) / (b - a)).round() as usize;
+            monte_carlo_integration(&f, w[0], w[1], share.max(1))
+        })
+        .collect::<Result<Vec<IntegralEstimate>>>()?;
+
+    let integral: f64 = segments.iter().map(|segment| segment.integral).sum();
+    Ok(IntegralEstimate {
+        integral,
+        sample_mean: if b > a { integral / (b - a) } else { 0.0 },
+        variance: segments.iter().map(|segment| segment.variance).sum(),
+        samples: segments.iter().map(|segment| segment.samples).sum(),
+    })
+}
+
+// Estimate the integral of f over the union of `intervals`, e.g. [0, 1] ∪ [2, 3]. The samples
+// are split across the intervals in proportion to their lengths, so the sampling density is
+// uniform over the whole union, and the contributions are summed. The intervals must be sorted
+// and non-overlapping (they may share an endpoint), otherwise the overlap would be counted twice.
+// Every interval needs at least one sample, so fewer samples than intervals is an error; past
+// that one, the rest are split so that exactly `sample_count` samples are drawn
+pub fn integrate_intervals(
+    f: impl Fn(f64) -> f64 + Sync,
+    intervals: &[(f64, f64)],
+    sample_count: usize,
+) -> Result<f64> {
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    if sample_count < intervals.len() {
+        return Err(MonteCarloError::InsufficientSamples {
+            required: intervals.len(),
+            provided: sample_count,
+        });
+    }
+    if let Some(&(a, b)) = intervals
+        .iter()
+        .find(|&&(a, b)| a >= b || check_bounds(a, b).is_err())
+    {
+        return Err(MonteCarloError::InvalidBounds { a, b });
+    }
+    if let Some(w) = intervals.windows(2).find(|w| w[0].1 > w[1].0) {
+        return Err(MonteCarloError::InvalidBounds {
+            a: w[1].0,
+            b: w[0].1,
+        });
+    }
+
+    let lengths: Vec<f64> = intervals.iter().map(|(a, b)| b - a).collect();
+    let shares = allocate(&lengths, sample_count - intervals.len());
+    intervals
+        .iter()
+        .zip(shares)
+        .map(|(&(a, b), share)| Ok(monte_carlo_integration(&f, a, b, share + 1)?.integral))
+        .sum()
+}
+
+// Estimate integral from a to b of f(x) dx, stopping after the first `probe_count` samples if
+// they all return exactly the same value, which takes at least 2 probes. The probes are ordinary
+// uniform samples of the run, so either way the result is the plain Monte Carlo estimate of the
+// samples drawn, and the returned estimator holds every one of them. A truly constant f always
+// stops early, saving the rest of the run. This is a heuristic, not a proof that f is constant:
+// an f that happens to agree at every probe (e.g. a narrow spike between them) also stops early,
+// with the small-sample estimate of the probes and a sampled variance of 0. Its sample count
+// shows that the run was cut short, so use enough probes for the domain when that matters
+pub fn integrate_skip_constant(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    probe_count: usize,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    let probe_count = probe_count.min(sample_count);
+    let mut rng = thread_rng();
+    let mut probes = VarianceEstimator::new();
+    (0..probe_count).for_each(|_| probes.add_sample(f(rng.gen_range(a..=b))));
+    if probes.std_error() == Some(0.0) {
+        return Ok(IntegralEstimate::from_estimator(&probes, a, b));
+    }
+
+    let ve = VarianceEstimator::merge(
+        probes,
+        sample_integrand(&f, a, b, sample_count - probe_count),
+    );
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+// Number of samples drawn between two checks of the cancellation flag
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+#[derive(Copy, Clone, Debug)]
+pub struct CancellableEstimate {
+    pub estimate: f64,
+    // Number of samples actually drawn, which is less than requested if cancelled
+    pub sample_count: usize,
+    pub completed: bool,
+}
+
+// Same as `monte_carlo_integration`, but stops early once `cancel` is set. The flag is checked
+// independently by each worker every `CANCEL_CHECK_INTERVAL` samples, so the returned estimate
+// is built from whole chunks of samples
+pub fn integrate_cancellable(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    cancel: &AtomicBool,
+) -> Result<CancellableEstimate> {
+    check_integration(a, b, sample_count)?;
+    let chunk_count = sample_count.div_ceil(CANCEL_CHECK_INTERVAL);
+    let (sum, drawn) = (0..chunk_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, chunk| {
+            if cancel.load(Ordering::Relaxed) {
+                return (0.0, 0);
+            }
+            let start = chunk * CANCEL_CHECK_INTERVAL;
+            let end = (start + CANCEL_CHECK_INTERVAL).min(sample_count);
+            let sum: f64 = (start..end).map(|_| f(rng.gen_range(a..=b))).sum();
+            (sum, end - start)
+        })
+        .reduce(|| (0.0, 0), |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1));
+
+    let estimate = if drawn == 0 {
+        0.0
+    } else {
+        sum * (b - a) / (drawn as f64)
+    };
+
+    Ok(CancellableEstimate {
+        estimate,
+        sample_count: drawn,
+        completed: drawn == sample_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_abs_of_signed_integrand() {
+        let f = |x: f64| x - 0.5;
+        assert_approx_eq!(integrate_abs(f, 0.0, 1.0, 100_000).unwrap(), 0.25, 0.01);
+        assert!(
+            monte_carlo_integration(f, 0.0, 1.0, 100_000)
+                .unwrap()
+                .integral
+                .abs()
+                < 0.01
+        );
+    }
+
+    #[test]
+    fn test_integral_estimate_scaling() {
+        let estimate = integrate_seeded(|x| x * x, 1.0, 4.0, 100_000, 3).unwrap();
+        assert_eq!(estimate.samples, 100_000);
+        assert_eq!(estimate.integral, estimate.sample_mean * 3.0);
+        // Mean of x^2 over [1, 4] is 7, and its integral 21
+        assert_approx_eq!(estimate.sample_mean, 7.0, 0.01);
+        assert_approx_eq!(estimate.integral, 21.0, 0.01);
+        assert!(estimate.variance > 0.0 && estimate.variance < 1e-2);
+    }
+
+    #[test]
+    fn test_significant_digits() {
+        let estimate = |integral: f64, std_error: f64| IntegralEstimate {
+            integral,
+            sample_mean: integral,
+            variance: std_error * std_error,
+            samples: 1000,
+        };
+        assert_eq!(estimate(2.00314, 1e-3).significant_digits(), 3);
+        assert_eq!(estimate(-2.00314, 0.3).significant_digits(), 0);
+        assert_eq!(estimate(1e-5, 1e-3).significant_digits(), 0);
+        assert_eq!(estimate(0.0, 0.0).significant_digits(), 0);
+        assert_eq!(
+            estimate(2.0, 0.0).significant_digits(),
+            MAX_SIGNIFICANT_DIGITS
+        );
+        assert_eq!(
+            IntegralEstimate {
+                samples: 1,
+                ..estimate(2.0, 0.0)
+            }
+            .significant_digits(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_significant_digits_grow_slowly() {
+        // Each quadrupling halves the error, which is worth log10(2) ~ 0.3 digits
+        let digits: Vec<usize> = (0..6)
+            .map(|power| {
+                integrate_seeded(|x| x.exp(), 0.0, 1.0, 1000 << (2 * power), 5)
+                    .unwrap()
+                    .significant_digits()
+            })
+            .collect();
+        assert!(digits.windows(2).all(|pair| pair[1] <= pair[0] + 1));
+        assert!((1..=2).contains(&(digits[5] - digits[0])));
+    }
+
+    #[test]
+    fn test_from_samples_linear() {
+        // The mean of x at 0.25 and 0.75 is 0.5, so the estimate is 0.5 * (2 - 0)
+        let result = integrate_from_samples(|x| x, 0.0, 2.0, &[0.25, 0.75]).unwrap();
+        assert_approx_eq!(result.integral, 1.0);
+        assert_approx_eq!(result.sample_mean, 0.5);
+        assert_eq!(result.samples, 2);
+    }
+
+    #[test]
+    fn test_from_samples_constant() {
+        let result = integrate_from_samples(|_| 3.0, 1.0, 5.0, &[1.0, 2.0, 4.5, 5.0]).unwrap();
+        assert_approx_eq!(result.integral, 12.0);
+        assert_eq!(result.variance, 0.0);
+    }
+
+    #[test]
+    fn test_from_samples_square() {
+        // Mean of x^2 over {0, 1, 2} is 5/3
+        let result = integrate_from_samples(|x| x * x, 0.0, 3.0, &[0.0, 1.0, 2.0]).unwrap();
+        assert_approx_eq!(result.integral, 5.0);
+    }
+
+    #[test]
+    fn test_from_samples_empty() {
+        assert_eq!(
+            integrate_from_samples(|x| x, 0.0, 1.0, &[]),
+            Err(MonteCarloError::ZeroSamples)
+        );
+    }
+
+    #[test]
+    fn test_weighted_fn_unit_weights() {
+        let weighted = integrate_weighted_fn(|x| (x * x, 1.0), 0.0, 3.0, 200_000).unwrap();
+        assert_approx_eq!(weighted.integral, 9.0, 0.01);
+        assert_eq!(weighted.samples, 200_000);
+
+        let weighted = integrate_weighted_fn(|x| (x.sin(), 1.0), 0.0, 1.0, 1000).unwrap();
+        assert_approx_eq!(weighted.integral, weighted.sample_mean);
+        assert!(weighted.variance > 0.0);
+    }
+
+    #[test]
+    fn test_weighted_fn_varying_weights() {
+        // Weighted mean of x with weight x over [0, 1] is (1/3) / (1/2)
+        let weighted = integrate_weighted_fn(|x| (x, x), 0.0, 1.0, 200_000).unwrap();
+        assert_approx_eq!(weighted.integral, 2.0 / 3.0, 0.01);
+
+        // Scaling every weight does not change the normalized result
+        let scaled = integrate_weighted_fn(|x| (x, 10.0 * x), 0.0, 1.0, 200_000).unwrap();
+        assert_approx_eq!(scaled.integral, 2.0 / 3.0, 0.01);
+    }
+
+    #[test]
+    fn test_dyn_matches_generic() {
+        let integrands: Vec<BoxedIntegrand> = vec![Box::new(|_| 2.0), Box::new(|x| x * x)];
+
+        assert_approx_eq!(
+            integrate_dyn(&*integrands[0], 0.0, 3.0, 1000)
+                .unwrap()
+                .integral,
+            monte_carlo_integration(|_| 2.0, 0.0, 3.0, 1000)
+                .unwrap()
+                .integral
+        );
+        assert_approx_eq!(
+            integrate_dyn(&*integrands[1], 0.0, 1.0, 100_000)
+                .unwrap()
+                .integral,
+            monte_carlo_integration(|x| x * x, 0.0, 1.0, 100_000)
+                .unwrap()
+                .integral,
+            0.02
+        );
+    }
+
+    #[test]
+    fn test_cancellable_completes() {
+        let cancel = AtomicBool::new(false);
+        let result = integrate_cancellable(|x| x, 0.0, 1.0, 10_000, &cancel).unwrap();
+        assert!(result.completed);
+        assert_eq!(result.sample_count, 10_000);
+        assert_approx_eq!(result.estimate, 0.5, 0.05);
+    }
+
+    #[test]
+    fn test_cancellable_mid_run() {
+        const SAMPLE_COUNT: usize = 10_000_000;
+        let cancel = AtomicBool::new(false);
+        let evaluations = AtomicUsize::new(0);
+        let f = |x: f64| {
+            if evaluations.fetch_add(1, Ordering::Relaxed) == 10_000 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            x
+        };
+
+        let result = integrate_cancellable(f, 0.0, 1.0, SAMPLE_COUNT, &cancel).unwrap();
+        assert!(!result.completed);
+        assert!(result.sample_count > 10_000);
+        assert!(result.sample_count < SAMPLE_COUNT);
+        assert_eq!(result.sample_count, evaluations.load(Ordering::Relaxed));
+        assert_approx_eq!(result.estimate, 0.5, 0.05);
+    }
+
+    #[test]
+    fn test_cancelled_before_start() {
+        let cancel = AtomicBool::new(true);
+        let result = integrate_cancellable(|x| x, 0.0, 1.0, 10_000, &cancel).unwrap();
+        assert!(!result.completed);
+        assert_eq!(result.sample_count, 0);
+        assert_eq!(result.estimate, 0.0);
+    }
+
+    #[test]
+    fn test_seeded_reproducible() {
+        let f = |x: f64| x.sin();
+        let first = integrate_seeded(f, 0.0, 1.0, 100_000, 7).unwrap().integral;
+        assert_eq!(
+            first,
+            integrate_seeded(f, 0.0, 1.0, 100_000, 7).unwrap().integral
+        );
+        assert_ne!(
+            first,
+            integrate_seeded(f, 0.0, 1.0, 100_000, 8).unwrap().integral
+        );
+    }
+
+    #[test]
+    fn test_seeded_independent_of_thread_count() {
+        let f = |x: f64| x.sin() * x;
+        let with_threads = |threads: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| sample_integrand_seeded(f, 0.0, 3.0, 100_000, 99).unwrap())
+        };
+
+        let single = with_threads(1);
+        assert_eq!(single, with_threads(2));
+        assert_eq!(single, with_threads(8));
+    }
+
+    #[test]
+    fn test_seeded_variance_matches_theory() {
+        // For f(x) = x with x uniform on [0, 1], the mean is 1/2 and the variance is exactly 1/12
+        let ve = sample_integrand_seeded(|x| x, 0.0, 1.0, 1_000_000, 2024).unwrap();
+        assert_approx_eq!(ve.mean, 0.5, 0.005);
+        assert_approx_eq!(ve.variance(), 1.0 / 12.0, 0.01);
+    }
+
+    #[test]
+    fn test_seeded_stable_golden_values() {
+        // Committed golden outputs. If this test fails, the stable stream has changed, which
+        // breaks the reproducibility guarantee. A polynomial integrand keeps f itself exact
+        let f = |x: f64| x * x;
+        let golden = [
+            (
+                integrate_seeded_stable(f, 0.0, 1.0, 10_000, 0)
+                    .unwrap()
+                    .integral,
+                3.337463432717308e-1,
+            ),
+            (
+                integrate_seeded_stable(f, 0.0, 1.0, 10_000, 42)
+                    .unwrap()
+                    .integral,
+                3.3454161279721617e-1,
+            ),
+            (
+                integrate_seeded_stable(f, -1.0, 2.0, 1_000, 2024)
+                    .unwrap()
+                    .integral,
+                2.960264334883183e0,
+            ),
+        ];
+        for (actual, expected) in golden {
+            assert_eq!(actual.to_bits(), f64::to_bits(expected));
+        }
+    }
+
+    #[test]
+    fn test_seeded_stable_independent_of_thread_count() {
+        let with_threads = |threads: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    integrate_seeded_stable(|x| x.sin(), 0.0, 3.0, 100_000, 5)
+                        .unwrap()
+                        .integral
+                })
+        };
+        assert_eq!(with_threads(1), with_threads(4));
+    }
+
+    #[test]
+    fn test_sequential_matches_parallel() {
+        let f = |x: f64| x.exp();
+        assert_eq!(
+            integrate_sequential(f, 0.0, 2.0, 50_000, 11)
+                .unwrap()
+                .integral,
+            integrate_seeded(f, 0.0, 2.0, 50_000, 11).unwrap().integral
+        );
+
+        let sequential = sample_integrand_sequential(f, 0.0, 2.0, 50_000, 11).unwrap();
+        let parallel = sample_integrand_seeded(f, 0.0, 2.0, 50_000, 11).unwrap();
+        assert_eq!(sequential.variance(), parallel.variance());
+    }
+
+    #[test]
+    fn test_partitions_sum_to_full_integral() {
+        let f = |x: f64| x * x;
+
+        let parts: Vec<PartialIntegral> = (0..8)
+            .map(|i| integrate_partition(f, 0.0, 3.0, 8, i, 20_000, 5).unwrap())
+            .collect();
+        let total: f64 = parts.iter().map(|p| p.contribution).sum();
+        let merged = parts.iter().fold(VarianceEstimator::new(), |acc, p| {
+            VarianceEstimator::merge(acc, p.estimator)
+        });
+
+        assert_approx_eq!(
+            total,
+            integrate_seeded(f, 0.0, 3.0, 160_000, 5).unwrap().integral,
+            0.01
+        );
+        assert_approx_eq!(total, 9.0, 0.01);
+        assert_eq!(merged.sample_count(), 160_000);
+
+        // A single partition is the whole domain with the original seed
+        let whole = integrate_partition(f, 0.0, 3.0, 1, 0, 20_000, 5).unwrap();
+        assert_eq!(
+            whole.contribution,
+            integrate_seeded(f, 0.0, 3.0, 20_000, 5).unwrap().integral
+        );
+
+        assert_eq!(
+            integrate_partition(f, 0.0, 3.0, 8, 8, 100, 5).unwrap_err(),
+            MonteCarloError::PartitionOutOfRange { index: 8, count: 8 }
+        );
+    }
+
+    #[test]
+    fn test_detect_support() {
+        let (lo, hi) = detect_support(
+            &|x: f64| if (2.0..3.0).contains(&x) { 1.0 } else { 0.0 },
+            0.0,
+            10.0,
+        )
+        .unwrap();
+        assert!(lo > 1.99 && lo <= 2.0);
+        assert!((3.0..3.01).contains(&hi));
+
+        assert_eq!(detect_support(&|_| 0.0, 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn test_auto_support_reduces_variance() {
+        const SIGMA: f64 = 0.2;
+        let bump = |x: f64| (-(x - 37.0).powi(2) / (2.0 * SIGMA * SIGMA)).exp();
+        let expected = SIGMA * (2.0 * std::f64::consts::PI).sqrt();
+
+        let runs = |auto_support: bool| {
+            let mut ve = VarianceEstimator::new();
+            (0..64).for_each(|_| {
+                ve.add_sample(
+                    integrate_auto_support(bump, 0.0, 100.0, 4096, auto_support)
+                        .unwrap()
+                        .integral,
+                )
+            });
+            ve
+        };
+        let plain = runs(false);
+        let scanned = runs(true);
+
+        assert_approx_eq!(scanned.mean, expected, 0.01);
+        assert!(scanned.variance() * 10.0 < plain.variance());
+
+        let zero = integrate_auto_support(|_| 0.0, 0.0, 1.0, 100, true).unwrap();
+        assert_eq!((zero.integral, zero.samples), (0.0, 0));
+    }
+
+    #[test]
+    fn test_find_sign_changes() {
+        let roots = find_sign_changes(&|x: f64| (x - 0.3) * (x - 0.7), 0.0, 1.0);
+        assert_eq!(roots.len(), 2);
+        assert_approx_eq!(roots[0], 0.3, 1e-9);
+        assert_approx_eq!(roots[1], 0.7, 1e-9);
+
+        assert!(find_sign_changes(&|x: f64| x * x + 1.0, -1.0, 1.0).is_empty());
+
+        // The root of sin at 0 falls exactly on the middle grid node, where neither adjacent
+        // cell changes sign. The roots at ±PI are the ends of the interval, not crossings
+        let pi = std::f64::consts::PI;
+        assert_eq!(find_sign_changes(&f64::sin, -pi, pi), vec![0.0]);
+    }
+
+    #[test]
+    fn test_sign_split_reduces_variance() {
+        let f = |x: f64| (2.0 * std::f64::consts::PI * x).sin();
+
+        let runs = |split: bool| {
+            let mut ve = VarianceEstimator::new();
+            (0..64).for_each(|_| {
+                ve.add_sample(if split {
+                    integrate_sign_split(f, 0.0, 1.0, 4096).unwrap().integral
+                } else {
+                    monte_carlo_integration(f, 0.0, 1.0, 4096).unwrap().integral
+                })
+            });
+            ve
+        };
+        let whole = runs(false);
+        let split = runs(true);
+
+        assert!(split.mean.abs() < 0.01);
+        assert!(split.variance() * 2.0 < whole.variance());
+
+        // The reported variance is that of the sum of the segment estimates
+        let estimate = integrate_sign_split(f, 0.0, 1.0, 4096).unwrap();
+        assert_eq!(estimate.samples, 4096);
+        assert_eq!(estimate.sample_mean, estimate.integral);
+        assert!((0.5..2.0).contains(&(estimate.variance / split.variance())));
+    }
+
+    #[test]
+    fn test_intervals_union() {
+        let total = integrate_intervals(|_| 1.0, &[(0.0, 1.0), (2.0, 3.0)], 1000).unwrap();
+        assert_approx_eq!(total, 2.0);
+
+        // x over [0, 1] ∪ [2, 4] is 1/2 + 6
+        let total = integrate_intervals(|x| x, &[(0.0, 1.0), (2.0, 4.0)], 300_000).unwrap();
+        assert_approx_eq!(total, 6.5, 0.01);
+        // Touching intervals are fine
+        assert!(integrate_intervals(|x| x, &[(0.0, 1.0), (1.0, 2.0)], 100).is_ok());
+    }
+
+    #[test]
+    fn test_intervals_stay_within_budget() {
+        let evaluations = AtomicUsize::new(0);
+        let f = |x: f64| {
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            x
+        };
+        let intervals = [(0.0, 1.0), (2.0, 3.0), (4.0, 5.0)];
+        for sample_count in [3, 4, 5, 100, 1001] {
+            evaluations.store(0, Ordering::Relaxed);
+            integrate_intervals(f, &intervals, sample_count).unwrap();
+            assert_eq!(evaluations.load(Ordering::Relaxed), sample_count);
+        }
+        assert_eq!(
+            integrate_intervals(f, &intervals, 2),
+            Err(MonteCarloError::InsufficientSamples {
+                required: 3,
+                provided: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_intervals_rejects_overlap_and_reversed() {
+        assert_eq!(
+            integrate_intervals(|x| x, &[(0.0, 2.0), (1.0, 3.0)], 100).unwrap_err(),
+            MonteCarloError::InvalidBounds { a: 1.0, b: 2.0 }
+        );
+        assert_eq!(
+            integrate_intervals(|x| x, &[(2.0, 3.0), (0.0, 1.0)], 100).unwrap_err(),
+            MonteCarloError::InvalidBounds { a: 0.0, b: 3.0 }
+        );
+        assert_eq!(
+            integrate_intervals(|x| x, &[(1.0, 0.0)], 100).unwrap_err(),
+            MonteCarloError::InvalidBounds { a: 1.0, b: 0.0 }
+        );
+    }
+
+    #[test]
+    fn test_skip_constant_detects_constant() {
+        let evaluations = AtomicUsize::new(0);
+        let f = |_| {
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            5.0
+        };
+
+        let estimate = integrate_skip_constant(f, 1.0, 3.0, 1_000_000, 8).unwrap();
+        assert_eq!(estimate.integral, 10.0);
+        assert_eq!(estimate.sample_mean, 5.0);
+        assert_eq!(estimate.variance, 0.0);
+        assert_eq!(evaluations.load(Ordering::Relaxed), 8);
+    }
+
+    #[test]
+    fn test_skip_constant_runs_non_constant() {
+        // The probes count towards the run rather than being thrown away
+        let evaluations = AtomicUsize::new(0);
+        let f = |x| {
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            x
+        };
+        let estimate = integrate_skip_constant(f, 0.0, 1.0, 10_000, 8).unwrap();
+        assert_eq!(estimate.samples, 10_000);
+        assert_eq!(evaluations.load(Ordering::Relaxed), 10_000);
+        assert_approx_eq!(estimate.integral, 0.5, 0.05);
+
+        // Without probes, even a constant function gets the full run
+        let estimate = integrate_skip_constant(|_| 1.0, 0.0, 1.0, 100, 0).unwrap();
+        assert_eq!(estimate.samples, 100);
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted_run() {
+        let f = |x: f64| x.cos();
+
+        let snapshot = {
+            let mut run = integrate_resumable(f, 0.0, 2.0, 3).unwrap();
+            run.sample(1000);
+            run.sample(5000);
+            run.snapshot()
+        };
+
+        let mut resumed = resume_integration(f, 0.0, 2.0, 3, snapshot).unwrap();
+        resumed.sample(10_000);
+
+        let uninterrupted = sample_integrand_seeded(f, 0.0, 2.0, 16_000, 3).unwrap();
+        assert_eq!(resumed.snapshot().sample_count(), 16_000);
+        assert_approx_eq!(resumed.snapshot().mean, uninterrupted.mean, 1e-12);
+        assert_approx_eq!(
+            resumed.snapshot().variance(),
+            uninterrupted.variance(),
+            1e-12
+        );
+        assert_approx_eq!(resumed.estimate(), 2.0_f64.sin(), 0.01);
+    }
+
+    #[test]
+    fn test_fallible_skips_errors() {
+        let f = |x: f64| {
+            if x < 0.25 {
+                Err("out of domain")
+            } else {
+                Ok(x.ln())
+            }
+        };
+        let result = integrate_fallible(f, 0.0, 1.0, 100_000).unwrap();
+
+        assert_approx_eq!(result.error_count as f64, 25_000.0, 0.05);
+        // Mean of ln(x) over [0.25, 1], scaled by the full width
+        let expected = (-1.0 - (0.25 * 0.25_f64.ln() - 0.25)) / 0.75;
+        assert_approx_eq!(result.estimate, expected, 0.02);
+    }
+
+    #[test]
+    fn test_fallible_all_ok() {
+        let result = integrate_fallible(|_| Ok::<f64, ()>(2.0), 0.0, 3.0, 1000).unwrap();
+        assert_eq!(result.error_count, 0);
+        assert_approx_eq!(result.estimate, 6.0);
+    }
+
+    #[test]
+    fn test_catching_panics_counts_skipped_samples() {
+        let panicked = AtomicUsize::new(0);
+        let f = |x: f64| {
+            if x < 0.05 {
+                panicked.fetch_add(1, Ordering::Relaxed);
+                panic!("integrand assertion failed at {x}");
+            }
+            x
+        };
+        let result = integrate_catching_panics(f, 0.0, 1.0, 200, true).unwrap();
+
+        assert_eq!(result.error_count, panicked.load(Ordering::Relaxed));
+        assert!(result.error_count > 0);
+        assert!(result.estimate > 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_panics_propagate_without_catching() {
+        integrate_catching_panics(|_| panic!("always"), 0.0, 1.0, 10, false).unwrap();
+    }
+
+    #[test]
+    fn test_logscale_trace() {
+        let f = |x: f64| x * x;
+        let trace = integrate_logscale_trace(f, 0.0, 1.0, 14, 21).unwrap();
+
+        let counts: Vec<usize> = trace.iter().map(|&(n, _)| n).collect();
+        assert_eq!(counts, (0..=14).map(|i| 1 << i).collect::<Vec<_>>());
+
+        let (n, last) = *trace.last().unwrap();
+        assert_approx_eq!(
+            last,
+            integrate_seeded(f, 0.0, 1.0, n, 21).unwrap().integral,
+            1e-12
+        );
+        assert_approx_eq!(last, 1.0 / 3.0, 0.05);
+
+        assert_eq!(
+            integrate_logscale_trace(f, 0.0, 1.0, usize::BITS, 21),
+            Err(MonteCarloError::InvalidParameter {
+                name: "max_power",
+                value: usize::BITS as f64
+            })
+        );
+    }
+
+    #[test]
+    fn test_checkpoints_match_separate_runs() {
+        let f = |x: f64| x.exp();
+        // Checkpoints inside a chunk, on a chunk boundary, and several within one chunk
+        let checkpoints = [1, 100, 2000, SEEDED_CHUNK_SIZE, 10_000, 10_001, 50_000];
+        let snapshots = sample_integrand_checkpoints(f, 0.0, 1.0, &checkpoints, 13).unwrap();
+
+        let counts: Vec<usize> = snapshots
+            .iter()
+            .map(|ve| ve.sample_count() as usize)
+            .collect();
+        assert_eq!(counts, checkpoints);
+
+        for (ve, &count) in snapshots.iter().zip(&checkpoints) {
+            let separate = sample_integrand_seeded(f, 0.0, 1.0, count, 13).unwrap();
+            assert_approx_eq!(ve.mean, separate.mean, 1e-12);
+            assert_approx_eq!(ve.variance(), separate.variance(), 1e-9);
+        }
+        assert_approx_eq!(
+            snapshots.last().unwrap().mean,
+            std::f64::consts::E - 1.0,
+            0.01
+        );
+        assert_eq!(
+            sample_integrand_checkpoints(f, 0.0, 1.0, &[], 13),
+            Err(MonteCarloError::ZeroSamples)
+        );
+    }
+
+    #[test]
+    fn test_checkpoints_must_increase() {
+        assert_eq!(
+            sample_integrand_checkpoints(|x| x, 0.0, 1.0, &[10, 10], 1),
+            Err(MonteCarloError::InvalidParameter {
+                name: "checkpoint",
+                value: 10.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_integrate_while_stops_on_predicate() {
+        let estimate = integrate_while(
+            |x| x,
+            0.0,
+            2.0,
+            |ve| ve.sample_count() < 5000,
+            1000,
+            1_000_000,
+        )
+        .unwrap();
+        assert_eq!(estimate.samples, 5000);
+        // The predicate sees the raw integrand values, the result is scaled by b - a
+        assert_approx_eq!(estimate.sample_mean, 1.0, 0.05);
+        assert_approx_eq!(estimate.integral, 2.0, 0.05);
+    }
+
+    #[test]
+    fn test_hybrid_gauss_exact_on_cubic() {
+        let f = |x: f64| 4.0 * x * x * x - 3.0 * x * x + x - 2.0;
+        // Antiderivative x^4 - x^3 + x^2 / 2 - 2x from -1 to 2
+        let expected = 16.0 - 8.0 + 2.0 - 4.0 - (1.0 + 1.0 + 0.5 + 2.0);
+
+        let (estimate, method) = integrate_hybrid(f, -1.0, 2.0, 4, 16).unwrap();
+        assert_eq!(method, IntegrationMethod::GaussLegendre);
+        assert!((estimate - expected).abs() < 1e-12);
+
+        // The Monte Carlo estimate is random, so compare it in units of its standard error,
+        // taken from a seeded run of the same size
+        let (estimate, method) = integrate_hybrid(f, -1.0, 2.0, 1_000_000, 16).unwrap();
+        assert_eq!(method, IntegrationMethod::MonteCarlo);
+        let std_error = sample_integrand_seeded(f, -1.0, 2.0, 1_000_000, 0)
+            .unwrap()
+            .std_error()
+            .unwrap()
+            * 3.0;
+        assert!((estimate - expected).abs() < 5.0 * std_error);
+    }
+
+    #[test]
+    fn test_streaming_cov_decreases() {
+        let trace: Vec<StreamingEstimate> = integrate_streaming(|x| x * x, 0.0, 2.0, 1000)
+            .unwrap()
+            .take(64)
+            .collect();
+
+        assert_eq!(trace.last().unwrap().sample_count, 64_000);
+        assert_approx_eq!(trace.last().unwrap().estimate, 8.0 / 3.0, 0.02);
+        let cov: Vec<f64> = trace
+            .iter()
+            .map(|step| step.coefficient_of_variation.unwrap())
+            .collect();
+        // CoV shrinks like 1 / sqrt(n): 8 times smaller after 64 times more samples
+        assert!(cov[63] < cov[0] / 4.0);
+        assert!(cov.windows(16).all(|w| w[15] < w[0]));
+    }
+
+    #[test]
+    fn test_effective_bits_per_quadrupling() {
+        let trace: Vec<StreamingEstimate> = integrate_streaming(f64::exp, 0.0, 1.0, 10_000)
+            .unwrap()
+            .take(16)
+            .collect();
+        let bits = |samples: usize| trace[samples / 10_000 - 1].effective_bits();
+
+        assert!(bits(10_000) > 5.0);
+        assert_approx_eq!(bits(40_000) - bits(10_000), 1.0, 0.1);
+        assert_approx_eq!(bits(160_000) - bits(10_000), 2.0, 0.1);
+    }
+
+    #[test]
+    fn test_effective_bits_near_zero() {
+        let step = integrate_streaming(|_| 0.0, 0.0, 1.0, 100)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(step.effective_bits(), 0.0);
+        let step = integrate_streaming(|x| x - 0.5, 0.0, 1.0, 100)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert!(step.effective_bits() < 8.0);
+        let step = integrate_streaming(|_| 2.0, 0.0, 1.0, 100)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(step.effective_bits(), f64::MANTISSA_DIGITS as f64);
+    }
+
+    #[test]
+    fn test_streaming_cov_near_zero_mean() {
+        // The integral of cos over [0, PI] is 0, so any estimate is within a few standard errors
+        // of zero, while that of sin is 2 with a CoV around 0.008 after 4000 samples
+        let cov = |f: fn(f64) -> f64| {
+            integrate_streaming(f, 0.0, std::f64::consts::PI, 1000)
+                .unwrap()
+                .nth(3)
+                .unwrap()
+                .coefficient_of_variation
+                .unwrap()
+        };
+        assert!(cov(f64::cos) > 0.1);
+        assert!(cov(f64::sin) < 0.02);
+
+        let step = integrate_streaming(|_| 0.0, 0.0, 1.0, 100)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(step.coefficient_of_variation, None);
+    }
+
+    #[test]
+    fn test_tolerance_relative_on_nonzero_integral() {
+        let estimate =
+            integrate_to_tolerance(|x| x * x, 0.0, 1.0, 0.0, 1e-2, 1000, 10_000_000).unwrap();
+        assert!(estimate.samples < 10_000_000);
+        assert!(estimate.variance.sqrt() < 1e-2 * estimate.integral);
+        assert_approx_eq!(estimate.integral, 1.0 / 3.0, 0.05);
+    }
+
+    #[test]
+    fn test_tolerance_absolute_near_zero() {
+        let pi = std::f64::consts::PI;
+        let estimate =
+            integrate_to_tolerance(f64::cos, 0.0, pi, 1e-2, 1e-2, 1000, 10_000_000).unwrap();
+        assert!(estimate.samples < 10_000_000);
+        assert!(estimate.variance.sqrt() < 1e-2);
+        assert!(estimate.integral.abs() < 0.05);
+
+        // The relative tolerance alone runs until the sample cap, and does not converge
+        assert_eq!(
+            integrate_to_tolerance(f64::cos, 0.0, pi, 0.0, 1e-2, 1000, 100_000).unwrap_err(),
+            MonteCarloError::NonConvergence {
+                sample_count: 100_000
+            }
+        );
+    }
+
+    #[test]
+    fn test_geometric_batches() {
+        let sizes: Vec<usize> = GeometricBatches::new(100, 1.5, 500).take(7).collect();
+        assert_eq!(sizes, [100, 150, 225, 338, 500, 500, 500]);
+        let fixed: Vec<usize> = GeometricBatches::new(64, 1.0, 1000).take(3).collect();
+        assert_eq!(fixed, [64, 64, 64]);
+    }
+
+    #[test]
+    fn test_batched_overshoots_by_at_most_one_batch() {
+        for target in [1, 999, 10_000, 123_456] {
+            let batches = GeometricBatches::new(100, 2.0, 1 << 20);
+            let estimate = integrate_while_batched(
+                |x| x,
+                0.0,
+                1.0,
+                |ve| (ve.sample_count() as usize) < target,
+                batches,
+                usize::MAX,
+            )
+            .unwrap();
+
+            let used = estimate.samples;
+            let mut drawn = 0;
+            let last_batch = batches
+                .take_while(|&batch| {
+                    let before = drawn;
+                    drawn += batch;
+                    before < target
+                })
+                .last()
+                .unwrap();
+            assert!(used >= target);
+            assert!(used - last_batch < target, "{used} samples for {target}");
+        }
+
+        let estimate = integrate_while_batched(|x| x, 0.0, 1.0, |_| true, [10, 20], 1000).unwrap();
+        assert_eq!(estimate.samples, 30);
+    }
+
+    #[test]
+    fn test_integrate_while_caps_at_max_samples() {
+        let estimate = integrate_while(|x| x, 0.0, 1.0, |_| true, 300, 1000).unwrap();
+        assert_eq!(estimate.samples, 1000);
+    }
+
+    #[test]
+    fn test_integrate_while_rejects_empty_batches() {
+        let error = MonteCarloError::InvalidParameter {
+            name: "batch size",
+            value: 0.0,
+        };
+        assert_eq!(
+            integrate_while(|x| x, 0.0, 1.0, |_| true, 0, 1000).unwrap_err(),
+            error
+        );
+        assert_eq!(
+            integrate_to_tolerance(|x| x, 0.0, 1.0, 1e-3, 0.0, 0, 1000).unwrap_err(),
+            error
+        );
+        // A zero-sized batch later in the sequence is rejected when it is reached
+        assert_eq!(
+            integrate_while_batched(|x| x, 0.0, 1.0, |_| true, [10, 0, 10], 1000).unwrap_err(),
+            error
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "batches need at least one sample")]
+    fn test_geometric_batches_reject_zero_cap() {
+        GeometricBatches::new(100, 2.0, 0);
+    }
+
+    #[test]
+    fn test_to_tolerance_with_geometric_batches() {
+        let estimate = integrate_to_tolerance_batched(
+            |x| x,
+            0.0,
+            1.0,
+            1e-3,
+            0.0,
+            GeometricBatches::new(64, 2.0, 1 << 16),
+            1_000_000,
+        )
+        .unwrap();
+        assert!(estimate.variance.sqrt() < 1e-3);
+        assert_approx_eq!(estimate.integral, 0.5, 0.01);
+        // About 1 / (12 * 1e-6) = 83_334 samples are needed, and a doubling batch overshoots
+        // that by at most the last batch
+        assert!(estimate.samples < 2 * 83_334 + 64);
+
+        assert_eq!(
+            integrate_to_tolerance_batched(|x| x, 0.0, 1.0, 1e-3, 0.0, [64, 64], 1_000_000)
+                .unwrap_err(),
+            MonteCarloError::NonConvergence { sample_count: 128 }
+        );
+    }
+
+    #[test]
+    fn test_progress_percentage_does_not_overflow() {
+        assert_eq!(progress_percentage(usize::MAX, usize::MAX), 100.0);
+        assert_approx_eq!(progress_percentage(usize::MAX / 2, usize::MAX), 50.0);
+        assert_eq!(progress_percentage(0, 10), 0.0);
+    }
+
+    #[test]
+    fn test_progress_is_monotonic() {
+        let reports = Mutex::new(Vec::new());
+        let estimate = integrate_with_progress(
+            |x| x,
+            0.0,
+            1.0,
+            100_000,
+            |percentage| reports.lock().unwrap().push(percentage),
+        )
+        .unwrap();
+
+        let reports = reports.into_inner().unwrap();
+        assert_approx_eq!(estimate.integral, 0.5, 0.02);
+        assert_eq!(estimate.samples, 100_000);
+        assert!(reports.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(*reports.last().unwrap(), 100.0);
+    }
+}
@@ -0,0 +1,180 @@
+// Configurable entry point for 1D integration, for settings that would otherwise need one
+// function per combination
+use crate::error::{check_integration, MonteCarloError, Result};
+use crate::integration::{
+    sample_integrand_bounded, sample_integrand_seeded_bounded, IntegralEstimate, UpperBound,
+};
+
+// Builder for integrating over [a, b]. For example
+// `Integrator::new(0.0, 1.0).sample_count(1000).threads(2).integrate(f)`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Integrator {
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    seed: Option<u64>,
+    threads: Option<usize>,
+    upper_bound: UpperBound,
+}
+
+impl Integrator {
+    // Integrator over [a, b] with 10000 samples, entropy from the thread-local generator, and the
+    // global rayon thread pool
+    pub fn new(a: f64, b: f64) -> Self {
+        Self {
+            a,
+            b,
+            sample_count: 10_000,
+            seed: None,
+            threads: None,
+            upper_bound: UpperBound::Inclusive,
+        }
+    }
+
+    pub fn sample_count(mut self, sample_count: usize) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    // Make the result reproducible, see `integrate_seeded`
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Run on a dedicated pool of `threads` threads instead of the global one, without touching
+    // the global configuration. The pool is built for every `integrate` call, which costs about as
+    // much as spawning the threads. `integrate` fails for 0 threads
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    // Whether b itself can be sampled, inclusive by default. Use `UpperBound::Exclusive` for an
+    // integrand that is singular at b
+    pub fn upper_bound(mut self, upper_bound: UpperBound) -> Self {
+        self.upper_bound = upper_bound;
+        self
+    }
+
+    // Estimate integral from a to b of f(x) dx
+    pub fn integrate(&self, f: impl Fn(f64) -> f64 + Sync) -> Result<IntegralEstimate> {
+        let (a, b, bound, n) = (self.a, self.b, self.upper_bound, self.sample_count);
+        check_integration(a, b, n)?;
+        let run = || {
+            let ve = match self.seed {
+                Some(seed) => sample_integrand_seeded_bounded(&f, a, b, bound, n, seed),
+                None => sample_integrand_bounded(&f, a, b, bound, n),
+            };
+            IntegralEstimate::from_estimator(&ve, a, b)
+        };
+
+        match self.threads {
+            Some(0) => Err(MonteCarloError::InvalidParameter {
+                name: "thread count",
+                value: 0.0,
+            }),
+            Some(threads) => Ok(rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("failed to build the thread pool")
+                .install(run)),
+            None => Ok(run()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_single_thread_matches_default() {
+        let integrator = Integrator::new(0.0, 2.0).sample_count(50_000).seed(17);
+        let f = |x: f64| x.sin() + x;
+        assert_eq!(integrator.integrate(f), integrator.threads(1).integrate(f));
+        assert_approx_eq!(
+            integrator.integrate(f).unwrap().integral,
+            3.0 - 2.0_f64.cos(),
+            0.01
+        );
+    }
+
+    #[test]
+    fn test_exclusive_upper_bound() {
+        let f = |x: f64| x.exp();
+        let inclusive = Integrator::new(0.0, 1.0).sample_count(100_000).seed(3);
+        let exclusive = inclusive.upper_bound(UpperBound::Exclusive);
+        let (lhs, rhs) = (
+            inclusive.integrate(f).unwrap(),
+            exclusive.integrate(f).unwrap(),
+        );
+        let std_error = (lhs.variance + rhs.variance).sqrt();
+        assert!((lhs.integral - rhs.integral).abs() < 5.0 * std_error);
+        assert_approx_eq!(rhs.integral, std::f64::consts::E - 1.0, 0.01);
+
+        // An interval one ulp wide, where the inclusive range lands on b about half the time
+        let (a, b) = (1.0, 1.0 + f64::EPSILON);
+        let hits_of_b = |upper_bound| {
+            let hits = AtomicUsize::new(0);
+            Integrator::new(a, b)
+                .sample_count(10_000)
+                .upper_bound(upper_bound)
+                .integrate(|x| {
+                    if x == b {
+                        hits.fetch_add(1, Ordering::Relaxed);
+                    }
+                    1.0 / (b - x)
+                })
+                .unwrap();
+            hits.into_inner()
+        };
+        assert!(hits_of_b(UpperBound::Inclusive) > 0);
+        assert_eq!(hits_of_b(UpperBound::Exclusive), 0);
+    }
+
+    #[test]
+    fn test_thread_count_is_respected() {
+        let pool_sizes = Mutex::new(HashSet::new());
+        let workers = Mutex::new(HashSet::new());
+        Integrator::new(0.0, 1.0)
+            .sample_count(100_000)
+            .threads(3)
+            .integrate(|x| {
+                pool_sizes
+                    .lock()
+                    .unwrap()
+                    .insert(rayon::current_num_threads());
+                workers.lock().unwrap().insert(std::thread::current().id());
+                x
+            })
+            .unwrap();
+
+        assert_eq!(pool_sizes.into_inner().unwrap(), HashSet::from([3]));
+        assert!(workers.into_inner().unwrap().len() <= 3);
+    }
+
+    #[test]
+    fn test_invalid_configuration() {
+        let f = |x: f64| x;
+        assert_eq!(
+            Integrator::new(0.0, 1.0).threads(0).integrate(f),
+            Err(MonteCarloError::InvalidParameter {
+                name: "thread count",
+                value: 0.0
+            })
+        );
+        assert_eq!(
+            Integrator::new(0.0, 1.0).sample_count(0).integrate(f),
+            Err(MonteCarloError::ZeroSamples)
+        );
+        assert_eq!(
+            Integrator::new(1.0, 0.0).integrate(f),
+            Err(MonteCarloError::InvalidBounds { a: 1.0, b: 0.0 })
+        );
+    }
+}
@@ -0,0 +1,179 @@
+// Interval arithmetic for certified bounds, as a deterministic complement to the statistical
+// error of Monte Carlo. Every operation rounds its bounds outward by one ulp, so the computed
+// interval contains the exact result despite floating point rounding
+use crate::error::{check_bounds, MonteCarloError, Result};
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    // Bounds must be ordered, but may be infinite
+    pub fn new(lo: f64, hi: f64) -> Result<Self> {
+        if lo.is_nan() || hi.is_nan() || lo > hi {
+            return Err(MonteCarloError::InvalidBounds { a: lo, b: hi });
+        }
+        Ok(Self { lo, hi })
+    }
+
+    pub fn point(x: f64) -> Self {
+        Self { lo: x, hi: x }
+    }
+
+    fn outward(lo: f64, hi: f64) -> Self {
+        Self {
+            lo: lo.next_down(),
+            hi: hi.next_up(),
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    // Image of the interval under a non-decreasing function, e.g. exp or sqrt
+    pub fn map_increasing(self, f: impl Fn(f64) -> f64) -> Self {
+        Self::outward(f(self.lo), f(self.hi))
+    }
+
+    // Image of the interval under a non-increasing function
+    pub fn map_decreasing(self, f: impl Fn(f64) -> f64) -> Self {
+        Self::outward(f(self.hi), f(self.lo))
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::outward(self.lo + rhs.lo, self.hi + rhs.hi)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::outward(self.lo - rhs.hi, self.hi - rhs.lo)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let products = [
+            self.lo * rhs.lo,
+            self.lo * rhs.hi,
+            self.hi * rhs.lo,
+            self.hi * rhs.hi,
+        ];
+        Self::outward(
+            products.iter().cloned().fold(f64::INFINITY, f64::min),
+            products.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+}
+
+// Certified bounds on the integral from a to b of f(x) dx. `f_bounds` must return an interval
+// enclosing f over the given interval, e.g. by evaluating f with interval arithmetic. [a, b] is
+// split into `subdivisions` sub-intervals, each bounded by its width times the enclosure of f
+pub fn interval_bound(
+    f_bounds: impl Fn(Interval) -> Interval,
+    a: f64,
+    b: f64,
+    subdivisions: usize,
+) -> Result<Interval> {
+    check_bounds(a, b)?;
+    if subdivisions == 0 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "subdivisions",
+            value: 0.0,
+        });
+    }
+    let step = (b - a) / (subdivisions as f64);
+    let bound = (0..subdivisions)
+        .map(|i| {
+            let lo = a + step * (i as f64);
+            let hi = if i + 1 == subdivisions {
+                b
+            } else {
+                a + step * ((i + 1) as f64)
+            };
+            let cell = Interval { lo, hi };
+            f_bounds(cell) * (Interval::point(hi) - Interval::point(lo))
+        })
+        .fold(Interval::point(0.0), Add::add);
+    Ok(bound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic_contains_exact_result() {
+        let x = Interval::new(-1.0, 2.0).unwrap();
+        let y = Interval::new(3.0, 4.0).unwrap();
+
+        assert!((x + y).contains(2.0) && (x + y).contains(6.0));
+        assert!((x - y).contains(-5.0) && (x - y).contains(-1.0));
+        assert!((x * y).contains(-4.0) && (x * y).contains(8.0));
+        assert!((x * x).contains(-2.0) && (x * x).contains(4.0));
+        assert_eq!(Ok(-x), Interval::new(-2.0, 1.0));
+
+        let third = Interval::point(1.0).map_increasing(|v| v / 3.0);
+        assert!(third.lo < third.hi);
+    }
+
+    #[test]
+    fn test_bounds_bracket_monotone_integral() {
+        // x^2 is increasing on [0, 1]
+        let coarse = interval_bound(|x| x * x, 0.0, 1.0, 10).unwrap();
+        let fine = interval_bound(|x| x * x, 0.0, 1.0, 1000).unwrap();
+
+        assert!(coarse.contains(1.0 / 3.0));
+        assert!(fine.contains(1.0 / 3.0));
+        assert!(fine.width() < coarse.width());
+        assert!(fine.width() < 2e-3);
+    }
+
+    #[test]
+    fn test_bounds_with_monotone_functions() {
+        let exp = interval_bound(|x| x.map_increasing(f64::exp), 0.0, 1.0, 100).unwrap();
+        assert!(exp.contains(std::f64::consts::E - 1.0));
+
+        let decay = interval_bound(|x| x.map_decreasing(|v| (-v).exp()), 0.0, 2.0, 100).unwrap();
+        assert!(decay.contains(1.0 - (-2.0_f64).exp()));
+    }
+
+    #[test]
+    fn test_invalid_intervals() {
+        assert_eq!(
+            Interval::new(2.0, 1.0),
+            Err(MonteCarloError::InvalidBounds { a: 2.0, b: 1.0 })
+        );
+        assert!(Interval::new(f64::NAN, 1.0).is_err());
+        assert!(Interval::new(f64::NEG_INFINITY, f64::INFINITY).is_ok());
+        assert!(interval_bound(|x| x, 0.0, 1.0, 0).is_err());
+        assert!(interval_bound(|x| x, 1.0, 0.0, 10).is_err());
+    }
+}
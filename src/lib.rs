@@ -0,0 +1,34 @@
+pub mod antithetic;
+pub mod block_variance_estimator;
+pub mod bootstrap;
+pub mod cost;
+pub mod deterministic;
+pub mod diagnostics;
+pub mod error;
+pub mod geometry;
+pub mod gradient;
+pub mod grid;
+pub mod harness;
+pub mod importance;
+pub mod integration;
+pub mod integrator;
+pub mod interval;
+pub mod mapping;
+pub mod memoize;
+pub mod multi_variance_estimator;
+pub mod multidimensional;
+pub mod parallel_estimator;
+pub mod polynomial;
+pub mod ratio_estimator;
+pub mod report;
+pub mod reservoir;
+pub mod sampler;
+pub mod shared_samples;
+pub mod sobol;
+pub mod stable_rng;
+pub mod stratified;
+pub mod tagged_estimator;
+pub mod variance_components;
+pub mod variance_estimator;
+pub mod vegas;
+pub mod weighted_variance_estimator;
@@ -0,0 +1,50 @@
+// Affine change of variable between the unit interval [0, 1] and [a, b]. Techniques written on
+// the unit interval go through these, so the scaling lives in one place
+
+// Map u in [0, 1] to [a, b]
+pub fn map_unit_to_interval(u: f64, a: f64, b: f64) -> f64 {
+    a + u * (b - a)
+}
+
+// Map x in [a, b] to [0, 1]. Inverse of `map_unit_to_interval`
+pub fn map_interval_to_unit(x: f64, a: f64, b: f64) -> f64 {
+    (x - a) / (b - a)
+}
+
+// Jacobian dx/du of `map_unit_to_interval`. An integral over [0, 1] of g(u) du is an integral
+// over [a, b] of f(x) dx when g(u) = f(map_unit_to_interval(u, a, b)) * interval_jacobian(a, b)
+pub fn interval_jacobian(a: f64, b: f64) -> f64 {
+    b - a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_endpoints() {
+        assert_eq!(map_unit_to_interval(0.0, -2.0, 3.0), -2.0);
+        assert_eq!(map_unit_to_interval(1.0, -2.0, 3.0), 3.0);
+        assert_eq!(map_unit_to_interval(0.5, -2.0, 3.0), 0.5);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for &u in &[0.0, 0.1, 0.25, 0.7, 1.0] {
+            let x = map_unit_to_interval(u, 1.5, 4.0);
+            assert_approx_eq!(map_interval_to_unit(x, 1.5, 4.0), u);
+        }
+    }
+
+    #[test]
+    fn test_jacobian() {
+        assert_eq!(interval_jacobian(1.5, 4.0), 2.5);
+
+        // The Jacobian matches the finite-difference derivative of the mapping
+        let h = 1e-6;
+        let derivative =
+            (map_unit_to_interval(0.3 + h, 1.5, 4.0) - map_unit_to_interval(0.3, 1.5, 4.0)) / h;
+        assert_approx_eq!(derivative, interval_jacobian(1.5, 4.0), 1e-6);
+    }
+}
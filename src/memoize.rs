@@ -0,0 +1,89 @@
+// Memoization of expensive integrands whose arguments repeat, e.g. integrands defined on a grid
+use crate::error::{check_integration, MonteCarloError, Result};
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug)]
+pub struct MemoizedEstimate {
+    pub estimate: f64,
+    // Fraction of the samples answered from the cache instead of calling f
+    pub hit_rate: f64,
+}
+
+// Estimate integral from a to b of f(x) dx, caching the values of f keyed on the sample points
+// rounded to multiples of `quantum`. f is only ever called at rounded points, so the result is the
+// integral of the quantized integrand f(quantum * round(x / quantum)). That is exact for an
+// integrand that is already discretized on that grid, and a bias of order `quantum` otherwise.
+// The caches are made with `map_init`, which starts a new one for every piece of work rayon splits
+// off, not once per thread, so there is no locking, but there are usually many more caches than
+// threads and a value may be computed once per cache. For continuous sampling with a tiny
+// quantum, samples almost never collide and the hit rate is near zero
+pub fn integrate_memoized(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    quantum: f64,
+) -> Result<MemoizedEstimate> {
+    check_integration(a, b, sample_count)?;
+    if quantum <= 0.0 || !quantum.is_finite() {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "quantum",
+            value: quantum,
+        });
+    }
+    let (sum, hits) = (0..sample_count)
+        .into_par_iter()
+        .map_init(
+            || (thread_rng(), HashMap::new()),
+            |(rng, cache), _| {
+                let key = (rng.gen_range(a..=b) / quantum).round() as i64;
+                match cache.get(&key) {
+                    Some(&value) => (value, 1),
+                    None => {
+                        let value = f(key as f64 * quantum);
+                        cache.insert(key, value);
+                        (value, 0)
+                    }
+                }
+            },
+        )
+        .reduce(|| (0.0, 0_usize), |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1));
+
+    Ok(MemoizedEstimate {
+        estimate: sum * (b - a) / (sample_count as f64),
+        hit_rate: hits as f64 / sample_count as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_memoized_grid_integrand() {
+        // An integrand tabulated on 101 grid points, e.g. read from a lookup table
+        let calls = AtomicUsize::new(0);
+        let f = |x: f64| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            x * x
+        };
+        let result = integrate_memoized(f, 0.0, 1.0, 100_000, 0.01).unwrap();
+
+        assert!(result.hit_rate > 0.5);
+        assert_eq!(
+            100_000 - calls.load(Ordering::Relaxed),
+            (result.hit_rate * 100_000.0).round() as usize
+        );
+        assert_approx_eq!(result.estimate, 1.0 / 3.0, 0.02);
+    }
+
+    #[test]
+    fn test_memoized_continuous_has_no_hits() {
+        let result = integrate_memoized(|x| x, 0.0, 1.0, 10_000, 1e-12).unwrap();
+        assert!(result.hit_rate < 0.01);
+    }
+}
@@ -0,0 +1,223 @@
+// Multivariate version of VarianceEstimator for vector-valued samples, tracking the mean vector and
+// the full covariance matrix with Welford's algorithm. The co-moments are stored row-major in a
+// dimension x dimension matrix
+use crate::error::{MonteCarloError, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiVarianceEstimator {
+    mean: Vec<f64>,
+    co_moments: Vec<f64>,
+    sample_count: i64,
+}
+
+impl MultiVarianceEstimator {
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            mean: vec![0.0; dimension],
+            co_moments: vec![0.0; dimension * dimension],
+            sample_count: 0,
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn sample_count(&self) -> i64 {
+        self.sample_count
+    }
+
+    pub fn mean(&self) -> &[f64] {
+        &self.mean
+    }
+
+    pub fn add_sample(&mut self, x: &[f64]) -> Result<()> {
+        if x.len() != self.dimension() {
+            return Err(MonteCarloError::DimensionMismatch {
+                expected: self.dimension(),
+                actual: x.len(),
+            });
+        }
+        self.sample_count += 1;
+        let n = self.sample_count as f64;
+        let delta: Vec<f64> = x.iter().zip(&self.mean).map(|(x, mean)| x - mean).collect();
+        for (mean, delta) in self.mean.iter_mut().zip(&delta) {
+            *mean += delta / n;
+        }
+
+        let dimension = self.dimension();
+        for (row, delta_i) in self.co_moments.chunks_mut(dimension).zip(&delta) {
+            for ((co_moment, x), mean) in row.iter_mut().zip(x).zip(&self.mean) {
+                *co_moment += delta_i * (x - mean);
+            }
+        }
+        Ok(())
+    }
+
+    // Sample covariance of components i and j, with the n - 1 denominator. The diagonal holds the
+    // variance of each component. 0 with fewer than 2 samples
+    pub fn covariance(&self, i: usize, j: usize) -> f64 {
+        if self.sample_count <= 1 {
+            return 0.0;
+        }
+        self.co_moments[i * self.dimension() + j] / (self.sample_count - 1) as f64
+    }
+
+    // Covariance matrix, one row per component
+    pub fn covariance_matrix(&self) -> Vec<Vec<f64>> {
+        (0..self.dimension())
+            .map(|i| {
+                (0..self.dimension())
+                    .map(|j| self.covariance(i, j))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Pearson correlation of components i and j, or 0 if either is constant
+    pub fn correlation(&self, i: usize, j: usize) -> f64 {
+        let denominator = (self.covariance(i, i) * self.covariance(j, j)).sqrt();
+        if denominator > 0.0 {
+            self.covariance(i, j) / denominator
+        } else {
+            0.0
+        }
+    }
+
+    // Combine the estimators of two disjoint sets of samples of the same dimension. On top of the
+    // sum of the co-moments, the difference of the means adds the rank-1 term
+    // delta delta^T * n_lhs * n_rhs / n
+    pub fn merge(lhs: Self, rhs: Self) -> Result<Self> {
+        if lhs.dimension() != rhs.dimension() {
+            return Err(MonteCarloError::DimensionMismatch {
+                expected: lhs.dimension(),
+                actual: rhs.dimension(),
+            });
+        }
+        if rhs.sample_count == 0 {
+            return Ok(lhs);
+        }
+        if lhs.sample_count == 0 {
+            return Ok(rhs);
+        }
+
+        let (na, nb) = (lhs.sample_count as f64, rhs.sample_count as f64);
+        let n = na + nb;
+        let delta: Vec<f64> = rhs.mean.iter().zip(&lhs.mean).map(|(r, l)| r - l).collect();
+        let dimension = lhs.dimension();
+
+        let mean = lhs
+            .mean
+            .iter()
+            .zip(&delta)
+            .map(|(mean, delta)| mean + delta * nb / n)
+            .collect();
+        let co_moments = (0..dimension * dimension)
+            .map(|k| {
+                let (i, j) = (k / dimension, k % dimension);
+                lhs.co_moments[k] + rhs.co_moments[k] + delta[i] * delta[j] * na * nb / n
+            })
+            .collect();
+
+        Ok(Self {
+            mean,
+            co_moments,
+            sample_count: lhs.sample_count + rhs.sample_count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variance_estimator::VarianceEstimator;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_perfectly_correlated() {
+        // The second component is 3 times the first, so the covariance is the geometric mean of
+        // the variances
+        let mut mve = MultiVarianceEstimator::new(2);
+        (0..100).for_each(|i| {
+            let x = (i as f64 * 0.7).sin();
+            mve.add_sample(&[x, 3.0 * x]).unwrap();
+        });
+
+        let geometric_mean = (mve.covariance(0, 0) * mve.covariance(1, 1)).sqrt();
+        assert_approx_eq!(mve.covariance(0, 1), geometric_mean, 1e-12);
+        assert_approx_eq!(mve.covariance(1, 0), geometric_mean, 1e-12);
+        assert_approx_eq!(mve.covariance(1, 1), 9.0 * mve.covariance(0, 0), 1e-12);
+        assert_approx_eq!(mve.correlation(0, 1), 1.0, 1e-12);
+    }
+
+    #[test]
+    fn test_diagonal_matches_variance_estimator() {
+        let mut mve = MultiVarianceEstimator::new(3);
+        let mut ves = [VarianceEstimator::new(); 3];
+        for i in 0..200 {
+            let x = [(i as f64).cos(), i as f64, (i % 7) as f64];
+            mve.add_sample(&x).unwrap();
+            ves.iter_mut().zip(x).for_each(|(ve, x)| ve.add_sample(x));
+        }
+
+        for (axis, ve) in ves.iter().enumerate() {
+            assert_approx_eq!(mve.mean()[axis], ve.mean);
+            assert_approx_eq!(mve.covariance(axis, axis), ve.variance());
+        }
+        let matrix = mve.covariance_matrix();
+        assert_approx_eq!(matrix[0][2], matrix[2][0]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut lhs = MultiVarianceEstimator::new(2);
+        let mut rhs = MultiVarianceEstimator::new(2);
+        let mut all = MultiVarianceEstimator::new(2);
+        for i in 0..300 {
+            let x = [
+                (i as f64 * 0.1).sin() + 2.0,
+                (i as f64 * 0.37).cos() * i as f64,
+            ];
+            if i < 110 {
+                lhs.add_sample(&x).unwrap();
+            } else {
+                rhs.add_sample(&x).unwrap();
+            }
+            all.add_sample(&x).unwrap();
+        }
+
+        let merged = MultiVarianceEstimator::merge(lhs, rhs).unwrap();
+        assert_eq!(merged.sample_count(), 300);
+        for i in 0..2 {
+            assert_approx_eq!(merged.mean()[i], all.mean()[i]);
+            for j in 0..2 {
+                assert_approx_eq!(merged.covariance(i, j), all.covariance(i, j));
+            }
+        }
+
+        assert_eq!(
+            MultiVarianceEstimator::merge(MultiVarianceEstimator::new(2), all.clone()).unwrap(),
+            all
+        );
+        assert_eq!(
+            MultiVarianceEstimator::merge(all, MultiVarianceEstimator::new(3)).unwrap_err(),
+            MonteCarloError::DimensionMismatch {
+                expected: 2,
+                actual: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_sample_dimension_mismatch() {
+        let mut mve = MultiVarianceEstimator::new(2);
+        assert_eq!(
+            mve.add_sample(&[1.0, 2.0, 3.0]),
+            Err(MonteCarloError::DimensionMismatch {
+                expected: 2,
+                actual: 3
+            })
+        );
+        assert_eq!(mve.sample_count(), 0);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::error::{check_bounds, check_integration, MonteCarloError, Result};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Valid bounds on every axis and at least one sample
+fn check_box<'a>(
+    lower: impl IntoIterator<Item = &'a f64>,
+    upper: &[f64],
+    sample_count: usize,
+) -> Result<()> {
+    for (&lo, &hi) in lower.into_iter().zip(upper) {
+        check_bounds(lo, hi)?;
+    }
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    Ok(())
+}
+
+fn box_volume<'a>(lower: impl IntoIterator<Item = &'a f64>, upper: &[f64]) -> f64 {
+    lower
+        .into_iter()
+        .zip(upper)
+        .map(|(lo, hi)| hi - lo)
+        .product()
+}
+
+// Estimate the integral of f over the box [lower, upper], in any number of dimensions. The point
+// buffer is allocated once per rayon job and reused across samples
+pub fn integrate_nd(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+) -> Result<f64> {
+    if lower.len() != upper.len() {
+        return Err(MonteCarloError::DimensionMismatch {
+            expected: lower.len(),
+            actual: upper.len(),
+        });
+    }
+    check_box(lower, upper, sample_count)?;
+
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(
+            || (thread_rng(), vec![0.0; lower.len()]),
+            |(rng, point), _| {
+                for (x, (&lo, &hi)) in point.iter_mut().zip(lower.iter().zip(upper)) {
+                    *x = rng.gen_range(lo..=hi);
+                }
+                f(point)
+            },
+        )
+        .sum();
+
+    Ok(sum * box_volume(lower, upper) / (sample_count as f64))
+}
+
+// Fixed-dimension version of `integrate_nd`. The point lives on the stack and the dimension is
+// known at compile time, so cheap low-dimensional integrands avoid the slice indirection and
+// any heap allocation
+pub fn integrate_nd_array<const D: usize>(
+    f: impl Fn([f64; D]) -> f64 + Sync,
+    lower: [f64; D],
+    upper: [f64; D],
+    sample_count: usize,
+) -> Result<f64> {
+    check_box(&lower, &upper, sample_count)?;
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            f(std::array::from_fn(|i| rng.gen_range(lower[i]..=upper[i])))
+        })
+        .sum();
+
+    Ok(sum * box_volume(&lower, &upper) / (sample_count as f64))
+}
+
+// Estimate the iterated integral of f(x, y) for x from outer.0 to outer.1 and y between the
+// bounds inner_bounds(x), which may depend on x (e.g. a triangle for y from 0 to x). Each sample
+// draws x, then y within its bounds, and is weighted by the widths of both intervals. As for an
+// iterated integral, reversed inner bounds (lo > hi) give a signed, negative width, and equal
+// ones contribute 0
+pub fn integrate_nested(
+    f: impl Fn(f64, f64) -> f64 + Sync,
+    outer: (f64, f64),
+    inner_bounds: impl Fn(f64) -> (f64, f64) + Sync,
+    sample_count: usize,
+) -> Result<f64> {
+    let (a, b) = outer;
+    check_integration(a, b, sample_count)?;
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            let (lo, hi) = inner_bounds(x);
+            let y = rng.gen_range(lo.min(hi)..=lo.max(hi));
+            f(x, y) * (hi - lo)
+        })
+        .sum();
+
+    Ok(sum * (b - a) / (sample_count as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_nested_triangle_area() {
+        let area = integrate_nested(|_, _| 1.0, (0.0, 1.0), |x| (0.0, x), 100_000).unwrap();
+        assert_approx_eq!(area, 0.5, 0.01);
+    }
+
+    #[test]
+    fn test_nested_triangle_integral() {
+        // Integral over y from 0 to x of x y is x^3 / 2, which integrates to 1/8 over [0, 1]
+        let result = integrate_nested(|x, y| x * y, (0.0, 1.0), |x| (0.0, x), 1_000_000).unwrap();
+        assert_approx_eq!(result, 0.125, 0.01);
+    }
+
+    #[test]
+    fn test_nd_array_matches_slice() {
+        // Integral of x y over [0, 1] x [0, 2] is 1
+        let array = integrate_nd_array(|[x, y]| x * y, [0.0, 0.0], [1.0, 2.0], 200_000).unwrap();
+        let slice = integrate_nd(|p| p[0] * p[1], &[0.0, 0.0], &[1.0, 2.0], 200_000).unwrap();
+        assert_approx_eq!(array, 1.0, 0.01);
+        assert_approx_eq!(slice, 1.0, 0.01);
+        assert_approx_eq!(array, slice, 0.02);
+    }
+
+    #[test]
+    fn test_nd_constant() {
+        assert_approx_eq!(
+            integrate_nd_array(|_| 2.0, [0.0, -1.0, 1.0], [1.0, 1.0, 4.0], 100).unwrap(),
+            12.0
+        );
+        assert_eq!(
+            integrate_nd(|_| 1.0, &[0.0], &[1.0, 2.0], 10),
+            Err(MonteCarloError::DimensionMismatch {
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_nested_empty_inner() {
+        let result = integrate_nested(|_, _| 1.0, (0.0, 1.0), |_| (1.0, 1.0), 1000).unwrap();
+        assert_eq!(result, 0.0);
+    }
+
+    #[test]
+    fn test_nested_reversed_inner() {
+        // Integral over y from x to 0 of 1 is -x, which integrates to -1/2 over [0, 1]
+        let result = integrate_nested(|_, _| 1.0, (0.0, 1.0), |x| (x, 0.0), 100_000).unwrap();
+        assert_approx_eq!(result, -0.5, 0.01);
+    }
+}
@@ -0,0 +1,155 @@
+// Common interface of the online estimators, so that one parallel sampling loop can accumulate
+// any of them. An estimator is folded per rayon job starting from `identity`, and the partial
+// estimators are then combined with `merge`, which must be associative for the result not to
+// depend on the scheduling (up to rounding)
+use crate::error::Result;
+use crate::ratio_estimator::RatioEstimator;
+use crate::variance_estimator::VarianceEstimator;
+use crate::weighted_variance_estimator::WeightedVarianceEstimator;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+pub trait ParallelEstimator: Send + Sized {
+    // What a single sample consists of, e.g. a value and its weight
+    type Sample: Send;
+
+    // Estimator of no samples, the neutral element of `merge`
+    fn identity() -> Self;
+
+    // Fails for a sample the estimator cannot take, e.g. a negative weight
+    fn accumulate(&mut self, sample: Self::Sample) -> Result<()>;
+
+    fn merge(lhs: Self, rhs: Self) -> Self;
+}
+
+impl ParallelEstimator for VarianceEstimator {
+    type Sample = f64;
+
+    fn identity() -> Self {
+        VarianceEstimator::new()
+    }
+
+    fn accumulate(&mut self, sample: f64) -> Result<()> {
+        self.add_sample(sample);
+        Ok(())
+    }
+
+    fn merge(lhs: Self, rhs: Self) -> Self {
+        VarianceEstimator::merge(lhs, rhs)
+    }
+}
+
+// Samples are (value, weight)
+impl ParallelEstimator for WeightedVarianceEstimator {
+    type Sample = (f64, f64);
+
+    fn identity() -> Self {
+        WeightedVarianceEstimator::new()
+    }
+
+    fn accumulate(&mut self, (value, weight): (f64, f64)) -> Result<()> {
+        self.add_sample(value, weight)
+    }
+
+    fn merge(lhs: Self, rhs: Self) -> Self {
+        WeightedVarianceEstimator::merge(lhs, rhs)
+    }
+}
+
+// Samples are (numerator, denominator)
+impl ParallelEstimator for RatioEstimator {
+    type Sample = (f64, f64);
+
+    fn identity() -> Self {
+        RatioEstimator::new()
+    }
+
+    fn accumulate(&mut self, (numerator, denominator): (f64, f64)) -> Result<()> {
+        self.add_sample(numerator, denominator);
+        Ok(())
+    }
+
+    fn merge(lhs: Self, rhs: Self) -> Self {
+        RatioEstimator::merge(lhs, rhs)
+    }
+}
+
+// Draw `sample_count` samples in parallel into an estimator. `sample` gets the thread-local
+// generator and the index of the sample. Stops at the first sample the estimator rejects
+pub fn accumulate_parallel<E: ParallelEstimator>(
+    sample_count: usize,
+    sample: impl Fn(&mut ThreadRng, usize) -> E::Sample + Sync,
+) -> Result<E> {
+    (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, i| sample(rng, i))
+        .try_fold(E::identity, |mut estimator, sample| {
+            estimator.accumulate(sample)?;
+            Ok(estimator)
+        })
+        .try_reduce(E::identity, |lhs, rhs| Ok(E::merge(lhs, rhs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    // Deterministic samples, so the parallel result can be compared to a sequential one
+    fn value(i: usize) -> f64 {
+        (i as f64 * 0.37).sin() * 3.0 + 1.0
+    }
+
+    fn weight(i: usize) -> f64 {
+        1.0 + (i % 5) as f64
+    }
+
+    #[test]
+    fn test_variance_estimator_through_generic_loop() {
+        let parallel: VarianceEstimator = accumulate_parallel(100_000, |_, i| value(i)).unwrap();
+
+        let mut sequential = VarianceEstimator::new();
+        (0..100_000).for_each(|i| sequential.add_sample(value(i)));
+        assert_eq!(parallel.sample_count(), 100_000);
+        assert_approx_eq!(parallel.mean, sequential.mean, 1e-10);
+        assert_approx_eq!(parallel.variance(), sequential.variance(), 1e-10);
+    }
+
+    #[test]
+    fn test_weighted_estimator_through_generic_loop() {
+        let parallel: WeightedVarianceEstimator =
+            accumulate_parallel(100_000, |_, i| (value(i), weight(i))).unwrap();
+
+        let mut sequential = WeightedVarianceEstimator::new();
+        (0..100_000).for_each(|i| sequential.add_sample(value(i), weight(i)).unwrap());
+        assert_eq!(parallel.sample_count(), 100_000);
+        assert_approx_eq!(parallel.weight_sum(), sequential.weight_sum(), 1e-12);
+        assert_approx_eq!(parallel.mean, sequential.mean, 1e-10);
+        assert_approx_eq!(parallel.variance(), sequential.variance(), 1e-10);
+    }
+
+    #[test]
+    fn test_identity_is_neutral() {
+        let estimator: RatioEstimator =
+            accumulate_parallel(100, |_, i| (value(i), weight(i))).unwrap();
+        assert_eq!(
+            ParallelEstimator::merge(RatioEstimator::identity(), estimator),
+            estimator
+        );
+        assert_eq!(
+            ParallelEstimator::merge(estimator, RatioEstimator::identity()),
+            estimator
+        );
+        assert_eq!(
+            accumulate_parallel::<VarianceEstimator>(0, |_, i| value(i)),
+            Ok(VarianceEstimator::identity())
+        );
+    }
+
+    #[test]
+    fn test_rejected_sample_stops_accumulation() {
+        let result: Result<WeightedVarianceEstimator> =
+            accumulate_parallel(1000, |_, i| (value(i), if i == 500 { -1.0 } else { 1.0 }));
+        assert!(result.is_err());
+    }
+}
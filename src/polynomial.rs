@@ -0,0 +1,57 @@
+// Polynomials given by their coefficients in increasing order of degree, i.e. coeffs[i] is the
+// coefficient of x^i
+use crate::error::Result;
+use crate::integration::monte_carlo_integration;
+
+// Evaluate the polynomial at x with Horner's method
+pub fn evaluate_polynomial(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().rev().fold(0.0, |acc, &c| acc * x + c)
+}
+
+// Estimate integral from a to b of the polynomial with Monte Carlo
+pub fn integrate_polynomial(coeffs: &[f64], a: f64, b: f64, sample_count: usize) -> Result<f64> {
+    Ok(monte_carlo_integration(|x| evaluate_polynomial(coeffs, x), a, b, sample_count)?.integral)
+}
+
+// Exact integral from a to b of the polynomial, from its antiderivative
+pub fn exact_polynomial_integral(coeffs: &[f64], a: f64, b: f64) -> f64 {
+    let antiderivative: Vec<f64> = std::iter::once(0.0)
+        .chain(
+            coeffs
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| c / ((i + 1) as f64)),
+        )
+        .collect();
+    evaluate_polynomial(&antiderivative, b) - evaluate_polynomial(&antiderivative, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    // 1 - 2x + 3x^3
+    const CUBIC: [f64; 4] = [1.0, -2.0, 0.0, 3.0];
+
+    #[test]
+    fn test_evaluate_polynomial() {
+        assert_eq!(evaluate_polynomial(&CUBIC, 2.0), 21.0);
+        assert_eq!(evaluate_polynomial(&[], 2.0), 0.0);
+    }
+
+    #[test]
+    fn test_exact_polynomial_integral() {
+        // x - x^2 + 3/4 x^4 from 0 to 2
+        assert_approx_eq!(exact_polynomial_integral(&CUBIC, 0.0, 2.0), 10.0);
+        assert_approx_eq!(exact_polynomial_integral(&[5.0], -1.0, 3.0), 20.0);
+    }
+
+    #[test]
+    fn test_monte_carlo_converges_to_exact() {
+        let exact = exact_polynomial_integral(&CUBIC, -1.0, 2.0);
+        let fine = integrate_polynomial(&CUBIC, -1.0, 2.0, 1_000_000).unwrap();
+
+        assert_approx_eq!(fine, exact, 0.01);
+    }
+}
@@ -0,0 +1,156 @@
+// Online estimator of a ratio of means E[X] / E[Y], as in self-normalized importance sampling or
+// pixel filtering in rendering. The variance needs the covariance between numerator and
+// denominator, so both are accumulated together with their co-moment
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RatioEstimator {
+    numerator_mean: f64,
+    denominator_mean: f64,
+    numerator_m2: f64,
+    denominator_m2: f64,
+    co_moment: f64,
+    sample_count: i64,
+}
+
+impl RatioEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_sample(&mut self, numerator: f64, denominator: f64) {
+        self.sample_count += 1;
+        let n = self.sample_count as f64;
+        let delta_x = numerator - self.numerator_mean;
+        let delta_y = denominator - self.denominator_mean;
+        self.numerator_mean += delta_x / n;
+        self.denominator_mean += delta_y / n;
+        self.numerator_m2 += delta_x * (numerator - self.numerator_mean);
+        self.denominator_m2 += delta_y * (denominator - self.denominator_mean);
+        self.co_moment += delta_x * (denominator - self.denominator_mean);
+    }
+
+    pub fn sample_count(&self) -> i64 {
+        self.sample_count
+    }
+
+    pub fn numerator_mean(&self) -> f64 {
+        self.numerator_mean
+    }
+
+    pub fn denominator_mean(&self) -> f64 {
+        self.denominator_mean
+    }
+
+    // Sample covariance of numerator and denominator, with the n - 1 denominator
+    pub fn covariance(&self) -> f64 {
+        if self.sample_count <= 1 {
+            return 0.0;
+        }
+        self.co_moment / (self.sample_count - 1) as f64
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.numerator_mean / self.denominator_mean
+    }
+
+    // Delta-method variance of the ratio, (Var X - 2 R Cov(X, Y) + R^2 Var Y) / (n E[Y]^2). It is
+    // a first order approximation, accurate when the denominator's relative error is small
+    pub fn variance(&self) -> f64 {
+        if self.sample_count <= 1 {
+            return 0.0;
+        }
+        let r = self.ratio();
+        let denominator = (self.sample_count - 1) as f64;
+        let spread = (self.numerator_m2 - 2.0 * r * self.co_moment + r * r * self.denominator_m2)
+            / denominator;
+        (spread / (self.sample_count as f64 * self.denominator_mean * self.denominator_mean))
+            .max(0.0)
+    }
+
+    pub fn merge(lhs: Self, rhs: Self) -> Self {
+        if lhs.sample_count == 0 {
+            return rhs;
+        }
+        if rhs.sample_count == 0 {
+            return lhs;
+        }
+
+        let (na, nb) = (lhs.sample_count as f64, rhs.sample_count as f64);
+        let n = na + nb;
+        let delta_x = rhs.numerator_mean - lhs.numerator_mean;
+        let delta_y = rhs.denominator_mean - lhs.denominator_mean;
+        Self {
+            numerator_mean: lhs.numerator_mean + delta_x * nb / n,
+            denominator_mean: lhs.denominator_mean + delta_y * nb / n,
+            numerator_m2: lhs.numerator_m2 + rhs.numerator_m2 + delta_x * delta_x * na * nb / n,
+            denominator_m2: lhs.denominator_m2
+                + rhs.denominator_m2
+                + delta_y * delta_y * na * nb / n,
+            co_moment: lhs.co_moment + rhs.co_moment + delta_x * delta_y * na * nb / n,
+            sample_count: lhs.sample_count + rhs.sample_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variance_estimator::VarianceEstimator;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_perfectly_correlated() {
+        let mut re = RatioEstimator::new();
+        (1..100).for_each(|i| re.add_sample(i as f64, 2.0 * i as f64));
+
+        assert_approx_eq!(re.ratio(), 0.5);
+        assert!(re.variance() < 1e-15);
+        assert!(re.covariance() > 0.0);
+    }
+
+    #[test]
+    fn test_constant_denominator() {
+        // With Y = 1 the ratio is the mean of X, with the usual variance of the mean
+        let mut re = RatioEstimator::new();
+        let mut ve = VarianceEstimator::new();
+        for i in 0..100 {
+            let x = (i as f64).sin();
+            re.add_sample(x, 1.0);
+            ve.add_sample(x);
+        }
+
+        assert_approx_eq!(re.ratio(), ve.mean);
+        assert_approx_eq!(re.variance(), ve.variance_of_mean());
+        assert_eq!(re.covariance(), 0.0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut lhs = RatioEstimator::new();
+        let mut rhs = RatioEstimator::new();
+        let mut all = RatioEstimator::new();
+        for i in 0..200 {
+            let (x, y) = ((i as f64).cos() + 2.0, (i as f64 * 0.3).sin() + 3.0);
+            if i < 70 {
+                lhs.add_sample(x, y);
+            } else {
+                rhs.add_sample(x, y);
+            }
+            all.add_sample(x, y);
+        }
+
+        let merged = RatioEstimator::merge(lhs, rhs);
+        assert_eq!(merged.sample_count(), 200);
+        assert_approx_eq!(merged.ratio(), all.ratio());
+        assert_approx_eq!(merged.covariance(), all.covariance());
+        assert_approx_eq!(merged.variance(), all.variance());
+        assert_eq!(RatioEstimator::merge(RatioEstimator::new(), all), all);
+    }
+
+    #[test]
+    fn test_few_samples() {
+        let mut re = RatioEstimator::new();
+        re.add_sample(1.0, 2.0);
+        assert_eq!(re.ratio(), 0.5);
+        assert_eq!(re.variance(), 0.0);
+    }
+}
@@ -0,0 +1,84 @@
+fn decimal_exponent(x: f64) -> i32 {
+    x.abs().log10().floor() as i32
+}
+
+// Format a measurement as "value ± error" following the usual convention for reporting
+// experimental results: the error is rounded to two significant figures if its leading digits are
+// below 300 (e.g. 0.014), and to one otherwise (e.g. 0.03). The estimate is then printed with the
+// same number of decimals as the error. Very small or very large values use a shared exponent,
+// e.g. "(1.234 ± 0.021)e-8"
+pub fn format_result(estimate: f64, std_error: f64) -> String {
+    if !estimate.is_finite() || !std_error.is_finite() || std_error <= 0.0 {
+        return format!("{estimate} ± {}", std_error.abs());
+    }
+
+    let magnitude = decimal_exponent(estimate.abs().max(std_error));
+    if !(-3..=5).contains(&magnitude) {
+        let scale = 10f64.powi(magnitude);
+        return format!(
+            "({})e{magnitude}",
+            format_result(estimate / scale, std_error / scale)
+        );
+    }
+
+    let exponent = decimal_exponent(std_error);
+    let leading_digits = (std_error * 10f64.powi(2 - exponent)).round();
+    let significant_figures = if leading_digits < 300.0 { 2 } else { 1 };
+    let mut decimals = significant_figures - 1 - exponent;
+
+    // Rounding can carry into a new digit, e.g. 0.096 becomes 0.1 instead of 0.10
+    let rounded_error = (std_error * 10f64.powi(decimals)).round() / 10f64.powi(decimals);
+    if decimal_exponent(rounded_error) > exponent {
+        decimals -= 1;
+    }
+
+    if decimals >= 0 {
+        let decimals = decimals as usize;
+        format!("{estimate:.decimals$} ± {rounded_error:.decimals$}")
+    } else {
+        let scale = 10f64.powi(-decimals);
+        let estimate = (estimate / scale).round() * scale;
+        let std_error = (std_error / scale).round() * scale;
+        format!("{estimate:.0} ± {std_error:.0}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_one_significant_figure() {
+        assert_eq!(format_result(2.0031, 0.0312), "2.00 ± 0.03");
+        assert_eq!(format_result(0.84, 0.5), "0.8 ± 0.5");
+    }
+
+    #[test]
+    fn test_format_two_significant_figures() {
+        assert_eq!(format_result(2.0031, 0.0143), "2.003 ± 0.014");
+        assert_eq!(format_result(0.3333, 0.0021), "0.3333 ± 0.0021");
+    }
+
+    #[test]
+    fn test_format_rounding_carry() {
+        assert_eq!(format_result(1.0, 0.096), "1.0 ± 0.1");
+        assert_eq!(format_result(42.0, 9.7), "40 ± 10");
+    }
+
+    #[test]
+    fn test_format_large_error() {
+        assert_eq!(format_result(12345.6, 234.5), "12350 ± 230");
+        assert_eq!(format_result(6.02e23, 3e21), "(6.02 ± 0.03)e23");
+    }
+
+    #[test]
+    fn test_format_small_error() {
+        assert_eq!(format_result(1.234e-8, 2.1e-10), "(1.234 ± 0.021)e-8");
+        assert_eq!(format_result(0.5, 1.7e-6), "0.5000000 ± 0.0000017");
+    }
+
+    #[test]
+    fn test_format_zero_error() {
+        assert_eq!(format_result(0.5, 0.0), "0.5 ± 0");
+    }
+}
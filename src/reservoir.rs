@@ -0,0 +1,178 @@
+// Fixed-size uniform random subset of a stream, with Algorithm R
+// J. S. Vitter, "Random sampling with a reservoir", 1985
+use crate::error::{check_integration, Result};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct Reservoir<T> {
+    capacity: usize,
+    // Number of items offered so far, retained or not
+    seen: u64,
+    items: Vec<T>,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: 0,
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+
+    pub fn add(&mut self, item: T, rng: &mut impl Rng) {
+        self.seen += 1;
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+        } else {
+            let slot = rng.gen_range(0..self.seen);
+            if slot < self.capacity as u64 {
+                self.items[slot as usize] = item;
+            }
+        }
+    }
+
+    // Combine reservoirs of two disjoint streams into one of the concatenated stream. Items are
+    // drawn without replacement from either side with probability proportional to the number of
+    // items each side has seen but not yet contributed, which makes the number taken from each
+    // side hypergeometric, as if the merged reservoir had sampled the whole stream directly
+    pub fn merge(mut lhs: Self, mut rhs: Self, rng: &mut impl Rng) -> Self {
+        let capacity = lhs.capacity.min(rhs.capacity);
+        let seen = lhs.seen + rhs.seen;
+        let mut items = Vec::with_capacity(capacity);
+
+        let (mut left_remaining, mut right_remaining) = (lhs.seen, rhs.seen);
+        while items.len() < capacity && left_remaining + right_remaining > 0 {
+            let from_left = rng.gen_range(0..left_remaining + right_remaining) < left_remaining;
+            let side = if from_left {
+                left_remaining -= 1;
+                &mut lhs.items
+            } else {
+                right_remaining -= 1;
+                &mut rhs.items
+            };
+            let index = rng.gen_range(0..side.len());
+            items.push(side.swap_remove(index));
+        }
+
+        Self {
+            capacity,
+            seen,
+            items,
+        }
+    }
+}
+
+// Estimate integral from a to b of f(x) dx, and keep up to `capacity` of the (x, f(x)) samples,
+// chosen uniformly among all of them, for plotting or diagnostics
+pub fn integrate_with_reservoir(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    capacity: usize,
+) -> Result<(f64, Reservoir<(f64, f64)>)> {
+    check_integration(a, b, sample_count)?;
+    let (sum, reservoir) = (0..sample_count)
+        .into_par_iter()
+        .fold(
+            || (0.0, Reservoir::new(capacity)),
+            |(sum, mut reservoir), _| {
+                let mut rng = thread_rng();
+                let x = rng.gen_range(a..=b);
+                let value = f(x);
+                reservoir.add((x, value), &mut rng);
+                (sum + value, reservoir)
+            },
+        )
+        .reduce(
+            || (0.0, Reservoir::new(capacity)),
+            |(lhs_sum, lhs), (rhs_sum, rhs)| {
+                (
+                    lhs_sum + rhs_sum,
+                    Reservoir::merge(lhs, rhs, &mut thread_rng()),
+                )
+            },
+        );
+
+    Ok((sum * (b - a) / (sample_count as f64), reservoir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_fills_up_to_capacity() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut reservoir = Reservoir::new(5);
+        (0..3).for_each(|i| reservoir.add(i, &mut rng));
+        assert_eq!(reservoir.items(), &[0, 1, 2]);
+
+        (3..100).for_each(|i| reservoir.add(i, &mut rng));
+        assert_eq!(reservoir.items().len(), 5);
+        assert_eq!(reservoir.seen(), 100);
+    }
+
+    #[test]
+    fn test_merge_inclusion_is_uniform() {
+        // Every item of the stream 0..10 should be retained with probability 3/10, no matter
+        // which side of the merge it was fed to
+        const TRIALS: usize = 20_000;
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut counts = [0_usize; 10];
+
+        for _ in 0..TRIALS {
+            let mut lhs = Reservoir::new(3);
+            let mut rhs = Reservoir::new(3);
+            (0..4).for_each(|i| lhs.add(i, &mut rng));
+            (4..10).for_each(|i| rhs.add(i, &mut rng));
+
+            let merged = Reservoir::merge(lhs, rhs, &mut rng);
+            assert_eq!(merged.seen(), 10);
+            merged.items().iter().for_each(|&i| counts[i] += 1);
+        }
+
+        for count in counts {
+            assert_approx_eq!(count as f64 / TRIALS as f64, 0.3, 0.05);
+        }
+    }
+
+    #[test]
+    fn test_integration_reservoir_is_representative() {
+        let (estimate, reservoir) =
+            integrate_with_reservoir(|x| x * x, 0.0, 1.0, 200_000, 2000).unwrap();
+        assert_approx_eq!(estimate, 1.0 / 3.0, 0.02);
+        assert_eq!(reservoir.items().len(), 2000);
+        assert_eq!(reservoir.seen(), 200_000);
+
+        // The retained points should be uniform on [0, 1]
+        let mut histogram = [0_usize; 4];
+        reservoir.items().iter().for_each(|&(x, value)| {
+            assert_eq!(value, x * x);
+            histogram[((x * 4.0) as usize).min(3)] += 1;
+        });
+        for count in histogram {
+            assert_approx_eq!(count as f64, 500.0, 0.2);
+        }
+    }
+}
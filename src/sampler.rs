@@ -0,0 +1,258 @@
+// Pluggable sources of sample points for 1D integration
+use crate::error::{check_integration, MonteCarloError, Result};
+use crate::mapping::{interval_jacobian, map_unit_to_interval};
+use crate::variance_estimator::VarianceEstimator;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Generates the points of a sample set in the unit interval [0, 1). A sampler may use the index
+// of the point and the total count, e.g. to stratify, in addition to the random generator
+pub trait Sampler1D: Sync {
+    fn sample<R: Rng>(&self, index: usize, count: usize, rng: &mut R) -> f64;
+}
+
+// Independent uniform samples, i.e. plain Monte Carlo
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UniformSampler;
+
+impl Sampler1D for UniformSampler {
+    fn sample<R: Rng>(&self, _index: usize, _count: usize, rng: &mut R) -> f64 {
+        rng.gen()
+    }
+}
+
+// Splits the unit interval into `count` equal cells and places exactly one uniform sample in
+// each, the simplest form of stratification
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JitteredSampler;
+
+impl Sampler1D for JitteredSampler {
+    fn sample<R: Rng>(&self, index: usize, count: usize, rng: &mut R) -> f64 {
+        (index as f64 + rng.gen::<f64>()) / count as f64
+    }
+}
+
+// Estimate integral from a to b of f(x) dx with `sample_count` points from `sampler`
+pub fn integrate_with_sampler(
+    sampler: &impl Sampler1D,
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<f64> {
+    check_integration(a, b, sample_count)?;
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, i| {
+            f(map_unit_to_interval(
+                sampler.sample(i, sample_count, rng),
+                a,
+                b,
+            ))
+        })
+        .sum();
+
+    Ok(sum * interval_jacobian(a, b) / (sample_count as f64))
+}
+
+// Estimate integral from a to b of f(x) dx with one jittered sample in each of `cells` cells
+pub fn integrate_jittered(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    cells: usize,
+) -> Result<f64> {
+    integrate_with_sampler(&JitteredSampler, f, a, b, cells)
+}
+
+// Indices of the cells, out of `cells` equal-width cells of [0, 1), that receive none of the
+// `sample_count` points of `sampler`. Even for a correct random sampler a cell is empty with
+// probability about exp(-sample_count / cells), so use at least 20 samples per cell
+pub fn uncovered_cells(
+    sampler: &impl Sampler1D,
+    sample_count: usize,
+    cells: usize,
+) -> Result<Vec<usize>> {
+    if cells == 0 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "cell count",
+            value: 0.0,
+        });
+    }
+    let mut rng = thread_rng();
+    let mut covered = vec![false; cells];
+    for i in 0..sample_count {
+        let u = sampler.sample(i, sample_count, &mut rng);
+        covered[((u * cells as f64) as usize).min(cells - 1)] = true;
+    }
+    Ok((0..cells).filter(|&cell| !covered[cell]).collect())
+}
+
+// Debug-build guard against samplers that never reach part of the domain, which silently biases
+// every integral. Prints a warning listing the uncovered cells and returns false if any, see
+// `uncovered_cells`. Release builds skip the check and always return true
+pub fn check_coverage(sampler: &impl Sampler1D, sample_count: usize, cells: usize) -> Result<bool> {
+    if !cfg!(debug_assertions) {
+        return Ok(true);
+    }
+
+    let uncovered = uncovered_cells(sampler, sample_count, cells)?;
+    if !uncovered.is_empty() {
+        eprintln!(
+            "warning: {} of {} cells received no samples out of {}: {:?}",
+            uncovered.len(),
+            cells,
+            sample_count,
+            uncovered
+        );
+    }
+    Ok(uncovered.is_empty())
+}
+
+// Number of samples in each run of `assert_unbiased`
+const UNBIASED_RUN_SAMPLES: usize = 256;
+
+// Correctness check for custom samplers: panic unless the mean of `runs` integrations from a to b
+// of f(x) dx with `sampler`, each of `UNBIASED_RUN_SAMPLES` samples, lies within `tol` of `exact`.
+// Catches samplers that favor part of the domain or mishandle the mapping to [a, b]. The grand
+// mean has a standard error of about sqrt(variance of f / (256 * runs)) * (b - a), so `tol` should
+// be a few times that to avoid false alarms
+pub fn assert_unbiased(
+    sampler: &impl Sampler1D,
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    exact: f64,
+    runs: usize,
+    tol: f64,
+) {
+    assert!(runs > 0, "at least one run is required");
+    let grand_mean = (0..runs)
+        .map(|_| integrate_with_sampler(sampler, &f, a, b, UNBIASED_RUN_SAMPLES))
+        .sum::<Result<f64>>()
+        .unwrap_or_else(|error| panic!("{error}"))
+        / runs as f64;
+    assert!(
+        (grand_mean - exact).abs() <= tol,
+        "sampler is biased: mean of {runs} runs is {grand_mean}, expected {exact} within {tol}"
+    );
+}
+
+// How close `sampler` gets to the variance of plain Monte Carlo: the variance of `runs`
+// integrations from a to b of f(x) dx, each of `sample_count` samples, over the ideal
+// pilot_variance (b - a)^2 / sample_count of independent uniform samples. `pilot_variance` is the
+// per-sample variance of f, e.g. from a large pilot run. A ratio near 1 means the sampler behaves
+// like independent sampling, above 1 flags overhead or correlated samples, and below 1 a sampler
+// that reduces variance, like stratification. The ratio has a relative error of about
+// sqrt(2 / runs)
+pub fn variance_ratio(
+    sampler: &impl Sampler1D,
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    pilot_variance: f64,
+    sample_count: usize,
+    runs: usize,
+) -> Result<f64> {
+    if runs < 2 {
+        return Err(MonteCarloError::InsufficientSamples {
+            required: 2,
+            provided: runs,
+        });
+    }
+    let mut ve = VarianceEstimator::new();
+    for _ in 0..runs {
+        ve.add_sample(integrate_with_sampler(sampler, &f, a, b, sample_count)?);
+    }
+
+    let width = b - a;
+    Ok(ve.variance() / (pilot_variance * width * width / sample_count as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration::sample_integrand_seeded;
+    use approx_eq::assert_approx_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_jittered_sampler_covers_cells() {
+        let mut rng = thread_rng();
+        for i in 0..10 {
+            let u = JitteredSampler.sample(i, 10, &mut rng);
+            assert!(u >= i as f64 / 10.0 && u < (i + 1) as f64 / 10.0);
+        }
+    }
+
+    // Only ever samples the left half of the unit interval
+    struct BrokenSampler;
+
+    impl Sampler1D for BrokenSampler {
+        fn sample<R: Rng>(&self, _index: usize, _count: usize, rng: &mut R) -> f64 {
+            rng.gen::<f64>() * 0.5
+        }
+    }
+
+    #[test]
+    fn test_coverage_of_correct_samplers() {
+        assert!(check_coverage(&UniformSampler, 2000, 100).unwrap());
+        assert!(check_coverage(&JitteredSampler, 100, 100).unwrap());
+    }
+
+    #[test]
+    fn test_coverage_detects_broken_sampler() {
+        assert_eq!(
+            uncovered_cells(&BrokenSampler, 2000, 10),
+            Ok(vec![5, 6, 7, 8, 9])
+        );
+        // Release builds skip the check
+        assert_eq!(
+            check_coverage(&BrokenSampler, 2000, 10),
+            Ok(!cfg!(debug_assertions))
+        );
+    }
+
+    #[test]
+    fn test_unbiased_samplers() {
+        assert_unbiased(&UniformSampler, |x| x, 0.0, 1.0, 0.5, 400, 0.01);
+        assert_unbiased(&JitteredSampler, |x| x.sin(), 0.0, PI, 2.0, 100, 0.01);
+    }
+
+    #[test]
+    #[should_panic(expected = "sampler is biased")]
+    fn test_unbiased_rejects_broken_sampler() {
+        // Only the left half is sampled, so the estimate of the integral of x is 1/4
+        assert_unbiased(&BrokenSampler, |x| x, 0.0, 1.0, 0.5, 400, 0.01);
+    }
+
+    #[test]
+    fn test_jittered_reduces_variance() {
+        let runs = |sampler: &dyn Fn() -> f64| {
+            let mut ve = VarianceEstimator::new();
+            (0..256).for_each(|_| ve.add_sample(sampler()));
+            ve
+        };
+        let plain =
+            runs(&|| integrate_with_sampler(&UniformSampler, |x| x.sin(), 0.0, PI, 64).unwrap());
+        let jittered = runs(&|| integrate_jittered(|x| x.sin(), 0.0, PI, 64).unwrap());
+
+        assert_approx_eq!(plain.mean, 2.0, 0.02);
+        assert_approx_eq!(jittered.mean, 2.0, 0.001);
+        assert!(jittered.variance() * 100.0 < plain.variance());
+    }
+
+    #[test]
+    fn test_variance_ratio_of_independent_samples() {
+        let f = |x: f64| x.sin();
+        let pilot_variance = sample_integrand_seeded(f, 0.0, PI, 1_000_000, 7)
+            .unwrap()
+            .variance();
+
+        let plain = variance_ratio(&UniformSampler, f, 0.0, PI, pilot_variance, 64, 2000).unwrap();
+        assert_approx_eq!(plain, 1.0, 0.2);
+        let jittered =
+            variance_ratio(&JitteredSampler, f, 0.0, PI, pilot_variance, 64, 2000).unwrap();
+        assert!(jittered < 0.1);
+    }
+}
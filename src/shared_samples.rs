@@ -0,0 +1,231 @@
+// Integrals of several related quantities estimated from the same sample points, so that their
+// errors are correlated and consistent with each other
+use crate::error::{check_integration, Result};
+use crate::integration::{sample_integrand, IntegralEstimate};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Estimate the integrals from a to b of f(x) g(x), f(x), and g(x) dx, in that order
+pub fn integrate_product(
+    f: impl Fn(f64) -> f64 + Sync,
+    g: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<(f64, f64, f64)> {
+    check_integration(a, b, sample_count)?;
+    let (fg_sum, f_sum, g_sum) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            let (fx, gx) = (f(x), g(x));
+            (fx * gx, fx, gx)
+        })
+        .reduce(
+            || (0.0, 0.0, 0.0),
+            |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1, lhs.2 + rhs.2),
+        );
+
+    let scale = (b - a) / (sample_count as f64);
+    Ok((fg_sum * scale, f_sum * scale, g_sum * scale))
+}
+
+// Estimate the integrals from a to b of f(x) dx and f(x)^2 dx, in that order, with a single
+// evaluation of f per sample. The second moment is what the optimal importance density and the
+// variance of the integrand are built from
+pub fn integrate_with_second_moment(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<(f64, f64)> {
+    check_integration(a, b, sample_count)?;
+    let (f_sum, f2_sum) = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let fx = f(rng.gen_range(a..=b));
+            (fx, fx * fx)
+        })
+        .reduce(|| (0.0, 0.0), |lhs, rhs| (lhs.0 + rhs.0, lhs.1 + rhs.1));
+
+    let scale = (b - a) / (sample_count as f64);
+    Ok((f_sum * scale, f2_sum * scale))
+}
+
+// Estimate the integral from a to b of f(x, p) dx for every p in `params`. All parameter values
+// are evaluated at the same sample points, so the differences between the results carry much
+// less noise than independent estimates would
+pub fn integrate_correlated(
+    f: impl Fn(f64, f64) -> f64 + Sync,
+    params: &[f64],
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<Vec<f64>> {
+    check_integration(a, b, sample_count)?;
+    let sums = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| rng.gen_range(a..=b))
+        .fold(
+            || vec![0.0; params.len()],
+            |mut sums, x| {
+                for (sum, &p) in sums.iter_mut().zip(params) {
+                    *sum += f(x, p);
+                }
+                sums
+            },
+        )
+        .reduce(
+            || vec![0.0; params.len()],
+            |mut lhs, rhs| {
+                for (l, r) in lhs.iter_mut().zip(rhs) {
+                    *l += r;
+                }
+                lhs
+            },
+        );
+
+    let scale = (b - a) / (sample_count as f64);
+    Ok(sums.into_iter().map(|sum| sum * scale).collect())
+}
+
+// Estimate the integral from a to b of combine(f(x), g(x)) dx, with f and g evaluated at the
+// same sample points
+fn integrate_pointwise(
+    f: impl Fn(f64) -> f64 + Sync,
+    g: impl Fn(f64) -> f64 + Sync,
+    combine: fn(f64, f64) -> f64,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<f64> {
+    check_integration(a, b, sample_count)?;
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, _| {
+            let x = rng.gen_range(a..=b);
+            combine(f(x), g(x))
+        })
+        .sum();
+
+    Ok(sum * (b - a) / (sample_count as f64))
+}
+
+// Estimate the integral from a to b of max(f(x), g(x)) dx, without needing to know where f and g
+// cross
+pub fn integrate_max(
+    f: impl Fn(f64) -> f64 + Sync,
+    g: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<f64> {
+    integrate_pointwise(f, g, f64::max, a, b, sample_count)
+}
+
+// Estimate the integral from a to b of min(f(x), g(x)) dx
+pub fn integrate_min(
+    f: impl Fn(f64) -> f64 + Sync,
+    g: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<f64> {
+    integrate_pointwise(f, g, f64::min, a, b, sample_count)
+}
+
+// Estimate the integral from a to b of f(x) - g(x) dx, with f and g evaluated at the same sample
+// points. When f and g are close, their noise mostly cancels, so the variance of the difference
+// is far below var(integral of f) + var(integral of g) of two independent estimates
+pub fn integrate_difference(
+    f: impl Fn(f64) -> f64 + Sync,
+    g: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+) -> Result<IntegralEstimate> {
+    check_integration(a, b, sample_count)?;
+    let ve = sample_integrand(&|x| f(x) - g(x), a, b, sample_count);
+    Ok(IntegralEstimate::from_estimator(&ve, a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration::monte_carlo_integration;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_product() {
+        let (fg, f, g) = integrate_product(|x| x, |x| x, 0.0, 1.0, 100_000).unwrap();
+        assert_approx_eq!(fg, 1.0 / 3.0, 0.02);
+        assert_approx_eq!(f, 0.5, 0.02);
+        // Both share the same samples
+        assert_eq!(f, g);
+    }
+
+    #[test]
+    fn test_second_moment() {
+        let (f, f2) = integrate_with_second_moment(|x| x, 0.0, 1.0, 100_000).unwrap();
+        assert_approx_eq!(f, 0.5, 0.02);
+        assert_approx_eq!(f2, 1.0 / 3.0, 0.02);
+
+        // Both come from the same evaluations, like `integrate_product` of f with itself
+        let (f, f2) = integrate_with_second_moment(|_| 3.0, 0.0, 2.0, 100).unwrap();
+        assert_approx_eq!(f, 6.0);
+        assert_approx_eq!(f2, 18.0);
+    }
+
+    #[test]
+    fn test_correlated_parameters() {
+        let params = [1.0, 1.01, 2.0, 4.0];
+        let results = integrate_correlated(|x, p| x * p, &params, 0.0, 1.0, 10_000).unwrap();
+        assert_eq!(results.len(), params.len());
+        assert_approx_eq!(results[0], 0.5, 0.05);
+
+        // The noise is shared, so every result is exactly proportional to its parameter
+        let base = results[0];
+        for (&result, &p) in results.iter().zip(&params) {
+            assert_approx_eq!(result / p, base, 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_max_and_min() {
+        // x and 1 - x cross at 1/2, the maximum is two trapezoids of area 3/8
+        assert_approx_eq!(
+            integrate_max(|x| x, |x| 1.0 - x, 0.0, 1.0, 100_000).unwrap(),
+            0.75,
+            0.01
+        );
+        assert_approx_eq!(
+            integrate_min(|x| x, |x| 1.0 - x, 0.0, 1.0, 100_000).unwrap(),
+            0.25,
+            0.01
+        );
+    }
+
+    #[test]
+    fn test_difference_of_similar_integrands() {
+        let f = |x: f64| x.exp();
+        let g = |x: f64| x.exp() + 0.01 * x;
+        let difference = integrate_difference(f, g, 0.0, 1.0, 10_000).unwrap();
+        assert_approx_eq!(difference.integral, -0.005, 0.05);
+        assert_eq!(difference.samples, 10_000);
+
+        let independent_bound = monte_carlo_integration(f, 0.0, 1.0, 10_000)
+            .unwrap()
+            .variance
+            + monte_carlo_integration(g, 0.0, 1.0, 10_000)
+                .unwrap()
+                .variance;
+        assert!(difference.variance < independent_bound * 1e-3);
+    }
+
+    #[test]
+    fn test_correlated_no_parameters() {
+        assert!(integrate_correlated(|x, p| x * p, &[], 0.0, 1.0, 100)
+            .unwrap()
+            .is_empty());
+    }
+}
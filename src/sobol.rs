@@ -0,0 +1,749 @@
+// Sobol low-discrepancy sequences, optionally with Owen's nested uniform scrambling. Plain Sobol
+// points converge faster than random ones but carry no error estimate; scrambling randomizes them
+// while keeping their net structure, so independent replicas give an unbiased error estimate
+use crate::error::{check_bounds, MonteCarloError, Result};
+use crate::mapping::map_unit_to_interval;
+use crate::sampler::Sampler1D;
+use crate::stable_rng::mix64;
+use crate::variance_estimator::VarianceEstimator;
+use rand::Rng;
+use rayon::prelude::*;
+use std::sync::OnceLock;
+
+const BITS: usize = 32;
+
+// Direction numbers in Joe and Kuo's format: a header line, then one line per dimension after the
+// first with the dimension d, the primitive polynomial degree s, its coefficients a, and the s
+// initial direction numbers m_i. The embedded file is only the start of new-joe-kuo-6.21201
+// (S. Joe and F. Y. Kuo, "Constructing Sobol sequences with better two-dimensional projections",
+// 2008), enough for the first 21 dimensions. The full table, or any other in the same format,
+// can be loaded with `SobolSequence::from_joe_kuo`
+static JOE_KUO_DIRECTIONS: &[u8] =
+    include_bytes!("../data/new-joe-kuo-6.21201-first-21-dimensions");
+
+fn direction_numbers(degree: usize, coefficients: u32, initial: &[u32]) -> [u32; BITS] {
+    let mut v = [0_u32; BITS];
+    for k in 0..BITS {
+        v[k] = if k < degree {
+            initial[k] << (BITS - 1 - k)
+        } else {
+            let mut value = v[k - degree] ^ (v[k - degree] >> degree);
+            for j in 1..degree {
+                if (coefficients >> (degree - 1 - j)) & 1 == 1 {
+                    value ^= v[k - j];
+                }
+            }
+            value
+        };
+    }
+    v
+}
+
+// Parse a direction number table in Joe and Kuo's format into the direction numbers of each
+// dimension, starting with the van der Corput sequence for the first one
+fn parse_joe_kuo(text: &str) -> Result<Vec<[u32; BITS]>> {
+    let mut directions = vec![std::array::from_fn(|k| 1 << (BITS - 1 - k))];
+    for (line_index, line) in text.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = MonteCarloError::InvalidDirectionNumbers {
+            line: line_index + 1,
+        };
+        let fields: Vec<u32> = line
+            .split_whitespace()
+            .map(|field| field.parse().map_err(|_| invalid.clone()))
+            .collect::<Result<_>>()?;
+
+        let degree = *fields.get(1).ok_or(invalid.clone())? as usize;
+        let initial = &fields[3.min(fields.len())..];
+        let valid = (1..BITS).contains(&degree)
+            && initial.len() == degree
+            && initial
+                .iter()
+                .enumerate()
+                .all(|(k, &m)| m % 2 == 1 && m < 1 << (k + 1));
+        if !valid {
+            return Err(invalid);
+        }
+        directions.push(direction_numbers(degree, fields[2], initial));
+    }
+    Ok(directions)
+}
+
+fn embedded_directions() -> &'static [[u32; BITS]] {
+    static DIRECTIONS: OnceLock<Vec<[u32; BITS]>> = OnceLock::new();
+    DIRECTIONS.get_or_init(|| {
+        let text = std::str::from_utf8(JOE_KUO_DIRECTIONS).unwrap();
+        parse_joe_kuo(text).expect("the embedded direction numbers are valid")
+    })
+}
+
+// Number of dimensions the embedded direction numbers support
+pub fn max_dimension() -> usize {
+    embedded_directions().len()
+}
+
+#[derive(Clone, Debug)]
+pub struct SobolSequence {
+    directions: Vec<[u32; BITS]>,
+    scramble_seed: Option<u64>,
+}
+
+impl SobolSequence {
+    // Sequence in `dimension` dimensions, up to `max_dimension()`, from the embedded direction
+    // numbers
+    pub fn new(dimension: usize) -> Result<Self> {
+        Self::from_directions(embedded_directions(), dimension)
+    }
+
+    // Sequence in `dimension` dimensions from a direction number table in Joe and Kuo's format,
+    // e.g. the full new-joe-kuo-6.21201 file for up to 21201 dimensions
+    pub fn from_joe_kuo(table: &str, dimension: usize) -> Result<Self> {
+        Self::from_directions(&parse_joe_kuo(table)?, dimension)
+    }
+
+    fn from_directions(directions: &[[u32; BITS]], dimension: usize) -> Result<Self> {
+        if dimension > directions.len() {
+            return Err(MonteCarloError::UnsupportedDimension {
+                dimension,
+                max: directions.len(),
+            });
+        }
+
+        Ok(Self {
+            directions: directions[..dimension].to_vec(),
+            scramble_seed: None,
+        })
+    }
+
+    // Owen-scrambled sequence. Each seed gives an independent randomization, with every point
+    // uniformly distributed over the unit cube
+    pub fn scrambled(dimension: usize, scramble_seed: u64) -> Result<Self> {
+        Ok(Self {
+            scramble_seed: Some(scramble_seed),
+            ..Self::new(dimension)?
+        })
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.directions.len()
+    }
+
+    pub fn scramble_seed(&self) -> Option<u64> {
+        self.scramble_seed
+    }
+
+    // Coordinate `axis` of point `index`, in [0, 1). Only the first 2^32 points are distinct
+    pub fn component(&self, index: usize, axis: usize) -> f64 {
+        let mut bits = self.directions[axis]
+            .iter()
+            .enumerate()
+            .filter(|&(k, _)| (index >> k) & 1 == 1)
+            .fold(0_u32, |acc, (_, &v)| acc ^ v);
+        if let Some(seed) = self.scramble_seed {
+            bits = owen_scramble(bits, mix64(seed ^ mix64(axis as u64)));
+        }
+        bits as f64 / (1_u64 << BITS) as f64
+    }
+
+    pub fn point(&self, index: usize, point: &mut [f64]) {
+        for (axis, x) in point.iter_mut().enumerate() {
+            *x = self.component(index, axis);
+        }
+    }
+}
+
+// Nested uniform scrambling: each bit is flipped by a random bit that depends on all the bits
+// above it, here derived by hashing the prefix rather than storing the full random tree
+fn owen_scramble(bits: u32, axis_seed: u64) -> u32 {
+    (0..BITS).fold(bits, |acc, depth| {
+        let prefix = (bits as u64) >> (BITS - depth);
+        let key = ((depth as u64) << BITS) | prefix;
+        let flip = (mix64(axis_seed ^ mix64(key)) & 1) as u32;
+        acc ^ (flip << (BITS - 1 - depth))
+    })
+}
+
+// One-dimensional Sobol points for `integrate_with_sampler`. The random generator is unused,
+// any randomization comes from the scramble seed. Sample i is point `start_index + i` of the
+// sequence, so a long run can be saved as its index and continued later with `from_index`
+#[derive(Clone, Debug)]
+pub struct SobolSampler {
+    sequence: SobolSequence,
+    start_index: usize,
+}
+
+impl SobolSampler {
+    pub fn new() -> Self {
+        Self::from_index(0)
+    }
+
+    // Owen-scrambled sampler, see `SobolSequence::scrambled`
+    pub fn scrambled(scramble_seed: u64) -> Self {
+        Self {
+            sequence: SobolSequence::scrambled(1, scramble_seed)
+                .expect("one dimension is always supported"),
+            start_index: 0,
+        }
+    }
+
+    // Sampler starting at point `index` of the unscrambled sequence. Points are computed directly
+    // from their index, so the earlier ones are never generated
+    pub fn from_index(index: usize) -> Self {
+        Self {
+            sequence: SobolSequence::new(1).expect("one dimension is always supported"),
+            start_index: index,
+        }
+    }
+
+    // The same sampler, scrambled or not, starting at point `index` instead
+    pub fn resumed_at(&self, index: usize) -> Self {
+        Self {
+            start_index: index,
+            ..self.clone()
+        }
+    }
+
+    pub fn scramble_seed(&self) -> Option<u64> {
+        self.sequence.scramble_seed()
+    }
+
+    pub fn start_index(&self) -> usize {
+        self.start_index
+    }
+
+    // Index of the next point after drawing `sample_count` samples from this sampler, to save and
+    // pass to `from_index` when resuming
+    pub fn index_after(&self, sample_count: usize) -> usize {
+        self.start_index + sample_count
+    }
+}
+
+impl Default for SobolSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler1D for SobolSampler {
+    fn sample<R: Rng>(&self, index: usize, _count: usize, _rng: &mut R) -> f64 {
+        self.sequence.component(self.start_index + index, 0)
+    }
+}
+
+// With `antithetic`, each point u is paired with its mirror 1 - u
+fn quasi_monte_carlo(
+    f: &(impl Fn(&[f64]) -> f64 + Sync),
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+    sequence: &SobolSequence,
+    antithetic: bool,
+) -> f64 {
+    let volume: f64 = lower.iter().zip(upper).map(|(lo, hi)| hi - lo).product();
+    let sum: f64 = (0..sample_count)
+        .into_par_iter()
+        .map_init(
+            || (vec![0.0; lower.len()], vec![0.0; lower.len()]),
+            |(u, point), i| {
+                sequence.point(i, u);
+                let mut evaluate = |mirror: bool| {
+                    for (axis, x) in point.iter_mut().enumerate() {
+                        let t = if mirror { 1.0 - u[axis] } else { u[axis] };
+                        *x = map_unit_to_interval(t, lower[axis], upper[axis]);
+                    }
+                    f(point)
+                };
+                if antithetic {
+                    (evaluate(false) + evaluate(true)) / 2.0
+                } else {
+                    evaluate(false)
+                }
+            },
+        )
+        .sum();
+    sum * volume / (sample_count as f64)
+}
+
+fn validate(lower: &[f64], upper: &[f64], sample_count: usize) -> Result<()> {
+    if lower.len() != upper.len() {
+        return Err(MonteCarloError::DimensionMismatch {
+            expected: lower.len(),
+            actual: upper.len(),
+        });
+    }
+    for (&lo, &hi) in lower.iter().zip(upper) {
+        check_bounds(lo, hi)?;
+    }
+    if sample_count == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    Ok(())
+}
+
+// Estimate the integral of f over the box [lower, upper] with the first `sample_count` points of
+// a Sobol sequence, Owen-scrambled if a seed is given. Powers of two give the best uniformity
+pub fn integrate_sobol(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+    scramble_seed: Option<u64>,
+) -> Result<f64> {
+    sobol_estimate(&f, lower, upper, sample_count, scramble_seed, false)
+}
+
+// Same as `integrate_sobol`, but each point u is also mirrored to 1 - u, doubling the number of
+// evaluations. A scrambled point is uniform, and so is its mirror, so the pair average stays
+// unbiased and the mirroring cancels the part of f that is odd around the center of the box
+pub fn integrate_sobol_antithetic(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+    scramble_seed: Option<u64>,
+) -> Result<f64> {
+    sobol_estimate(&f, lower, upper, sample_count, scramble_seed, true)
+}
+
+fn sobol_estimate(
+    f: &(impl Fn(&[f64]) -> f64 + Sync),
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+    scramble_seed: Option<u64>,
+    antithetic: bool,
+) -> Result<f64> {
+    validate(lower, upper, sample_count)?;
+    let sequence = match scramble_seed {
+        Some(seed) => SobolSequence::scrambled(lower.len(), seed)?,
+        None => SobolSequence::new(lower.len())?,
+    };
+    Ok(quasi_monte_carlo(
+        f,
+        lower,
+        upper,
+        sample_count,
+        &sequence,
+        antithetic,
+    ))
+}
+
+// Run `estimate` with `replicas` independently scrambled sequences in parallel, and accumulate the
+// estimates in replica order. Replica i is scrambled with mix64(seed ^ i). At least 2 replicas are
+// needed for their spread to mean anything
+fn replicate(
+    dimension: usize,
+    replicas: usize,
+    seed: u64,
+    estimate: impl Fn(&SobolSequence) -> f64 + Sync,
+) -> Result<VarianceEstimator> {
+    if replicas < 2 {
+        return Err(MonteCarloError::InsufficientSamples {
+            required: 2,
+            provided: replicas,
+        });
+    }
+    let estimates = (0..replicas)
+        .into_par_iter()
+        .map(|replica| {
+            let sequence = SobolSequence::scrambled(dimension, mix64(seed ^ replica as u64))?;
+            Ok(estimate(&sequence))
+        })
+        .collect::<Result<Vec<f64>>>()?;
+
+    let mut ve = VarianceEstimator::new();
+    estimates.into_iter().for_each(|x| ve.add_sample(x));
+    Ok(ve)
+}
+
+// Estimate the integral of f over the box [lower, upper] by averaging `replicas` independently
+// scrambled Sobol estimates of `sample_count` points each. Returns the estimate and its standard
+// error, computed from the spread of the replicas
+pub fn integrate_scrambled_sobol(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+    replicas: usize,
+    seed: u64,
+) -> Result<(f64, f64)> {
+    scrambled_sobol_estimate(&f, lower, upper, sample_count, replicas, seed, false)
+}
+
+// Same as `integrate_scrambled_sobol`, with every point mirrored as in
+// `integrate_sobol_antithetic`
+pub fn integrate_scrambled_sobol_antithetic(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+    replicas: usize,
+    seed: u64,
+) -> Result<(f64, f64)> {
+    scrambled_sobol_estimate(&f, lower, upper, sample_count, replicas, seed, true)
+}
+
+fn scrambled_sobol_estimate(
+    f: &(impl Fn(&[f64]) -> f64 + Sync),
+    lower: &[f64],
+    upper: &[f64],
+    sample_count: usize,
+    replicas: usize,
+    seed: u64,
+    antithetic: bool,
+) -> Result<(f64, f64)> {
+    validate(lower, upper, sample_count)?;
+
+    let ve = replicate(lower.len(), replicas, seed, |sequence| {
+        quasi_monte_carlo(f, lower, upper, sample_count, sequence, antithetic)
+    })?;
+    Ok((ve.mean, ve.std_error().unwrap()))
+}
+
+// Estimate integral from a to b of f(x) dx with `replicas` independently scrambled Sobol runs of
+// `sample_count` points each, run in parallel. Returns the combined mean along with the estimator
+// over the replica estimates, whose `std_error` is the error bar of that mean. A single QMC run
+// has no usable variance estimate of its own, so this is the standard way to get one
+pub fn integrate_replicated(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    sample_count: usize,
+    replicas: usize,
+    base_seed: u64,
+) -> Result<(f64, VarianceEstimator)> {
+    validate(&[a], &[b], sample_count)?;
+    let ve = replicate(1, replicas, base_seed, |sequence| {
+        quasi_monte_carlo(
+            &|x: &[f64]| f(x[0]),
+            &[a],
+            &[b],
+            sample_count,
+            sequence,
+            false,
+        )
+    })?;
+    Ok((ve.mean, ve))
+}
+
+// Estimate integral from a to b of f(x) dx with randomized QMC: `replicas` copies of the first
+// `points_per_replica` plain Sobol points, each moved by its own Cranley-Patterson random shift,
+// u -> frac(u + shift). A shifted point is uniform on [0, 1), so every replica is an unbiased
+// estimate, while keeping the low discrepancy of the sequence. Replica i is shifted by
+// mix64(seed ^ i). Returns the mean of the replicas along with their estimator, whose `std_error`
+// is the error bar of that mean, as for `integrate_replicated`
+pub fn integrate_rqmc(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    points_per_replica: usize,
+    replicas: usize,
+    seed: u64,
+) -> Result<(f64, VarianceEstimator)> {
+    validate(&[a], &[b], points_per_replica)?;
+    if replicas < 2 {
+        return Err(MonteCarloError::InsufficientSamples {
+            required: 2,
+            provided: replicas,
+        });
+    }
+
+    let sequence = SobolSequence::new(1)?;
+    let estimates: Vec<f64> = (0..replicas)
+        .into_par_iter()
+        .map(|replica| {
+            let shift = (mix64(seed ^ replica as u64) >> 11) as f64 / (1_u64 << 53) as f64;
+            let sum: f64 = (0..points_per_replica)
+                .map(|i| {
+                    let u = (sequence.component(i, 0) + shift).fract();
+                    f(map_unit_to_interval(u, a, b))
+                })
+                .sum();
+            sum * (b - a) / points_per_replica as f64
+        })
+        .collect();
+
+    let mut ve = VarianceEstimator::new();
+    estimates.into_iter().for_each(|x| ve.add_sample(x));
+    Ok((ve.mean, ve))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use rand::SeedableRng;
+
+    // Whether each of the 2^m cells of width 2^-m holds exactly one of the first 2^m points
+    fn is_stratified(sequence: &SobolSequence, axis: usize, m: u32) -> bool {
+        let count = 1_usize << m;
+        let mut seen = vec![false; count];
+        for i in 0..count {
+            let cell = (sequence.component(i, axis) * count as f64) as usize;
+            if std::mem::replace(&mut seen[cell], true) {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn test_first_points() {
+        let sequence = SobolSequence::new(2).unwrap();
+        let first: Vec<f64> = (0..4).map(|i| sequence.component(i, 0)).collect();
+        assert_eq!(first, [0.0, 0.5, 0.25, 0.75]);
+        let second: Vec<f64> = (0..4).map(|i| sequence.component(i, 1)).collect();
+        assert_eq!(second, [0.0, 0.5, 0.75, 0.25]);
+    }
+
+    #[test]
+    fn test_matches_reference_points() {
+        // The first points of the Joe-Kuo reference implementation, which enumerates the
+        // sequence in Gray code order
+        let reference = [
+            [0.0, 0.0, 0.0],
+            [0.5, 0.5, 0.5],
+            [0.75, 0.25, 0.25],
+            [0.25, 0.75, 0.75],
+            [0.375, 0.375, 0.625],
+            [0.875, 0.875, 0.125],
+            [0.625, 0.125, 0.875],
+            [0.125, 0.625, 0.375],
+        ];
+        let sampler = SobolSequence::new(3).unwrap();
+        for (i, expected) in reference.iter().enumerate() {
+            let mut point = [0.0; 3];
+            sampler.point(i ^ (i >> 1), &mut point);
+            assert_eq!(&point, expected);
+        }
+    }
+
+    #[test]
+    fn test_embedded_table() {
+        assert_eq!(max_dimension(), 21);
+        assert!(SobolSequence::new(max_dimension()).is_ok());
+    }
+
+    #[test]
+    fn test_load_joe_kuo_table() {
+        let table = "d s a m_i\n2 1 0 1\n3 2 1 1 3\n";
+        let loaded = SobolSequence::from_joe_kuo(table, 3).unwrap();
+        let embedded = SobolSequence::new(3).unwrap();
+        for i in 0..64 {
+            for axis in 0..3 {
+                assert_eq!(loaded.component(i, axis), embedded.component(i, axis));
+            }
+        }
+
+        assert!(SobolSequence::from_joe_kuo(table, 4).is_err());
+        assert_eq!(
+            SobolSequence::from_joe_kuo("d s a m_i\n2 1 0 2\n", 2).unwrap_err(),
+            MonteCarloError::InvalidDirectionNumbers { line: 2 }
+        );
+        assert_eq!(
+            SobolSequence::from_joe_kuo("d s a m_i\n2 2 x 1 3\n", 2).unwrap_err(),
+            MonteCarloError::InvalidDirectionNumbers { line: 2 }
+        );
+    }
+
+    #[test]
+    fn test_unsupported_dimension() {
+        assert_eq!(
+            SobolSequence::new(max_dimension() + 1).unwrap_err(),
+            MonteCarloError::UnsupportedDimension {
+                dimension: max_dimension() + 1,
+                max: max_dimension()
+            }
+        );
+    }
+
+    #[test]
+    fn test_scrambling_preserves_stratification() {
+        let plain = SobolSequence::new(max_dimension()).unwrap();
+        let scrambled = SobolSequence::scrambled(max_dimension(), 17).unwrap();
+        for axis in 0..max_dimension() {
+            assert!(is_stratified(&plain, axis, 10));
+            assert!(is_stratified(&scrambled, axis, 10));
+        }
+        assert_ne!(plain.component(0, 0), scrambled.component(0, 0));
+    }
+
+    #[test]
+    fn test_seeds_are_independent() {
+        let lhs = SobolSequence::scrambled(3, 1).unwrap();
+        let rhs = SobolSequence::scrambled(3, 2).unwrap();
+        assert!((0..8).any(|i| lhs.component(i, 2) != rhs.component(i, 2)));
+    }
+
+    #[test]
+    fn test_sampler() {
+        let estimate = crate::sampler::integrate_with_sampler(
+            &SobolSampler::default(),
+            |x| x * x,
+            0.0,
+            1.0,
+            1024,
+        )
+        .unwrap();
+        assert_approx_eq!(estimate, 1.0 / 3.0, 0.01);
+    }
+
+    #[test]
+    fn test_rqmc_error_bar_is_valid() {
+        let exact = std::f64::consts::E - 1.0;
+        let covered = (0..20)
+            .filter(|&seed| {
+                let (estimate, ve) = integrate_rqmc(f64::exp, 0.0, 1.0, 256, 16, seed).unwrap();
+                (estimate - exact).abs() < 3.0 * ve.std_error().unwrap()
+            })
+            .count();
+        assert!(
+            covered >= 17,
+            "only {covered} of 20 error bars cover the exact value"
+        );
+
+        assert_eq!(
+            integrate_rqmc(f64::exp, 0.0, 1.0, 256, 1, 0).unwrap_err(),
+            MonteCarloError::InsufficientSamples {
+                required: 2,
+                provided: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_rqmc_beats_plain_monte_carlo() {
+        let (estimate, rqmc) = integrate_rqmc(f64::exp, 0.0, 1.0, 1024, 16, 3).unwrap();
+        let plain =
+            crate::integration::sample_integrand_seeded(f64::exp, 0.0, 1.0, 16 * 1024, 3).unwrap();
+
+        assert_approx_eq!(estimate, std::f64::consts::E - 1.0, 1e-3);
+        assert!(rqmc.std_error().unwrap() * 10.0 < plain.std_error().unwrap());
+    }
+
+    #[test]
+    fn test_sampler_resumes_from_index() {
+        let mut rng = rand::thread_rng();
+        let uninterrupted = SobolSampler::default();
+        let first = SobolSampler::from_index(0);
+        let resumed = SobolSampler::from_index(first.index_after(100));
+
+        for i in 0..100 {
+            assert_eq!(
+                resumed.sample(i, 100, &mut rng),
+                uninterrupted.sample(100 + i, 200, &mut rng)
+            );
+        }
+
+        let seeded = SobolSampler::scrambled(9);
+        let seeded_resumed = seeded.resumed_at(seeded.index_after(64));
+        assert_eq!(seeded_resumed.scramble_seed(), Some(9));
+        assert_eq!(seeded_resumed.start_index(), 64);
+        assert_eq!(
+            seeded_resumed.sample(3, 64, &mut rng),
+            seeded.sample(67, 128, &mut rng)
+        );
+
+        // Two halves of a run average to the full run
+        let f = |x: f64| x.exp();
+        let half = |sampler: &SobolSampler| {
+            crate::sampler::integrate_with_sampler(sampler, f, 0.0, 1.0, 512).unwrap()
+        };
+        let full =
+            crate::sampler::integrate_with_sampler(&uninterrupted, f, 0.0, 1.0, 1024).unwrap();
+        assert_approx_eq!(
+            (half(&first) + half(&SobolSampler::from_index(512))) / 2.0,
+            full,
+            1e-12
+        );
+    }
+
+    #[test]
+    fn test_scrambled_replicas_unbiased_and_fast() {
+        // Integral of exp(x + y) over the unit square is (e - 1)^2
+        let exact = (std::f64::consts::E - 1.0).powi(2);
+        let f = |p: &[f64]| (p[0] + p[1]).exp();
+        let rms_error = |sample_count: usize| {
+            let squared: f64 = (0..16)
+                .map(|seed| {
+                    let estimate =
+                        integrate_sobol(f, &[0.0, 0.0], &[1.0, 1.0], sample_count, Some(seed))
+                            .unwrap();
+                    (estimate - exact).powi(2)
+                })
+                .sum();
+            (squared / 16.0).sqrt()
+        };
+
+        // Plain Monte Carlo would only improve by a factor of 4 for 16 times more samples
+        assert!(rms_error(256) > rms_error(4096) * 16.0);
+
+        let (estimate, std_error) =
+            integrate_scrambled_sobol(f, &[0.0, 0.0], &[1.0, 1.0], 1024, 32, 3).unwrap();
+        assert!((estimate - exact).abs() < 5.0 * std_error);
+        assert!(std_error < 1e-3);
+    }
+
+    #[test]
+    fn test_antithetic_sobol_reduces_variance() {
+        let f = |p: &[f64]| (p[0] + 2.0 * p[1]).exp();
+        let exact = (std::f64::consts::E - 1.0) * (std::f64::consts::E.powi(2) - 1.0) / 2.0;
+        const POINTS: usize = 256;
+
+        let replica_variance = |estimate: &dyn Fn(u64) -> f64| {
+            let mut ve = VarianceEstimator::new();
+            (0..64).for_each(|seed| ve.add_sample(estimate(seed)));
+            assert_approx_eq!(ve.mean, exact, 0.01);
+            ve.variance()
+        };
+        let sobol = replica_variance(&|seed| {
+            integrate_sobol(f, &[0.0, 0.0], &[1.0, 1.0], POINTS, Some(seed)).unwrap()
+        });
+        let combined = replica_variance(&|seed| {
+            integrate_sobol_antithetic(f, &[0.0, 0.0], &[1.0, 1.0], POINTS, Some(seed)).unwrap()
+        });
+        let antithetic_mc = replica_variance(&|seed| {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            (0..POINTS)
+                .map(|_| {
+                    let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+                    (f(&[u, v]) + f(&[1.0 - u, 1.0 - v])) / 2.0
+                })
+                .sum::<f64>()
+                / POINTS as f64
+        });
+
+        assert!(combined < sobol);
+        assert!(combined < antithetic_mc);
+
+        let (estimate, std_error) =
+            integrate_scrambled_sobol_antithetic(f, &[0.0, 0.0], &[1.0, 1.0], POINTS, 32, 5)
+                .unwrap();
+        assert!((estimate - exact).abs() < 5.0 * std_error);
+    }
+
+    #[test]
+    fn test_replicated_error_estimate() {
+        let exact = std::f64::consts::E - 1.0;
+        // Standardized errors (mean - exact) / std_error of many replicated runs. If the error
+        // estimate is valid, their mean square is close to 1, here (16 - 1) / (16 - 3) with the
+        // t-distribution of 16 replicas
+        let mut squared = VarianceEstimator::new();
+        for base_seed in 0..128 {
+            let (mean, ve) = integrate_replicated(f64::exp, 0.0, 1.0, 64, 16, base_seed).unwrap();
+            assert_eq!(ve.sample_count(), 16);
+            assert_eq!(mean, ve.mean);
+            squared.add_sample(((mean - exact) / ve.std_error().unwrap()).powi(2));
+        }
+        assert!(squared.mean > 0.5 && squared.mean < 2.0);
+    }
+
+    #[test]
+    fn test_scrambled_needs_replicas() {
+        assert!(integrate_scrambled_sobol(|p| p[0], &[0.0], &[1.0], 64, 1, 0).is_err());
+        assert!(integrate_scrambled_sobol_antithetic(|p| p[0], &[0.0], &[1.0], 64, 1, 0).is_err());
+        assert!(integrate_replicated(|x| x, 0.0, 1.0, 64, 1, 0).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+// A random number generator whose output is part of this crate's contract. The stream for a given
+// seed will not change across versions, independently of the `rand` ecosystem, so published
+// results can be reproduced long after the dependencies have moved on.
+//
+// The algorithm is SplitMix64 (Steele, Lea and Flood, "Fast splittable pseudorandom number
+// generators", 2014), with its constants vendored here. Floats are built from the top 53 bits
+use std::num::Wrapping;
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+pub(crate) fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[derive(Clone, Debug)]
+pub struct StableRng {
+    state: Wrapping<u64>,
+}
+
+impl StableRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            state: Wrapping(seed),
+        }
+    }
+
+    // Independent stream for parallel work. Each stream starts at a hashed position of the
+    // SplitMix64 cycle, far away from every other stream's start with overwhelming probability
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        Self::from_seed(mix64(seed ^ mix64(stream.wrapping_add(GOLDEN_GAMMA))))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state += GOLDEN_GAMMA;
+        mix64(self.state.0)
+    }
+
+    // Uniformly distributed in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1_u64 << 53) as f64)
+    }
+
+    // Uniformly distributed in [a, b)
+    pub fn next_in(&mut self, a: f64, b: f64) -> f64 {
+        a + (b - a) * self.next_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_output() {
+        // Published SplitMix64 outputs for seed 1234567
+        let mut rng = StableRng::from_seed(1234567);
+        let expected: [u64; 5] = [
+            6457827717110365317,
+            3203168211198807973,
+            9817491932198370423,
+            4593380528125082431,
+            16408922859458223821,
+        ];
+        for value in expected {
+            assert_eq!(rng.next_u64(), value);
+        }
+    }
+
+    #[test]
+    fn test_floats_in_range() {
+        let mut rng = StableRng::with_stream(42, 3);
+        for _ in 0..10_000 {
+            let u = rng.next_f64();
+            assert!((0.0..1.0).contains(&u));
+            let x = rng.next_in(-2.0, 5.0);
+            assert!((-2.0..5.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn test_streams_differ() {
+        let mut lhs = StableRng::with_stream(42, 0);
+        let mut rhs = StableRng::with_stream(42, 1);
+        assert_ne!(lhs.next_u64(), rhs.next_u64());
+    }
+}
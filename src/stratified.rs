@@ -0,0 +1,489 @@
+use crate::error::{check_bounds, check_integration, MonteCarloError, Result};
+use crate::mapping::map_unit_to_interval;
+use crate::variance_estimator::VarianceEstimator;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Bounds of the i-th of `strata` equal-width sub-intervals of [a, b]
+pub(crate) fn stratum_bounds(a: f64, b: f64, strata: usize, i: usize) -> (f64, f64) {
+    let lo = map_unit_to_interval(i as f64 / strata as f64, a, b);
+    let hi = if i + 1 == strata {
+        b
+    } else {
+        map_unit_to_interval((i + 1) as f64 / strata as f64, a, b)
+    };
+    (lo, hi)
+}
+
+// Valid bounds, split into at least one stratum: no strata at all leaves nothing to sample
+fn check_strata(a: f64, b: f64, strata: usize) -> Result<()> {
+    check_bounds(a, b)?;
+    if strata == 0 {
+        return Err(MonteCarloError::InvalidParameter {
+            name: "strata",
+            value: 0.0,
+        });
+    }
+    Ok(())
+}
+
+fn sample_stratum(
+    f: &(impl Fn(f64) -> f64 + Sync),
+    lo: f64,
+    hi: f64,
+    sample_count: usize,
+    ve: &mut VarianceEstimator,
+) {
+    let mut rng = thread_rng();
+    for _ in 0..sample_count {
+        ve.add_sample(f(rng.gen_range(lo..=hi)));
+    }
+}
+
+// Estimate integral from a to b of f(x) dx by splitting [a, b] into `strata` equal-width
+// sub-intervals and drawing the same number of samples in each of them
+pub fn integrate_stratified(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    strata: usize,
+    samples_per_stratum: usize,
+) -> Result<f64> {
+    Ok(integrate_stratified_with_variance(f, a, b, strata, samples_per_stratum)?.0)
+}
+
+// Same as `integrate_stratified`, also returning the variance of the estimate. The strata are
+// sampled independently, so it is the sum over strata of width^2 * variance / sample count: only
+// the variation within each stratum counts, not the differences between their means. Returns
+// (estimate, variance)
+pub fn integrate_stratified_with_variance(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    strata: usize,
+    samples_per_stratum: usize,
+) -> Result<(f64, f64)> {
+    check_strata(a, b, strata)?;
+    if samples_per_stratum == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    let estimators: Vec<(f64, VarianceEstimator)> = (0..strata)
+        .into_par_iter()
+        .map(|i| {
+            let (lo, hi) = stratum_bounds(a, b, strata, i);
+            let mut ve = VarianceEstimator::new();
+            sample_stratum(&f, lo, hi, samples_per_stratum, &mut ve);
+            (hi - lo, ve)
+        })
+        .collect();
+
+    let estimate = estimators.iter().map(|(width, ve)| width * ve.mean).sum();
+    let variance = estimators
+        .iter()
+        .map(|(width, ve)| width * width * ve.variance_of_mean())
+        .sum();
+    Ok((estimate, variance))
+}
+
+// Estimate integral from a to b of f(x) dx like `integrate_stratified`, but with explicit stratum
+// boundaries instead of equal widths, from a to b included. Every stratum gets the same number of
+// samples, so narrow strata where f changes fast are sampled more densely than wide flat ones
+pub fn integrate_stratified_with_boundaries(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    boundaries: &[f64],
+    samples_per_stratum: usize,
+) -> Result<f64> {
+    check_integration(a, b, samples_per_stratum)?;
+    match boundaries {
+        [first, .., last] if *first == a && *last == b => {}
+        _ => return Err(MonteCarloError::InvalidBounds { a, b }),
+    }
+    if let Some(w) = boundaries.windows(2).find(|w| w[0] >= w[1]) {
+        return Err(MonteCarloError::InvalidBounds { a: w[0], b: w[1] });
+    }
+
+    Ok(boundaries
+        .par_windows(2)
+        .map(|w| {
+            let mut ve = VarianceEstimator::new();
+            sample_stratum(&f, w[0], w[1], samples_per_stratum, &mut ve);
+            ve.mean * (w[1] - w[0])
+        })
+        .sum())
+}
+
+// Split `budget` samples across strata proportionally to `weights`, handing the rounding
+// leftovers to the strata with the largest weights. Without any stratum nothing is allocated
+pub(crate) fn allocate(weights: &[f64], budget: usize) -> Vec<usize> {
+    if weights.is_empty() {
+        return Vec::new();
+    }
+    let total_weight: f64 = weights.iter().sum();
+    if total_weight <= 0.0 {
+        let share = budget / weights.len();
+        let mut allocation = vec![share; weights.len()];
+        (0..budget - share * weights.len()).for_each(|i| allocation[i] += 1);
+        return allocation;
+    }
+
+    let mut allocation: Vec<usize> = weights
+        .iter()
+        .map(|w| (budget as f64 * w / total_weight).floor() as usize)
+        .collect();
+
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by(|&i, &j| weights[j].total_cmp(&weights[i]));
+    let leftover = budget - allocation.iter().sum::<usize>();
+    order
+        .iter()
+        .cycle()
+        .take(leftover)
+        .for_each(|&i| allocation[i] += 1);
+
+    allocation
+}
+
+// Estimate integral from a to b of f(x) dx with stratified sampling using Neyman allocation.
+// A pilot pass draws `pilot_per_stratum` samples in each of the `strata` equal-width strata to
+// estimate their standard deviations. The rest of `total_samples` is then assigned to strata
+// proportionally to `width * standard deviation`, so rough regions get more samples than flat ones
+pub fn integrate_stratified_optimal(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    strata: usize,
+    total_samples: usize,
+    pilot_per_stratum: usize,
+) -> Result<f64> {
+    check_strata(a, b, strata)?;
+    // Every stratum needs a pilot sample, or it would contribute nothing to the estimate
+    if pilot_per_stratum == 0 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    if total_samples < strata * pilot_per_stratum {
+        return Err(MonteCarloError::InsufficientSamples {
+            required: strata * pilot_per_stratum,
+            provided: total_samples,
+        });
+    }
+
+    let mut estimators: Vec<VarianceEstimator> = (0..strata)
+        .into_par_iter()
+        .map(|i| {
+            let (lo, hi) = stratum_bounds(a, b, strata, i);
+            let mut ve = VarianceEstimator::new();
+            sample_stratum(&f, lo, hi, pilot_per_stratum, &mut ve);
+            ve
+        })
+        .collect();
+
+    let weights: Vec<f64> = estimators
+        .iter()
+        .enumerate()
+        .map(|(i, ve)| {
+            let (lo, hi) = stratum_bounds(a, b, strata, i);
+            (hi - lo) * ve.variance().sqrt()
+        })
+        .collect();
+    let allocation = allocate(&weights, total_samples - strata * pilot_per_stratum);
+
+    Ok(estimators
+        .par_iter_mut()
+        .zip(allocation)
+        .enumerate()
+        .map(|(i, (ve, sample_count))| {
+            let (lo, hi) = stratum_bounds(a, b, strata, i);
+            sample_stratum(&f, lo, hi, sample_count, ve);
+            ve.mean * (hi - lo)
+        })
+        .sum())
+}
+
+// Estimate how much stratifying [a, b] into `strata` equal-width strata reduces the variance
+// compared to plain Monte Carlo with the same sample count, from a pilot run of `pilot_samples`
+// samples spread across the strata. The plain variance is the within-stratum variance plus the
+// spread of the stratum means, stratification removes the latter. A gain close to 1 means adding
+// strata is not worthwhile. Every stratum needs at least 2 pilot samples for its variance
+pub fn stratification_gain(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    strata: usize,
+    pilot_samples: usize,
+) -> Result<f64> {
+    check_strata(a, b, strata)?;
+    if pilot_samples < 2 * strata {
+        return Err(MonteCarloError::InsufficientSamples {
+            required: 2 * strata,
+            provided: pilot_samples,
+        });
+    }
+    let per_stratum = pilot_samples / strata;
+    let estimators: Vec<(f64, VarianceEstimator)> = (0..strata)
+        .into_par_iter()
+        .map(|i| {
+            let (lo, hi) = stratum_bounds(a, b, strata, i);
+            let mut ve = VarianceEstimator::new();
+            sample_stratum(&f, lo, hi, per_stratum, &mut ve);
+            ((hi - lo) / (b - a), ve)
+        })
+        .collect();
+
+    let mean: f64 = estimators.iter().map(|(w, ve)| w * ve.mean).sum();
+    let within: f64 = estimators.iter().map(|(w, ve)| w * ve.variance()).sum();
+    let between: f64 = estimators
+        .iter()
+        .map(|(w, ve)| w * (ve.mean - mean) * (ve.mean - mean))
+        .sum();
+
+    Ok(if within > 0.0 {
+        (within + between) / within
+    } else if between > 0.0 {
+        f64::INFINITY
+    } else {
+        1.0
+    })
+}
+
+// Estimate the cumulative integral from a to t of f(x) dx for every t in `checkpoints`, all from
+// one pass of `sample_count` samples with one jittered sample per equal-width cell of [a, b].
+// A checkpoint inside a cell gets the fraction of that cell's contribution proportional to the
+// covered width, which is exact for f constant over each cell. The estimates are nested: for
+// nonnegative f they are monotone in t. Checkpoints outside [a, b] are clamped to it
+pub fn cumulative_integral(
+    f: impl Fn(f64) -> f64 + Sync,
+    a: f64,
+    b: f64,
+    checkpoints: &[f64],
+    sample_count: usize,
+) -> Result<Vec<f64>> {
+    check_integration(a, b, sample_count)?;
+    let values: Vec<f64> = (0..sample_count)
+        .into_par_iter()
+        .map_init(thread_rng, |rng, i| {
+            let (lo, hi) = stratum_bounds(a, b, sample_count, i);
+            f(rng.gen_range(lo..=hi))
+        })
+        .collect();
+
+    // prefix[i] is the estimated integral over the first i cells
+    let mut prefix = Vec::with_capacity(sample_count + 1);
+    prefix.push(0.0);
+    for (i, value) in values.iter().enumerate() {
+        let (lo, hi) = stratum_bounds(a, b, sample_count, i);
+        prefix.push(prefix[i] + value * (hi - lo));
+    }
+
+    Ok(checkpoints
+        .iter()
+        .map(|&t| {
+            let t = t.clamp(a, b);
+            let position = (t - a) / (b - a) * sample_count as f64;
+            let cell = (position as usize).min(sample_count - 1);
+            let (lo, _) = stratum_bounds(a, b, sample_count, cell);
+            prefix[cell] + values[cell] * (t - lo)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    // Flat on [0, 0.5), rough on [0.5, 1]. Its integral over [0, 1] is 1 + (cos(20) - cos(40)) / 40
+    fn half_rough(x: f64) -> f64 {
+        if x < 0.5 {
+            1.0
+        } else {
+            1.0 + (40.0 * x).sin()
+        }
+    }
+
+    fn estimate_variance(estimate: impl Fn() -> f64 + Sync) -> VarianceEstimator {
+        (0..256)
+            .into_par_iter()
+            .fold(VarianceEstimator::new, |mut ve, _| {
+                ve.add_sample(estimate());
+                ve
+            })
+            .reduce(VarianceEstimator::new, VarianceEstimator::merge)
+    }
+
+    #[test]
+    fn test_cumulative_constant() {
+        let checkpoints = [0.0, 0.25, 0.3337, 1.0, 2.5, 3.0];
+        let cumulative = cumulative_integral(|_| 1.0, 0.0, 3.0, &checkpoints, 1000).unwrap();
+        for (&g, &t) in cumulative.iter().zip(&checkpoints) {
+            assert!((g - t).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_cumulative_monotone_and_consistent() {
+        let checkpoints: Vec<f64> = (0..=20).map(|i| i as f64 * 0.05).collect();
+        let cumulative = cumulative_integral(|x| x * x, 0.0, 1.0, &checkpoints, 10_000).unwrap();
+
+        assert!(cumulative.windows(2).all(|w| w[0] <= w[1]));
+        for (&g, &t) in cumulative.iter().zip(&checkpoints).skip(4) {
+            assert_approx_eq!(g, t * t * t / 3.0, 0.01);
+        }
+    }
+
+    #[test]
+    fn test_custom_boundaries_concentrated_near_feature() {
+        // Steep transition at x = 0.3, flat elsewhere
+        let f = |x: f64| (50.0 * (x - 0.3)).tanh();
+        let expected = ((35.0_f64).cosh().ln() - (15.0_f64).cosh().ln()) / 50.0;
+        let equal: Vec<f64> = (0..=8).map(|i| i as f64 / 8.0).collect();
+        let custom = [0.0, 0.2, 0.26, 0.28, 0.3, 0.32, 0.34, 0.4, 1.0];
+
+        let equal = estimate_variance(|| {
+            integrate_stratified_with_boundaries(f, 0.0, 1.0, &equal, 16).unwrap()
+        });
+        let custom = estimate_variance(|| {
+            integrate_stratified_with_boundaries(f, 0.0, 1.0, &custom, 16).unwrap()
+        });
+
+        assert_approx_eq!(equal.mean, expected, 0.01);
+        assert_approx_eq!(custom.mean, expected, 0.01);
+        assert!(custom.variance() < equal.variance() / 4.0);
+    }
+
+    #[test]
+    fn test_custom_boundaries_validation() {
+        let f = |x: f64| x;
+        assert_eq!(
+            integrate_stratified_with_boundaries(f, 0.0, 1.0, &[0.0, 0.5], 4),
+            Err(MonteCarloError::InvalidBounds { a: 0.0, b: 1.0 })
+        );
+        assert_eq!(
+            integrate_stratified_with_boundaries(f, 0.0, 1.0, &[1.0], 4),
+            Err(MonteCarloError::InvalidBounds { a: 0.0, b: 1.0 })
+        );
+        assert_eq!(
+            integrate_stratified_with_boundaries(f, 0.0, 1.0, &[0.0, 0.6, 0.4, 1.0], 4),
+            Err(MonteCarloError::InvalidBounds { a: 0.6, b: 0.4 })
+        );
+        assert_approx_eq!(
+            integrate_stratified_with_boundaries(|_| 3.0, 0.0, 1.0, &[0.0, 0.1, 1.0], 2).unwrap(),
+            3.0
+        );
+    }
+
+    #[test]
+    fn test_stratified_variance_linear() {
+        // x is uniform within each of the k strata of width 1/k, with variance 1 / (12 k^2), so m
+        // samples per stratum give a total variance of k (1/k)^2 / (12 k^2 m) = 1 / (12 k^3 m)
+        let (estimate, variance) =
+            integrate_stratified_with_variance(|x| x, 0.0, 1.0, 4, 1000).unwrap();
+        assert_approx_eq!(estimate, 0.5, 0.01);
+        assert_approx_eq!(variance, 1.0 / (12.0 * 64.0 * 1000.0), 0.1);
+
+        // A constant has no variation within the strata
+        let (_, variance) = integrate_stratified_with_variance(|_| 3.0, 0.0, 2.0, 4, 10).unwrap();
+        assert_eq!(variance, 0.0);
+    }
+
+    #[test]
+    fn test_allocate() {
+        assert_eq!(allocate(&[1.0, 3.0], 8), vec![2, 6]);
+        assert_eq!(allocate(&[0.0, 0.0, 0.0], 7), vec![3, 2, 2]);
+        assert_eq!(allocate(&[1.0, 1.0, 2.0], 5).iter().sum::<usize>(), 5);
+        assert!(allocate(&[], 5).is_empty());
+    }
+
+    #[test]
+    fn test_stratified_constant() {
+        assert_approx_eq!(integrate_stratified(|_| 2.0, 1.0, 4.0, 8, 4).unwrap(), 6.0);
+        assert_approx_eq!(
+            integrate_stratified_optimal(|_| 2.0, 1.0, 4.0, 8, 64, 2).unwrap(),
+            6.0
+        );
+    }
+
+    #[test]
+    fn test_no_strata() {
+        let error = MonteCarloError::InvalidParameter {
+            name: "strata",
+            value: 0.0,
+        };
+        assert_eq!(
+            integrate_stratified(|x| x, 0.0, 1.0, 0, 4),
+            Err(error.clone())
+        );
+        assert_eq!(
+            integrate_stratified_with_variance(|x| x, 0.0, 1.0, 0, 4),
+            Err(error.clone())
+        );
+        assert_eq!(
+            integrate_stratified_optimal(|x| x, 0.0, 1.0, 0, 100, 4),
+            Err(error)
+        );
+    }
+
+    #[test]
+    fn test_stratification_gain_grows_for_linear() {
+        let gains: Vec<f64> = [1, 2, 4, 8]
+            .iter()
+            .map(|&strata| stratification_gain(|x| x, 0.0, 1.0, strata, 40_000).unwrap())
+            .collect();
+
+        assert_approx_eq!(gains[0], 1.0);
+        assert!(gains.windows(2).all(|w| w[0] < w[1]));
+        // For a linear integrand, the gain is the square of the number of strata
+        assert_approx_eq!(gains[3], 64.0, 0.1);
+    }
+
+    #[test]
+    fn test_stratification_gain_constant() {
+        assert_eq!(stratification_gain(|_| 1.0, 0.0, 1.0, 4, 100), Ok(1.0));
+    }
+
+    #[test]
+    fn test_stratification_gain_invalid_pilot() {
+        assert_eq!(
+            stratification_gain(|x| x, 0.0, 1.0, 0, 100),
+            Err(MonteCarloError::InvalidParameter {
+                name: "strata",
+                value: 0.0
+            })
+        );
+        assert_eq!(
+            stratification_gain(|x| x, 0.0, 1.0, 8, 10),
+            Err(MonteCarloError::InsufficientSamples {
+                required: 16,
+                provided: 10
+            })
+        );
+        assert!(stratification_gain(|x| x, 0.0, 1.0, 8, 16).is_ok());
+    }
+
+    #[test]
+    fn test_optimal_insufficient_budget() {
+        assert_eq!(
+            integrate_stratified_optimal(|x| x, 0.0, 1.0, 4, 30, 10),
+            Err(MonteCarloError::InsufficientSamples {
+                required: 40,
+                provided: 30
+            })
+        );
+    }
+
+    #[test]
+    fn test_optimal_beats_equal_allocation() {
+        let expected = 1.0 + (20.0_f64.cos() - 40.0_f64.cos()) / 40.0;
+
+        let equal =
+            estimate_variance(|| integrate_stratified(half_rough, 0.0, 1.0, 4, 100).unwrap());
+        let optimal = estimate_variance(|| {
+            integrate_stratified_optimal(half_rough, 0.0, 1.0, 4, 400, 10).unwrap()
+        });
+
+        assert_approx_eq!(equal.mean, expected, 0.01);
+        assert_approx_eq!(optimal.mean, expected, 0.01);
+        assert!(optimal.variance() < equal.variance() * 0.8);
+    }
+}
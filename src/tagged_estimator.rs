@@ -0,0 +1,92 @@
+use crate::error::{MonteCarloError, Result};
+use crate::variance_estimator::VarianceEstimator;
+
+// What the samples fed into an estimator represent. Merging estimators of different kinds
+// produces a meaningless mean and variance
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleKind {
+    // Raw integrand values f(x)
+    IntegrandValue,
+    // Scaled integral estimates, e.g. one per repeated run
+    IntegralEstimate,
+}
+
+// VarianceEstimator that remembers the kind of its samples, and refuses to merge with an
+// estimator of another kind
+#[derive(Copy, Clone, Debug)]
+pub struct TaggedEstimator {
+    kind: SampleKind,
+    estimator: VarianceEstimator,
+}
+
+impl TaggedEstimator {
+    pub fn new(kind: SampleKind) -> Self {
+        Self {
+            kind,
+            estimator: VarianceEstimator::new(),
+        }
+    }
+
+    pub fn from_estimator(kind: SampleKind, estimator: VarianceEstimator) -> Self {
+        Self { kind, estimator }
+    }
+
+    pub fn kind(&self) -> SampleKind {
+        self.kind
+    }
+
+    pub fn estimator(&self) -> &VarianceEstimator {
+        &self.estimator
+    }
+
+    pub fn add_sample(&mut self, x: f64) {
+        self.estimator.add_sample(x);
+    }
+
+    pub fn merge(lhs: Self, rhs: Self) -> Result<Self> {
+        if lhs.kind != rhs.kind {
+            return Err(MonteCarloError::KindMismatch {
+                lhs: lhs.kind,
+                rhs: rhs.kind,
+            });
+        }
+
+        Ok(Self {
+            kind: lhs.kind,
+            estimator: VarianceEstimator::merge(lhs.estimator, rhs.estimator),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_same_kind() {
+        let mut lhs = TaggedEstimator::new(SampleKind::IntegrandValue);
+        let mut rhs = TaggedEstimator::new(SampleKind::IntegrandValue);
+        (0..100).for_each(|i| lhs.add_sample(i as f64));
+        (100..200).for_each(|i| rhs.add_sample(i as f64));
+
+        let merged = TaggedEstimator::merge(lhs, rhs).unwrap();
+        assert_eq!(merged.kind(), SampleKind::IntegrandValue);
+        assert_eq!(merged.estimator().mean, 99.5);
+    }
+
+    #[test]
+    fn test_merge_mismatched_kinds() {
+        let mut values = TaggedEstimator::new(SampleKind::IntegrandValue);
+        values.add_sample(1.0);
+        let estimates =
+            TaggedEstimator::from_estimator(SampleKind::IntegralEstimate, VarianceEstimator::new());
+
+        assert_eq!(
+            TaggedEstimator::merge(values, estimates).unwrap_err(),
+            MonteCarloError::KindMismatch {
+                lhs: SampleKind::IntegrandValue,
+                rhs: SampleKind::IntegralEstimate,
+            }
+        );
+    }
+}
@@ -0,0 +1,123 @@
+// One-way ANOVA decomposition of the variance of several groups of samples, e.g. Monte Carlo runs
+// of slightly different integrands, into the spread between the groups and the Monte Carlo noise
+// within them
+use crate::variance_estimator::VarianceEstimator;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VarianceComponents {
+    // Mean of all samples of all groups together
+    pub pooled_mean: f64,
+    // Pooled within-group variance, the mean square within groups
+    pub within_variance: f64,
+    // Mean square between groups, the spread of the group means weighted by their sizes
+    pub mean_square_between: f64,
+    // Estimated variance of the true group means around the pooled mean, with the within-group
+    // noise of the group means removed. Clamped at 0, as noise can make it negative
+    pub between_variance: f64,
+    // Variance of all samples together, with the n - 1 denominator
+    pub total_variance: f64,
+}
+
+impl VarianceComponents {
+    // Decompose the variance of the samples summarized by `groups`. Needs at least two groups
+    // and more samples than groups, otherwise the corresponding components are 0
+    pub fn from_groups(groups: &[VarianceEstimator]) -> Self {
+        let pooled = VarianceEstimator::merge_pairwise(groups);
+        let total_count = pooled.sample_count() as f64;
+        let group_count = groups.len() as f64;
+
+        let within_sum: f64 = groups.iter().map(|ve| ve.moments().m2).sum();
+        let between_sum: f64 = groups
+            .iter()
+            .map(|ve| ve.sample_count() as f64 * (ve.mean - pooled.mean).powi(2))
+            .sum();
+
+        let within_variance = if total_count > group_count {
+            within_sum / (total_count - group_count)
+        } else {
+            0.0
+        };
+        let mean_square_between = if group_count > 1.0 {
+            between_sum / (group_count - 1.0)
+        } else {
+            0.0
+        };
+
+        // Effective group size, which is the common size for balanced groups
+        let square_count_sum: f64 = groups
+            .iter()
+            .map(|ve| (ve.sample_count() as f64).powi(2))
+            .sum();
+        let effective_size = if group_count > 1.0 {
+            (total_count - square_count_sum / total_count) / (group_count - 1.0)
+        } else {
+            0.0
+        };
+        let between_variance = if effective_size > 0.0 {
+            ((mean_square_between - within_variance) / effective_size).max(0.0)
+        } else {
+            0.0
+        };
+
+        Self {
+            pooled_mean: pooled.mean,
+            within_variance,
+            mean_square_between,
+            between_variance,
+            total_variance: pooled.variance(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+
+    // Groups of 100 samples at their mean plus or minus 1
+    fn groups(means: &[f64]) -> Vec<VarianceEstimator> {
+        means
+            .iter()
+            .map(|&mean| {
+                let mut ve = VarianceEstimator::new();
+                (0..100).for_each(|i| ve.add_sample(mean + if i % 2 == 0 { 1.0 } else { -1.0 }));
+                ve
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_known_components() {
+        let components = VarianceComponents::from_groups(&groups(&[0.0, 2.0, 4.0, 6.0]));
+
+        assert_approx_eq!(components.pooled_mean, 3.0);
+        // Each group has variance 100 / 99 around its own mean
+        assert_approx_eq!(components.within_variance, 100.0 / 99.0);
+        // The group means {0, 2, 4, 6} have variance 20 / 3
+        assert_approx_eq!(components.mean_square_between, 100.0 * 20.0 / 3.0);
+        assert_approx_eq!(components.between_variance, 20.0 / 3.0 - 1.0 / 99.0);
+    }
+
+    #[test]
+    fn test_sums_of_squares_add_up() {
+        let components = VarianceComponents::from_groups(&groups(&[1.0, -3.0, 0.5]));
+        let within_sum = components.within_variance * (300.0 - 3.0);
+        let between_sum = components.mean_square_between * 2.0;
+        assert_approx_eq!(within_sum + between_sum, components.total_variance * 299.0);
+    }
+
+    #[test]
+    fn test_identical_groups() {
+        let components = VarianceComponents::from_groups(&groups(&[5.0, 5.0, 5.0]));
+        assert_eq!(components.mean_square_between, 0.0);
+        assert_eq!(components.between_variance, 0.0);
+        assert_approx_eq!(components.within_variance, 1.0 * 300.0 / 297.0);
+    }
+
+    #[test]
+    fn test_single_group() {
+        let components = VarianceComponents::from_groups(&groups(&[2.0]));
+        assert_eq!(components.between_variance, 0.0);
+        assert_approx_eq!(components.within_variance, components.total_variance);
+    }
+}
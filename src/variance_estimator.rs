@@ -1,12 +1,29 @@
 // Variance estimator that uses the Welford’s algorithm
 // Code adapted from https://pbr-book.org/4ed/Utilities/Mathematical_Infrastructure#RobustVarianceEstimation
-#[derive(Copy, Clone, Debug, Default)]
+use crate::error::{MonteCarloError, Result};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VarianceEstimator {
     pub mean: f64,
     sum_square_differences: f64,
     sample_count: i64,
 }
 
+// Sample count above which the variance estimate is usually trustworthy for well-behaved
+// integrands. Heavy-tailed integrands need many more samples
+pub const RECOMMENDED_MIN_SAMPLES: usize = 30;
+
+// Sufficient statistics of a VarianceEstimator: the count, the mean, and the sum of squared
+// differences from the mean (M2)
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Moments {
+    pub count: i64,
+    pub mean: f64,
+    pub m2: f64,
+}
+
 impl VarianceEstimator {
     pub fn new() -> Self {
         Self {
@@ -24,6 +41,42 @@ impl VarianceEstimator {
         self.sum_square_differences += delta * delta2;
     }
 
+    // Reverse the Welford update for a sample previously added, e.g. to maintain statistics over a
+    // sliding window. Removal is less numerically stable than addition: it subtracts nearly equal
+    // quantities, so rounding error accumulates over many removals and the estimator should be
+    // rebuilt from the window periodically. Removing a value that was never added gives garbage,
+    // removing from an empty estimator is an error
+    pub fn remove_sample(&mut self, x: f64) -> Result<()> {
+        if self.sample_count == 0 {
+            return Err(MonteCarloError::ZeroSamples);
+        }
+        if self.sample_count == 1 {
+            *self = Self::new();
+            return Ok(());
+        }
+
+        let delta2 = x - self.mean;
+        self.sample_count -= 1;
+        self.mean -= delta2 / (self.sample_count as f64);
+        let delta = x - self.mean;
+        self.sum_square_differences = (self.sum_square_differences - delta * delta2).max(0.0);
+        Ok(())
+    }
+
+    pub fn sample_count(&self) -> i64 {
+        self.sample_count
+    }
+
+    pub fn moments(&self) -> Moments {
+        Moments {
+            count: self.sample_count,
+            mean: self.mean,
+            m2: self.sum_square_differences,
+        }
+    }
+
+    // Unbiased sample variance, dividing by n - 1. This is the one to use when estimating the
+    // variance of the population the samples are drawn from, e.g. for error bars
     pub fn variance(&self) -> f64 {
         if self.sample_count > 1 {
             self.sum_square_differences / (self.sample_count - 1) as f64
@@ -32,43 +85,201 @@ impl VarianceEstimator {
         }
     }
 
-    #[allow(dead_code)]
+    // Biased variance, dividing by n. This is the exact variance of the samples themselves, and
+    // the maximum likelihood estimate for normally distributed samples
+    pub fn variance_biased(&self) -> f64 {
+        if self.sample_count > 0 {
+            self.sum_square_differences / self.sample_count as f64
+        } else {
+            0.0
+        }
+    }
+
+    // Variance of the mean of the samples, as opposed to the variance of the samples themselves.
+    // For samples of f, multiplying it by (b - a)^2 gives the variance of the integral estimate
+    pub fn variance_of_mean(&self) -> f64 {
+        if self.sample_count > 1 {
+            self.variance() / self.sample_count as f64
+        } else {
+            0.0
+        }
+    }
+
+    // Sample standard deviation, the square root of `variance`. 0 with fewer than 2 samples
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    // Standard deviation of the mean, the square root of `variance_of_mean`. Unlike `std_error`,
+    // it returns 0 instead of None with fewer than 2 samples
+    pub fn std_dev_of_mean(&self) -> f64 {
+        self.variance_of_mean().sqrt()
+    }
+
+    // Variance divided by the mean. Uses the same sample count guard as `variance`, and also
+    // returns 0 when the mean is zero or subnormal, where the division would blow up
     pub fn relative_variance(&self) -> f64 {
-        if self.sample_count < 1 || self.mean == 0.0 {
+        if self.sample_count <= 1 || self.mean.abs() < f64::MIN_POSITIVE {
             0.0
         } else {
             self.variance() / self.mean
         }
     }
 
-    pub fn merge(lhs: Self, rhs: Self) -> Self {
-        if rhs.sample_count == 0 {
-            return lhs;
+    // Whether enough samples were accumulated for the variance to mean anything. With fewer than
+    // 2 samples `variance` returns 0, which must not be reported as a zero error. See
+    // `RECOMMENDED_MIN_SAMPLES` for a sensible `min_samples`
+    pub fn is_variance_reliable(&self, min_samples: usize) -> bool {
+        self.sample_count >= 2 && self.sample_count as usize >= min_samples
+    }
+
+    // Standard error of the mean, or None if the variance is undefined
+    pub fn std_error(&self) -> Option<f64> {
+        self.is_variance_reliable(2)
+            .then(|| (self.variance() / self.sample_count as f64).sqrt())
+    }
+
+    // Probable error of the mean, 0.6745 * std_error: the half-width of the interval around the
+    // mean that holds the true value with probability 1/2 under the normal approximation. Some
+    // older texts report it instead of the standard error. 0 with fewer than 2 samples
+    pub fn probable_error(&self) -> f64 {
+        0.6745 * self.std_error().unwrap_or(0.0)
+    }
+
+    // Coefficient of variation of the mean, `std_error / |mean|`, a scale-free stopping criterion.
+    // None if the variance is undefined, or if the mean is zero or subnormal. A mean that is small
+    // but not zero, like that of an integral close to 0, gives a large CoV, which is the honest
+    // answer: such an estimate has no relative precision
+    pub fn coefficient_of_variation(&self) -> Option<f64> {
+        if self.mean.abs() < f64::MIN_POSITIVE {
+            return None;
+        }
+        self.std_error()
+            .map(|std_error| std_error / self.mean.abs())
+    }
+
+    // Student's t-statistic (mean - hypothesized_mean) / std_error, for testing whether the samples
+    // are consistent with a hypothesized mean, e.g. a reference value of an integral. Compare it
+    // against the t-distribution with `degrees_of_freedom`, or the normal distribution for large
+    // counts. NaN if the variance is undefined
+    pub fn t_statistic(&self, hypothesized_mean: f64) -> f64 {
+        match self.std_error() {
+            Some(std_error) => (self.mean - hypothesized_mean) / std_error,
+            None => f64::NAN,
         }
+    }
+
+    // Degrees of freedom of the variance estimate, one less than the sample count
+    pub fn degrees_of_freedom(&self) -> i64 {
+        (self.sample_count - 1).max(0)
+    }
+
+    // Normal-approximation confidence interval `mean ± z * std_error`, e.g. z = 1.96 for 95%.
+    // None if the variance is undefined
+    pub fn confidence_interval(&self, z: f64) -> Option<(f64, f64)> {
+        self.std_error()
+            .map(|std_error| (self.mean - z * std_error, self.mean + z * std_error))
+    }
 
-        let left_sample_count_f64 = lhs.sample_count as f64;
-        let right_sample_count_f64 = rhs.sample_count as f64;
-        let sample_count = lhs.sample_count + rhs.sample_count;
+    // Combine the estimators of two disjoint sets of samples. The update is applied to the larger
+    // one as a correction weighted by the fraction of samples in the smaller one: that fraction is
+    // at most 1/2, so nothing overflows for huge counts, and a tiny estimator barely perturbs a
+    // large one. The result does not depend on the argument order beyond rounding
+    pub fn merge(lhs: Self, rhs: Self) -> Self {
+        let (large, small) = if rhs.sample_count > lhs.sample_count {
+            (rhs, lhs)
+        } else {
+            (lhs, rhs)
+        };
+        if small.sample_count == 0 {
+            return large;
+        }
 
-        let sqr_mean_diff = (rhs.mean - lhs.mean) * (rhs.mean - lhs.mean);
-        let sum_square_differences = lhs.sum_square_differences
-            + rhs.sum_square_differences
-            + sqr_mean_diff * left_sample_count_f64 * right_sample_count_f64
-                / (sample_count as f64);
-        let mean = (left_sample_count_f64 * lhs.mean + right_sample_count_f64 * rhs.mean)
-            / (sample_count as f64);
+        let sample_count = large.sample_count + small.sample_count;
+        let small_fraction = small.sample_count as f64 / sample_count as f64;
+        let delta = small.mean - large.mean;
+        let sum_square_differences = large.sum_square_differences
+            + small.sum_square_differences
+            + delta * delta * small_fraction * large.sample_count as f64;
 
         Self {
-            mean,
+            mean: large.mean + delta * small_fraction,
             sum_square_differences,
             sample_count,
         }
     }
+
+    // Merge in place a batch summarized by its mean, unbiased `variance` (as returned by
+    // `variance`), and sample count, e.g. statistics received in compact form over the wire. The
+    // batch's M2 is reconstructed as variance * (count - 1), so a batch of one sample only needs
+    // its value as the mean
+    pub fn merge_batch(&mut self, mean: f64, variance: f64, count: u64) {
+        let batch = Self {
+            mean,
+            sum_square_differences: variance * count.saturating_sub(1) as f64,
+            sample_count: count as i64,
+        };
+        *self = Self::merge(*self, batch);
+    }
+
+    // Fold estimators into one as they arrive, e.g. from an mpsc receiver, without collecting
+    // them first. Each merge folds into the running total, so rounding error grows linearly with
+    // the number of estimators; when many small estimators are available at once, `merge_pairwise`
+    // is more accurate
+    pub fn merge_from_iter(iter: impl Iterator<Item = Self>) -> Self {
+        iter.fold(Self::new(), Self::merge)
+    }
+
+    // Merge estimators as a balanced binary tree, whose rounding error only grows with the
+    // logarithm of their number
+    pub fn merge_pairwise(estimators: &[Self]) -> Self {
+        match estimators {
+            [] => Self::new(),
+            [single] => *single,
+            _ => {
+                let (lhs, rhs) = estimators.split_at(estimators.len() / 2);
+                Self::merge(Self::merge_pairwise(lhs), Self::merge_pairwise(rhs))
+            }
+        }
+    }
+}
+
+// Two estimators compare equal when they have the same sample count and approximately the same
+// mean and variance
+#[cfg(feature = "approx")]
+impl approx::AbsDiffEq for VarianceEstimator {
+    type Epsilon = f64;
+
+    fn default_epsilon() -> f64 {
+        f64::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: f64) -> bool {
+        self.sample_count == other.sample_count
+            && self.mean.abs_diff_eq(&other.mean, epsilon)
+            && self.variance().abs_diff_eq(&other.variance(), epsilon)
+    }
+}
+
+#[cfg(feature = "approx")]
+impl approx::RelativeEq for VarianceEstimator {
+    fn default_max_relative() -> f64 {
+        f64::default_max_relative()
+    }
+
+    fn relative_eq(&self, other: &Self, epsilon: f64, max_relative: f64) -> bool {
+        self.sample_count == other.sample_count
+            && self.mean.relative_eq(&other.mean, epsilon, max_relative)
+            && self
+                .variance()
+                .relative_eq(&other.variance(), epsilon, max_relative)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::integration::sample_integrand_seeded;
     use approx_eq::assert_approx_eq;
     use rayon::prelude::*;
 
@@ -82,6 +293,34 @@ mod tests {
         assert_eq!(ve.relative_variance(), 0.0);
     }
 
+    #[test]
+    fn test_no_samples() {
+        let ve = VarianceEstimator::new();
+        assert_eq!(ve.mean, 0.0);
+        assert_eq!(ve.variance(), 0.0);
+        assert_eq!(ve.variance_biased(), 0.0);
+        assert_eq!(ve.variance_of_mean(), 0.0);
+        assert_eq!(ve.relative_variance(), 0.0);
+    }
+
+    #[test]
+    fn test_single_sample() {
+        let mut ve = VarianceEstimator::new();
+        ve.add_sample(3.0);
+        assert_eq!(ve.mean, 3.0);
+        assert_eq!(ve.variance(), 0.0);
+        assert_eq!(ve.relative_variance(), 0.0);
+    }
+
+    #[test]
+    fn test_subnormal_mean() {
+        let mut ve = VarianceEstimator::new();
+        ve.add_sample(f64::MIN_POSITIVE / 4.0);
+        ve.add_sample(-f64::MIN_POSITIVE / 8.0);
+        assert!(ve.mean.abs() < f64::MIN_POSITIVE);
+        assert_eq!(ve.relative_variance(), 0.0);
+    }
+
     #[test]
     fn test_range() {
         let mut ve = VarianceEstimator::new();
@@ -91,6 +330,114 @@ mod tests {
         assert_eq!(ve.mean, 49.5);
         assert_approx_eq!(ve.variance(), 841.67, 0.01);
         assert_approx_eq!(ve.relative_variance(), 841.67 / ve.mean, 0.01);
+        assert_approx_eq!(ve.variance_of_mean(), ve.variance() / 100.0);
+        assert_eq!(ve.variance_biased(), ve.sum_square_differences / 100.0);
+        assert_approx_eq!(ve.variance_biased(), 833.25);
+    }
+
+    #[test]
+    fn test_moments() {
+        let mut ve = VarianceEstimator::new();
+        (0..100).for_each(|i| ve.add_sample(i as f64));
+
+        let moments = ve.moments();
+        assert_eq!(moments.count, ve.sample_count());
+        assert_eq!(moments.mean, ve.mean);
+        assert_approx_eq!(moments.m2 / (moments.count - 1) as f64, ve.variance());
+    }
+
+    #[test]
+    fn test_variance_reliability() {
+        let mut ve = VarianceEstimator::new();
+        assert!(!ve.is_variance_reliable(0));
+        assert_eq!(ve.std_error(), None);
+
+        ve.add_sample(1.0);
+        assert!(!ve.is_variance_reliable(1));
+        assert_eq!(ve.std_error(), None);
+        assert_eq!(ve.confidence_interval(1.96), None);
+
+        (1..RECOMMENDED_MIN_SAMPLES).for_each(|i| ve.add_sample(i as f64));
+        assert!(ve.is_variance_reliable(2));
+        assert!(ve.is_variance_reliable(RECOMMENDED_MIN_SAMPLES));
+        assert!(!ve.is_variance_reliable(RECOMMENDED_MIN_SAMPLES + 1));
+    }
+
+    #[test]
+    fn test_std_error() {
+        let mut ve = VarianceEstimator::new();
+        (0..100).for_each(|i| ve.add_sample(i as f64));
+
+        let std_error = ve.std_error().unwrap();
+        assert_approx_eq!(std_error, (841.67_f64 / 100.0).sqrt(), 0.01);
+
+        let (lo, hi) = ve.confidence_interval(2.0).unwrap();
+        assert_approx_eq!(lo, 49.5 - 2.0 * std_error);
+        assert_approx_eq!(hi, 49.5 + 2.0 * std_error);
+    }
+
+    #[test]
+    fn test_std_dev() {
+        let mut ve = VarianceEstimator::new();
+        assert_eq!(ve.std_dev(), 0.0);
+        ve.add_sample(5.0);
+        assert_eq!(ve.std_dev(), 0.0);
+        assert_eq!(ve.std_dev_of_mean(), 0.0);
+
+        // The sum of squared differences of 0..100 is 100 (100^2 - 1) / 12 = 83325
+        let mut ve = VarianceEstimator::new();
+        (0..100).for_each(|i| ve.add_sample(i as f64));
+        assert_approx_eq!(ve.std_dev(), 29.011_491, 1e-6);
+        assert_approx_eq!(ve.std_dev_of_mean(), 2.901_149, 1e-6);
+    }
+
+    #[test]
+    fn test_probable_error() {
+        // 2, 4, 4, 4, 5, 5, 7, 9 has mean 5 and sum of squared differences 32, so the standard
+        // error is sqrt(32 / 7 / 8) = 0.7559
+        let mut ve = VarianceEstimator::new();
+        [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+            .iter()
+            .for_each(|&x| ve.add_sample(x));
+        assert_approx_eq!(ve.probable_error(), 0.6745 * (32.0_f64 / 56.0).sqrt());
+        assert_approx_eq!(ve.probable_error(), 0.509_88, 1e-4);
+
+        let mut ve = VarianceEstimator::new();
+        assert_eq!(ve.probable_error(), 0.0);
+        ve.add_sample(1.0);
+        assert_eq!(ve.probable_error(), 0.0);
+    }
+
+    #[test]
+    fn test_t_statistic() {
+        // Samples of x uniform on [0, 1], whose mean is 1/2
+        let ve = sample_integrand_seeded(|x| x, 0.0, 1.0, 100_000, 3).unwrap();
+        assert_eq!(ve.degrees_of_freedom(), 99_999);
+        assert!(ve.t_statistic(0.5).abs() < 4.0);
+        // The standard error is about 0.29 / sqrt(100000) = 0.0009, so 0.51 is 11 errors away
+        assert!(ve.t_statistic(0.51) < -8.0);
+        assert!(ve.t_statistic(0.49) > 8.0);
+
+        let mut ve = VarianceEstimator::new();
+        ve.add_sample(1.0);
+        assert!(ve.t_statistic(0.0).is_nan());
+        assert_eq!(VarianceEstimator::new().degrees_of_freedom(), 0);
+    }
+
+    #[test]
+    fn test_coefficient_of_variation() {
+        let mut ve = VarianceEstimator::new();
+        (0..100).for_each(|i| ve.add_sample(-(i as f64)));
+        assert_approx_eq!(
+            ve.coefficient_of_variation().unwrap(),
+            ve.std_error().unwrap() / 49.5
+        );
+
+        let mut ve = VarianceEstimator::new();
+        ve.add_sample(1.0);
+        assert_eq!(ve.coefficient_of_variation(), None);
+        [-1.0, 0.0].iter().for_each(|&x| ve.add_sample(x));
+        assert_eq!(ve.coefficient_of_variation(), None);
     }
 
     #[test]
@@ -109,6 +456,210 @@ mod tests {
         assert_approx_eq!(ve.relative_variance(), 3350.0 / ve.mean, 0.01);
     }
 
+    #[test]
+    fn test_merge_lopsided_counts() {
+        let values: Vec<f64> = (0..100_001)
+            .map(|i| (i as f64 * 0.37).sin() + 1e6)
+            .collect();
+        let mut large = VarianceEstimator::new();
+        values[..100_000].iter().for_each(|&x| large.add_sample(x));
+        let mut small = VarianceEstimator::new();
+        small.add_sample(values[100_000]);
+
+        let mut sequential = large;
+        sequential.add_sample(values[100_000]);
+        for merged in [
+            VarianceEstimator::merge(large, small),
+            VarianceEstimator::merge(small, large),
+        ] {
+            assert_eq!(merged.sample_count(), 100_001);
+            assert_approx_eq!(merged.mean, sequential.mean, 1e-15);
+            assert_approx_eq!(merged.variance(), sequential.variance(), 1e-12);
+            assert_approx_eq!(merged.variance(), two_pass_variance(&values), 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_merge_huge_counts() {
+        // n_l n_r (mean difference)^2 overflows even though the merged M2 does not
+        let estimator = |mean| VarianceEstimator {
+            mean,
+            sum_square_differences: 0.0,
+            sample_count: 3_000_000_000_000_000_000,
+        };
+        let merged = VarianceEstimator::merge(estimator(0.0), estimator(1e145));
+        assert_eq!(merged.sample_count(), 6_000_000_000_000_000_000);
+        assert_approx_eq!(merged.mean, 0.5e145);
+        assert!(merged.sum_square_differences.is_finite());
+        assert_approx_eq!(merged.sum_square_differences, 1.5e308);
+    }
+
+    #[test]
+    fn test_merge_order_insensitive() {
+        let estimators: Vec<VarianceEstimator> = (1..200)
+            .map(|i| {
+                let mut ve = VarianceEstimator::new();
+                (0..i * 7 % 53 + 1).for_each(|j| ve.add_sample(((i * j) as f64).cos() * i as f64));
+                ve
+            })
+            .collect();
+
+        let left = estimators
+            .iter()
+            .fold(VarianceEstimator::new(), |acc, &ve| {
+                VarianceEstimator::merge(acc, ve)
+            });
+        let right = estimators
+            .iter()
+            .rev()
+            .fold(VarianceEstimator::new(), |acc, &ve| {
+                VarianceEstimator::merge(ve, acc)
+            });
+        let pairwise = VarianceEstimator::merge_pairwise(&estimators);
+
+        for other in [right, pairwise] {
+            assert_eq!(left.sample_count(), other.sample_count());
+            assert_approx_eq!(left.mean, other.mean, 1e-12);
+            assert_approx_eq!(left.variance(), other.variance(), 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_add_then_remove() {
+        let mut ve = VarianceEstimator::new();
+        (0..100).for_each(|i| ve.add_sample((i as f64).sin() * 10.0 + 3.0));
+        let before = ve;
+
+        ve.add_sample(123.456);
+        ve.remove_sample(123.456).unwrap();
+
+        assert_eq!(ve.sample_count(), before.sample_count());
+        assert_approx_eq!(ve.mean, before.mean, 1e-12);
+        assert_approx_eq!(ve.variance(), before.variance(), 1e-12);
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        const WINDOW: usize = 10;
+        let data: Vec<f64> = (0..200).map(|i| ((i * 37) % 101) as f64).collect();
+        let mut ve = VarianceEstimator::new();
+        for (i, &x) in data.iter().enumerate() {
+            ve.add_sample(x);
+            if i >= WINDOW {
+                ve.remove_sample(data[i - WINDOW]).unwrap();
+            }
+        }
+
+        let window = &data[data.len() - WINDOW..];
+        assert_eq!(ve.sample_count(), WINDOW as i64);
+        assert_approx_eq!(ve.mean, window.iter().sum::<f64>() / WINDOW as f64, 1e-9);
+        assert_approx_eq!(ve.variance(), two_pass_variance(window), 1e-9);
+    }
+
+    #[test]
+    fn test_remove_last_sample() {
+        let mut ve = VarianceEstimator::new();
+        ve.add_sample(5.0);
+        ve.remove_sample(5.0).unwrap();
+        assert_eq!(ve, VarianceEstimator::new());
+        assert_eq!(ve.remove_sample(5.0), Err(MonteCarloError::ZeroSamples));
+    }
+
+    #[test]
+    fn test_merge_from_channel() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let estimators: Vec<VarianceEstimator> = (0..100)
+            .map(|i| {
+                let mut ve = VarianceEstimator::new();
+                (0..10).for_each(|j| ve.add_sample((i * 10 + j) as f64));
+                ve
+            })
+            .collect();
+
+        let producer = {
+            let estimators = estimators.clone();
+            std::thread::spawn(move || {
+                estimators
+                    .into_iter()
+                    .for_each(|ve| sender.send(ve).unwrap())
+            })
+        };
+        let streamed = VarianceEstimator::merge_from_iter(receiver.into_iter());
+        producer.join().unwrap();
+
+        let batch = VarianceEstimator::merge_pairwise(&estimators);
+        assert_eq!(streamed.sample_count(), 1000);
+        assert_eq!(batch.sample_count(), 1000);
+        assert_approx_eq!(streamed.mean, batch.mean, 1e-12);
+        assert_approx_eq!(streamed.variance(), batch.variance(), 1e-12);
+        assert_approx_eq!(batch.mean, 499.5);
+    }
+
+    #[test]
+    fn test_merge_batch_matches_merge() {
+        let mut lhs = VarianceEstimator::new();
+        let mut rhs = VarianceEstimator::new();
+        (0..100).for_each(|i| lhs.add_sample((i as f64 * 0.3).sin()));
+        (0..37).for_each(|i| rhs.add_sample((i as f64).sqrt()));
+
+        let mut batched = lhs;
+        batched.merge_batch(rhs.mean, rhs.variance(), rhs.sample_count() as u64);
+        let merged = VarianceEstimator::merge(lhs, rhs);
+        assert_eq!(batched.sample_count(), merged.sample_count());
+        assert_approx_eq!(batched.mean, merged.mean, 1e-15);
+        assert_approx_eq!(batched.variance(), merged.variance(), 1e-12);
+
+        // A single value, and an empty batch
+        let mut single = lhs;
+        single.merge_batch(2.5, 0.0, 1);
+        let mut expected = lhs;
+        expected.add_sample(2.5);
+        assert_approx_eq!(single.variance(), expected.variance(), 1e-12);
+        single.merge_batch(100.0, 5.0, 0);
+        assert_eq!(single.sample_count(), 101);
+    }
+
+    #[test]
+    fn test_merge_empty() {
+        assert_eq!(
+            VarianceEstimator::merge_from_iter(std::iter::empty()),
+            VarianceEstimator::new()
+        );
+        assert_eq!(
+            VarianceEstimator::merge_pairwise(&[]),
+            VarianceEstimator::new()
+        );
+    }
+
+    // Reference variance computed by first finding the mean, then summing squared differences
+    fn two_pass_variance(data: &[f64]) -> f64 {
+        let mean = data.iter().sum::<f64>() / data.len() as f64;
+        data.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (data.len() - 1) as f64
+    }
+
+    #[test]
+    fn test_matches_two_pass() {
+        let datasets: Vec<Vec<f64>> = vec![
+            (0..100).map(|i| i as f64).collect(),
+            // Near-constant data with a large offset, where the naive sum of squares formula
+            // loses every significant digit
+            (0..1000).map(|i| 1e6 + ((i % 7) as f64) * 1e-3).collect(),
+            // Values spanning many orders of magnitude, with both signs
+            (0..500)
+                .map(|i| (if i % 2 == 0 { 1.0 } else { -1.0 }) * 10f64.powi(i % 13 - 6))
+                .collect(),
+            (0..2000)
+                .map(|i| ((i * 37) % 101) as f64 * 0.5 - 20.0)
+                .collect(),
+        ];
+
+        for data in &datasets {
+            let mut ve = VarianceEstimator::new();
+            data.iter().for_each(|&x| ve.add_sample(x));
+            assert_approx_eq!(ve.variance(), two_pass_variance(data), 1e-8);
+        }
+    }
+
     #[test]
     fn test_concurrent_accumulate() {
         // An integer sequence from 0 to 10000 has an variance around 8334166.67
@@ -122,4 +673,35 @@ mod tests {
 
         assert_approx_eq!(ve.variance(), 8334166.67, 0.01);
     }
+
+    #[cfg(feature = "approx")]
+    #[test]
+    fn test_approx_eq() {
+        use approx::{assert_relative_eq, assert_relative_ne};
+
+        let mut ve1 = VarianceEstimator::new();
+        (0..100).for_each(|i| ve1.add_sample(i as f64));
+
+        let mut lhs = VarianceEstimator::new();
+        let mut rhs = VarianceEstimator::new();
+        (0..37).for_each(|i| lhs.add_sample(i as f64));
+        (37..100).rev().for_each(|i| rhs.add_sample(i as f64));
+        let ve2 = VarianceEstimator::merge(lhs, rhs);
+
+        assert_relative_eq!(ve1, ve2, max_relative = 1e-12);
+        assert_relative_ne!(ve1, lhs);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut ve = VarianceEstimator::new();
+        (0..100).for_each(|i| ve.add_sample(i as f64));
+
+        let json = serde_json::to_string(&ve).unwrap();
+        assert_eq!(
+            serde_json::from_str::<VarianceEstimator>(&json).unwrap(),
+            ve
+        );
+    }
 }
@@ -0,0 +1,425 @@
+// Simplified VEGAS adaptive integrator
+// G. P. Lepage, "A new algorithm for adaptive multidimensional integration", 1978
+use crate::error::{check_bounds, MonteCarloError, Result};
+use crate::mapping::{interval_jacobian, map_unit_to_interval};
+use crate::variance_estimator::VarianceEstimator;
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+
+// Number of bins of the importance grid along each axis
+const BIN_COUNT: usize = 50;
+
+// Damping exponent of the grid refinement. Smaller values adapt more slowly but more stably
+const DAMPING: f64 = 1.5;
+
+// Separable importance map: for each axis, the edges of BIN_COUNT bins partitioning [0, 1].
+// Each bin receives the same share of samples, so narrow bins concentrate samples. With the
+// `serde` feature, an adapted grid can be saved and reused by `integrate_vegas_warm`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VegasGrid {
+    edges: Vec<Vec<f64>>,
+}
+
+impl VegasGrid {
+    pub fn uniform(dimension: usize) -> Self {
+        let axis: Vec<f64> = (0..=BIN_COUNT)
+            .map(|i| i as f64 / BIN_COUNT as f64)
+            .collect();
+        Self {
+            edges: vec![axis; dimension],
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.edges.len()
+    }
+
+    // Bin edges along `axis`, from 0 to 1
+    pub fn edges(&self, axis: usize) -> &[f64] {
+        &self.edges[axis]
+    }
+
+    // Check that every axis has BIN_COUNT bins with ordered edges from 0 to 1, as a grid loaded
+    // from elsewhere might not
+    fn validate(&self) -> Result<()> {
+        for edges in &self.edges {
+            if edges.len() != BIN_COUNT + 1 {
+                return Err(MonteCarloError::DimensionMismatch {
+                    expected: BIN_COUNT + 1,
+                    actual: edges.len(),
+                });
+            }
+            let (first, last) = (edges[0], edges[BIN_COUNT]);
+            if first != 0.0 || last != 1.0 {
+                return Err(MonteCarloError::InvalidBounds { a: first, b: last });
+            }
+            if let Some(w) = edges
+                .windows(2)
+                .find(|w| w[0].partial_cmp(&w[1]) != Some(Ordering::Less))
+            {
+                return Err(MonteCarloError::InvalidBounds { a: w[0], b: w[1] });
+            }
+        }
+        Ok(())
+    }
+
+    // Map uniform coordinates of the unit hypercube to the grid's sampling density. Writes the
+    // image into `y` and returns the Jacobian of the mapping, along with the bin of each axis
+    fn map(&self, u: &[f64], y: &mut [f64], bins: &mut [usize]) -> f64 {
+        let mut jacobian = 1.0;
+        for (axis, edges) in self.edges.iter().enumerate() {
+            let position = u[axis] * BIN_COUNT as f64;
+            let bin = (position as usize).min(BIN_COUNT - 1);
+            let width = edges[bin + 1] - edges[bin];
+            y[axis] = edges[bin] + (position - bin as f64) * width;
+            bins[axis] = bin;
+            jacobian *= BIN_COUNT as f64 * width;
+        }
+        jacobian
+    }
+
+    // Move the bin edges so that each bin holds the same share of the damped importance
+    // computed from the per-bin sums of squared weighted values
+    fn refine(&mut self, bin_sums: &[f64]) {
+        for (axis, edges) in self.edges.iter_mut().enumerate() {
+            let sums = &bin_sums[axis * BIN_COUNT..(axis + 1) * BIN_COUNT];
+
+            // Smooth neighbouring bins to avoid chasing noise
+            let smoothed: Vec<f64> = (0..BIN_COUNT)
+                .map(|i| {
+                    let lo = i.saturating_sub(1);
+                    let hi = (i + 1).min(BIN_COUNT - 1);
+                    sums[lo..=hi].iter().sum::<f64>() / ((hi - lo + 1) as f64)
+                })
+                .collect();
+            let total: f64 = smoothed.iter().sum();
+            if total <= 0.0 || !total.is_finite() {
+                continue;
+            }
+
+            let importance: Vec<f64> = smoothed
+                .iter()
+                .map(|&d| {
+                    let r = d / total;
+                    if r > 0.0 && r < 1.0 {
+                        ((r - 1.0) / r.ln()).powf(DAMPING)
+                    } else {
+                        r
+                    }
+                })
+                .collect();
+            let per_bin = importance.iter().sum::<f64>() / BIN_COUNT as f64;
+            if per_bin <= 0.0 {
+                continue;
+            }
+
+            let mut new_edges = vec![0.0; BIN_COUNT + 1];
+            let mut bin = 0;
+            let mut accumulated = 0.0;
+            for (i, new_edge) in new_edges.iter_mut().enumerate().take(BIN_COUNT).skip(1) {
+                let target = per_bin * i as f64;
+                while bin < BIN_COUNT - 1 && accumulated + importance[bin] < target {
+                    accumulated += importance[bin];
+                    bin += 1;
+                }
+                // Bins of zero importance, where f vanished, get no share: an edge landing on one
+                // goes to its start rather than dividing 0 by 0
+                let fraction = if importance[bin] > 0.0 {
+                    ((target - accumulated) / importance[bin]).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                *new_edge = edges[bin] + fraction * (edges[bin + 1] - edges[bin]);
+            }
+            new_edges[BIN_COUNT] = 1.0;
+            *edges = new_edges;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VegasEstimate {
+    // Inverse-variance weighted combination of the per-iteration estimates
+    pub estimate: f64,
+    // Variance of `estimate`
+    pub variance: f64,
+    // Grid adapted over all the iterations
+    pub grid: VegasGrid,
+}
+
+fn run_iteration(
+    f: &(impl Fn(&[f64]) -> f64 + Sync),
+    lower: &[f64],
+    upper: &[f64],
+    grid: &VegasGrid,
+    sample_count: usize,
+) -> (VarianceEstimator, Vec<f64>) {
+    let dimension = grid.dimension();
+    let volume: f64 = lower
+        .iter()
+        .zip(upper)
+        .map(|(&lo, &hi)| interval_jacobian(lo, hi))
+        .product();
+    let identity = || (VarianceEstimator::new(), vec![0.0; dimension * BIN_COUNT]);
+
+    (0..sample_count)
+        .into_par_iter()
+        .fold_with(identity(), |(mut ve, mut bin_sums), _| {
+            let mut rng = thread_rng();
+            let u: Vec<f64> = (0..dimension).map(|_| rng.gen::<f64>()).collect();
+            let mut y = vec![0.0; dimension];
+            let mut bins = vec![0; dimension];
+            let jacobian = grid.map(&u, &mut y, &mut bins);
+
+            let x: Vec<f64> = (0..dimension)
+                .map(|axis| map_unit_to_interval(y[axis], lower[axis], upper[axis]))
+                .collect();
+            let value = f(&x) * jacobian * volume;
+
+            ve.add_sample(value);
+            bins.iter()
+                .enumerate()
+                .for_each(|(axis, &bin)| bin_sums[axis * BIN_COUNT + bin] += value * value);
+            (ve, bin_sums)
+        })
+        .reduce(identity, |(lhs_ve, mut lhs_sums), (rhs_ve, rhs_sums)| {
+            lhs_sums
+                .iter_mut()
+                .zip(rhs_sums)
+                .for_each(|(lhs, rhs)| *lhs += rhs);
+            (VarianceEstimator::merge(lhs_ve, rhs_ve), lhs_sums)
+        })
+}
+
+// Estimate the integral of f over the box [lower, upper] with VEGAS. Every iteration draws
+// `samples_per_iteration` points from the current separable importance grid, and then refines the
+// grid along each axis to concentrate samples where |f| is large
+pub fn integrate_vegas(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    lower: &[f64],
+    upper: &[f64],
+    iterations: usize,
+    samples_per_iteration: usize,
+) -> Result<VegasEstimate> {
+    validate_arguments(lower, upper, iterations, samples_per_iteration)?;
+    run_vegas(
+        &f,
+        lower,
+        upper,
+        VegasGrid::uniform(lower.len()),
+        iterations,
+        samples_per_iteration,
+    )
+}
+
+// Same as `integrate_vegas`, but starts from a previously adapted `grid` instead of a uniform one,
+// e.g. from a related integrand or an earlier run. A good grid makes the early iterations as
+// precise as the late ones, instead of spending them on adaptation
+pub fn integrate_vegas_warm(
+    f: impl Fn(&[f64]) -> f64 + Sync,
+    grid: &VegasGrid,
+    lower: &[f64],
+    upper: &[f64],
+    iterations: usize,
+    samples_per_iteration: usize,
+) -> Result<VegasEstimate> {
+    validate_arguments(lower, upper, iterations, samples_per_iteration)?;
+    if grid.dimension() != lower.len() {
+        return Err(MonteCarloError::DimensionMismatch {
+            expected: lower.len(),
+            actual: grid.dimension(),
+        });
+    }
+    grid.validate()?;
+
+    run_vegas(
+        &f,
+        lower,
+        upper,
+        grid.clone(),
+        iterations,
+        samples_per_iteration,
+    )
+}
+
+fn validate_arguments(
+    lower: &[f64],
+    upper: &[f64],
+    iterations: usize,
+    samples_per_iteration: usize,
+) -> Result<()> {
+    if lower.len() != upper.len() {
+        return Err(MonteCarloError::DimensionMismatch {
+            expected: lower.len(),
+            actual: upper.len(),
+        });
+    }
+    for (&lo, &hi) in lower.iter().zip(upper) {
+        check_bounds(lo, hi)?;
+    }
+    if iterations == 0 || samples_per_iteration < 2 {
+        return Err(MonteCarloError::ZeroSamples);
+    }
+    Ok(())
+}
+
+fn run_vegas(
+    f: &(impl Fn(&[f64]) -> f64 + Sync),
+    lower: &[f64],
+    upper: &[f64],
+    mut grid: VegasGrid,
+    iterations: usize,
+    samples_per_iteration: usize,
+) -> Result<VegasEstimate> {
+    let mut weighted_sum = 0.0;
+    let mut weight_sum = 0.0;
+
+    for _ in 0..iterations {
+        let (ve, bin_sums) = run_iteration(f, lower, upper, &grid, samples_per_iteration);
+        let variance = ve.variance() / (ve.sample_count() as f64);
+        if variance > 0.0 {
+            weighted_sum += ve.mean / variance;
+            weight_sum += 1.0 / variance;
+        } else {
+            // An exact iteration makes every other one irrelevant
+            return Ok(VegasEstimate {
+                estimate: ve.mean,
+                variance: 0.0,
+                grid,
+            });
+        }
+        grid.refine(&bin_sums);
+    }
+
+    Ok(VegasEstimate {
+        estimate: weighted_sum / weight_sum,
+        variance: 1.0 / weight_sum,
+        grid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx_eq::assert_approx_eq;
+    use std::f64::consts::PI;
+
+    const SIGMA: f64 = 0.05;
+
+    fn peak(x: &[f64]) -> f64 {
+        let r2 = (x[0] - 0.5).powi(2) + (x[1] - 0.5).powi(2);
+        (-r2 / (2.0 * SIGMA * SIGMA)).exp()
+    }
+
+    #[test]
+    fn test_vegas_constant() {
+        let result = integrate_vegas(|_| 2.0, &[0.0, 1.0], &[2.0, 4.0], 3, 1000).unwrap();
+        assert_approx_eq!(result.estimate, 12.0);
+    }
+
+    #[test]
+    fn test_vegas_dimension_mismatch() {
+        assert_eq!(
+            integrate_vegas(|_| 1.0, &[0.0, 0.0], &[1.0], 3, 1000).unwrap_err(),
+            MonteCarloError::DimensionMismatch {
+                expected: 2,
+                actual: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_grid_refine_concentrates_bins() {
+        let mut grid = VegasGrid::uniform(1);
+        let mut bin_sums = vec![0.0; BIN_COUNT];
+        bin_sums[BIN_COUNT / 2] = 1.0;
+        grid.refine(&bin_sums);
+
+        let edges = grid.edges(0);
+        assert_eq!(edges[0], 0.0);
+        assert_eq!(edges[BIN_COUNT], 1.0);
+        assert!(edges.windows(2).all(|w| w[0] < w[1]));
+        // Most bins end up around the important one, near 0.5
+        let center = (edges[BIN_COUNT / 2 + 1] - edges[BIN_COUNT / 2]) * BIN_COUNT as f64;
+        assert!(center < 1.0);
+    }
+
+    #[test]
+    fn test_warm_start_first_iteration() {
+        const SAMPLES: usize = 10_000;
+        let trained = integrate_vegas(peak, &[0.0, 0.0], &[1.0, 1.0], 10, SAMPLES).unwrap();
+
+        let cold = integrate_vegas(peak, &[0.0, 0.0], &[1.0, 1.0], 1, SAMPLES).unwrap();
+        let warm = integrate_vegas_warm(peak, &trained.grid, &[0.0, 0.0], &[1.0, 1.0], 1, SAMPLES)
+            .unwrap();
+
+        assert_approx_eq!(warm.estimate, 2.0 * PI * SIGMA * SIGMA, 0.02);
+        assert!(warm.variance * 20.0 < cold.variance);
+    }
+
+    #[test]
+    fn test_warm_start_rejects_invalid_grid() {
+        let grid = VegasGrid::uniform(2);
+        assert_eq!(
+            integrate_vegas_warm(peak, &grid, &[0.0], &[1.0], 1, 100).unwrap_err(),
+            MonteCarloError::DimensionMismatch {
+                expected: 1,
+                actual: 2
+            }
+        );
+
+        let mut unordered = VegasGrid::uniform(1);
+        unordered.edges[0].swap(3, 4);
+        assert!(integrate_vegas_warm(|x| x[0], &unordered, &[0.0], &[1.0], 1, 100).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_grid_serde_round_trip() {
+        let trained = integrate_vegas(peak, &[0.0, 0.0], &[1.0, 1.0], 3, 1000).unwrap();
+        let json = serde_json::to_string(&trained.grid).unwrap();
+        assert_eq!(
+            serde_json::from_str::<VegasGrid>(&json).unwrap(),
+            trained.grid
+        );
+    }
+
+    #[test]
+    fn test_vegas_peaked_2d() {
+        const ITERATIONS: usize = 10;
+        const SAMPLES: usize = 10_000;
+        let expected = 2.0 * PI * SIGMA * SIGMA;
+
+        let vegas = integrate_vegas(peak, &[0.0, 0.0], &[1.0, 1.0], ITERATIONS, SAMPLES).unwrap();
+
+        let mut rng = thread_rng();
+        let mut uniform = VarianceEstimator::new();
+        (0..ITERATIONS * SAMPLES).for_each(|_| uniform.add_sample(peak(&[rng.gen(), rng.gen()])));
+        let uniform_variance = uniform.variance() / (uniform.sample_count() as f64);
+
+        assert_approx_eq!(vegas.estimate, expected, 0.01);
+        assert!((vegas.estimate - expected).abs() < 5.0 * vegas.variance.sqrt());
+        assert!(vegas.variance * 50.0 < uniform_variance);
+    }
+
+    #[test]
+    fn test_integrand_zero_on_part_of_domain() {
+        let mut grid = VegasGrid::uniform(1);
+        let mut bin_sums = vec![0.0; BIN_COUNT];
+        bin_sums[BIN_COUNT / 2..]
+            .iter_mut()
+            .for_each(|sum| *sum = 1.0);
+        grid.refine(&bin_sums);
+        assert!(grid.validate().is_ok());
+        assert!(grid.edges(0).iter().all(|edge| edge.is_finite()));
+
+        // Zero on most of the square, so most bins see no samples after the first iteration
+        let step = |x: &[f64]| if x[0] > 0.7 && x[1] < 0.4 { 1.0 } else { 0.0 };
+        let result = integrate_vegas(step, &[0.0, 0.0], &[1.0, 1.0], 10, 2000).unwrap();
+        assert!(result.grid.validate().is_ok());
+        assert!(result.variance.is_finite());
+        assert!((result.estimate - 0.12).abs() < 5.0 * result.variance.sqrt() + 1e-3);
+    }
+}
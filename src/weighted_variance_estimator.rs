@@ -0,0 +1,249 @@
+// Weighted variant of VarianceEstimator, using West's incremental update
+// D. H. D. West, "Updating mean and variance estimates: an improved method", 1979
+use crate::error::{MonteCarloError, Result};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct WeightedVarianceEstimator {
+    pub mean: f64,
+    weighted_sum_square_differences: f64,
+    weight_sum: f64,
+    weight_square_sum: f64,
+    sample_count: i64,
+}
+
+impl WeightedVarianceEstimator {
+    pub fn new() -> Self {
+        Self {
+            mean: 0.0,
+            weighted_sum_square_differences: 0.0,
+            weight_sum: 0.0,
+            weight_square_sum: 0.0,
+            sample_count: 0,
+        }
+    }
+
+    // Weights must be finite and nonnegative. A zero weight carries no information, so the sample
+    // is skipped without being counted; a negative or non-finite weight is an error and leaves the
+    // estimator unchanged
+    pub fn add_sample(&mut self, x: f64, weight: f64) -> Result<()> {
+        if weight < 0.0 || !weight.is_finite() {
+            return Err(MonteCarloError::InvalidParameter {
+                name: "weight",
+                value: weight,
+            });
+        }
+        if weight == 0.0 {
+            return Ok(());
+        }
+
+        self.sample_count += 1;
+        self.weight_sum += weight;
+        self.weight_square_sum += weight * weight;
+        let delta = x - self.mean;
+        self.mean += delta * weight / self.weight_sum;
+        let delta2 = x - self.mean;
+        self.weighted_sum_square_differences += weight * delta * delta2;
+        Ok(())
+    }
+
+    pub fn sample_count(&self) -> i64 {
+        self.sample_count
+    }
+
+    pub fn weight_sum(&self) -> f64 {
+        self.weight_sum
+    }
+
+    // Kish's effective sample size (sum w)^2 / sum w^2. It equals the sample count for equal
+    // weights, and shrinks as the weights become more skewed
+    pub fn effective_sample_size(&self) -> f64 {
+        if self.weight_square_sum > 0.0 {
+            self.weight_sum * self.weight_sum / self.weight_square_sum
+        } else {
+            0.0
+        }
+    }
+
+    // Unbiased variance for reliability weights. For equal weights, it reduces to the usual
+    // sample variance with the n - 1 denominator
+    pub fn variance(&self) -> f64 {
+        if self.sample_count <= 1 {
+            return 0.0;
+        }
+        let denominator = self.weight_sum - self.weight_square_sum / self.weight_sum;
+        if denominator > 0.0 {
+            self.weighted_sum_square_differences / denominator
+        } else {
+            0.0
+        }
+    }
+
+    // Standard error of the weighted mean, using the effective sample size instead of the raw
+    // sample count. None if the variance is undefined
+    pub fn std_error(&self) -> Option<f64> {
+        (self.sample_count >= 2).then(|| (self.variance() / self.effective_sample_size()).sqrt())
+    }
+
+    // Normal-approximation confidence interval `mean ± z * std_error`, e.g. z = 1.96 for 95%.
+    // None if the variance is undefined
+    pub fn confidence_interval(&self, z: f64) -> Option<(f64, f64)> {
+        self.std_error()
+            .map(|std_error| (self.mean - z * std_error, self.mean + z * std_error))
+    }
+
+    // Exponential forgetting: scale the past samples down to `factor` of their effective count,
+    // leaving the mean as is. Calling it before adding each new sample gives more influence to
+    // recent samples, which tracks a drifting integrand. The effective sample size and the sum of
+    // squared differences both shrink by `factor`, so the variance stays about the same while the
+    // variance of the mean grows
+    pub fn decay(&mut self, factor: f64) -> Result<()> {
+        if factor <= 0.0 || factor > 1.0 || factor.is_nan() {
+            return Err(MonteCarloError::InvalidParameter {
+                name: "decay factor",
+                value: factor,
+            });
+        }
+        self.weight_sum *= factor;
+        self.weight_square_sum *= factor;
+        self.weighted_sum_square_differences *= factor;
+        Ok(())
+    }
+
+    pub fn merge(lhs: Self, rhs: Self) -> Self {
+        if rhs.sample_count == 0 {
+            return lhs;
+        }
+        if lhs.sample_count == 0 {
+            return rhs;
+        }
+
+        let weight_sum = lhs.weight_sum + rhs.weight_sum;
+        let delta = rhs.mean - lhs.mean;
+        let mean = lhs.mean + delta * rhs.weight_sum / weight_sum;
+        let weighted_sum_square_differences = lhs.weighted_sum_square_differences
+            + rhs.weighted_sum_square_differences
+            + delta * delta * lhs.weight_sum * rhs.weight_sum / weight_sum;
+
+        Self {
+            mean,
+            weighted_sum_square_differences,
+            weight_sum,
+            weight_square_sum: lhs.weight_square_sum + rhs.weight_square_sum,
+            sample_count: lhs.sample_count + rhs.sample_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variance_estimator::VarianceEstimator;
+    use approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_equal_weights_match_unweighted() {
+        let mut weighted = WeightedVarianceEstimator::new();
+        let mut unweighted = VarianceEstimator::new();
+        (0..100).for_each(|i| {
+            weighted.add_sample(i as f64, 2.5).unwrap();
+            unweighted.add_sample(i as f64);
+        });
+
+        assert_approx_eq!(weighted.mean, unweighted.mean);
+        assert_approx_eq!(weighted.variance(), unweighted.variance());
+        assert_approx_eq!(weighted.effective_sample_size(), 100.0);
+        assert_approx_eq!(
+            weighted.std_error().unwrap(),
+            unweighted.std_error().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_weighted_mean() {
+        let mut ve = WeightedVarianceEstimator::new();
+        ve.add_sample(1.0, 1.0).unwrap();
+        ve.add_sample(4.0, 3.0).unwrap();
+        assert_approx_eq!(ve.mean, 3.25);
+        assert!(ve.std_error().is_some());
+
+        let mut single = WeightedVarianceEstimator::new();
+        single.add_sample(1.0, 1.0).unwrap();
+        assert_eq!(single.std_error(), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut all = WeightedVarianceEstimator::new();
+        let mut lhs = WeightedVarianceEstimator::new();
+        let mut rhs = WeightedVarianceEstimator::new();
+        (0..200).for_each(|i| {
+            let (x, w) = (i as f64, 1.0 + (i % 7) as f64);
+            all.add_sample(x, w).unwrap();
+            if i < 50 {
+                lhs.add_sample(x, w).unwrap();
+            } else {
+                rhs.add_sample(x, w).unwrap();
+            }
+        });
+
+        let merged = WeightedVarianceEstimator::merge(lhs, rhs);
+        assert_approx_eq!(merged.mean, all.mean);
+        assert_approx_eq!(merged.variance(), all.variance());
+        assert_approx_eq!(merged.effective_sample_size(), all.effective_sample_size());
+    }
+
+    #[test]
+    fn test_skewed_weights_widen_interval() {
+        // A few samples carry nearly all of the weight
+        let mut ve = WeightedVarianceEstimator::new();
+        (0..1000).for_each(|i| {
+            let weight = if i % 100 == 0 { 1000.0 } else { 1.0 };
+            ve.add_sample((i % 10) as f64, weight).unwrap();
+        });
+
+        let ess = ve.effective_sample_size();
+        assert!(ess < 20.0);
+
+        let (lo, hi) = ve.confidence_interval(1.96).unwrap();
+        let naive_half_width = 1.96 * (ve.variance() / ve.sample_count() as f64).sqrt();
+        assert!(hi - lo > 2.0 * naive_half_width * 5.0);
+        assert_approx_eq!((hi - lo) / (2.0 * naive_half_width), (1000.0 / ess).sqrt());
+    }
+
+    #[test]
+    fn test_decay() {
+        let mut ve = WeightedVarianceEstimator::new();
+        (0..100).for_each(|i| ve.add_sample(i as f64, 1.0).unwrap());
+        let before = ve;
+
+        ve.decay(0.25).unwrap();
+        assert_eq!(ve.mean, before.mean);
+        assert_approx_eq!(ve.effective_sample_size(), 25.0);
+        assert!(ve.std_error().unwrap() > before.std_error().unwrap());
+
+        // New samples now weigh more than the decayed ones
+        ve.add_sample(1000.0, 1.0).unwrap();
+        let mut undecayed = before;
+        undecayed.add_sample(1000.0, 1.0).unwrap();
+        assert!(ve.mean > undecayed.mean);
+
+        assert!(ve.decay(0.0).is_err());
+        assert!(ve.decay(1.5).is_err());
+    }
+
+    #[test]
+    fn test_invalid_weights() {
+        let mut ve = WeightedVarianceEstimator::new();
+        ve.add_sample(1.0, 1.0).unwrap();
+        let before = ve;
+
+        // A zero weight is skipped
+        ve.add_sample(100.0, 0.0).unwrap();
+        assert_eq!(ve, before);
+
+        for weight in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(ve.add_sample(100.0, weight).is_err());
+            assert_eq!(ve, before);
+        }
+    }
+}